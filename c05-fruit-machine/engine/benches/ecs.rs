@@ -0,0 +1,127 @@
+//Measures the cost of ticking and preparing to render scenes with many objects,
+//comparing GameObject components against systems running over the scene's World
+//Every Scene has a World, which can't be turned off, so the GameObject numbers include storing objects in it
+//Rendering is only measured up to culling and sorting, as draw calls need a window and a GPU,
+//so the cost of the draw calls themselves isn't included
+//Run with `cargo bench --bench ecs`
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use engine::{Camera, Frame, GameObject, GameObjectCallback, Mesh, Renderable, Scene, SceneCallbackDefault, Transform, Vec3, World, shaders::Unshaded3D};
+
+const OBJECT_COUNTS: [usize; 2] = [10_000, 50_000];
+const FRAMES: u32 = 100;
+///Objects are laid out in a square grid this far apart, in front of the camera
+const SPACING: f64 = 2.0;
+
+///Moves its object every tick, like a typical GameObject behaviour
+#[derive(Debug)]
+struct Drift {
+    velocity: Vec3,
+}
+
+impl GameObjectCallback for Drift {
+    fn on_tick(&mut self, object: &mut GameObject, frame: &Frame) {
+        object.transform.translate(self.velocity * frame.time.delta_time.as_secs_f64());
+    }
+}
+
+///Velocity of an entity, moved by a system instead of a GameObject component
+struct Velocity(Vec3);
+///Position of an entity that has no GameObject
+struct Position(Vec3);
+
+fn grid_position(i: usize, count: usize) -> Vec3 {
+    let side = (count as f64).sqrt().ceil() as usize;
+    Vec3::new(((i % side) as f64 - side as f64 / 2.0) * SPACING, ((i / side) as f64 - side as f64 / 2.0) * SPACING, 20.0)
+}
+
+fn velocity(i: usize) -> Vec3 {
+    Vec3::new((i % 7) as f64 - 3.0, (i % 5) as f64 - 2.0, 0.0)
+}
+
+fn new_scene() -> Scene {
+    Scene::new(Box::new(SceneCallbackDefault {}), Renderable::Camera(Camera::new(Transform::origin(), 3.0)))
+}
+
+fn new_object(i: usize, count: usize) -> GameObject {
+    let position = grid_position(i, count);
+    let mut object = GameObject::new(None, format!("Object {}", i), Transform::from_pos(position.x, position.y, position.z));
+    object.meshes.push((Transform::origin(), Mesh::cube(Box::new(Unshaded3D::new([1.0; 4])))));
+    object
+}
+
+///Scene where every object moves itself with a boxed component
+fn component_scene(count: usize) -> Scene {
+    let mut scene = new_scene();
+    for i in 0..count {
+        scene.add_object(new_object(i, count).with_component(Box::new(Drift { velocity: velocity(i) })));
+    }
+    scene
+}
+
+///Scene where one system moves every object, using a Velocity stored next to each GameObject
+fn system_scene(count: usize) -> Scene {
+    let mut scene = new_scene();
+    for i in 0..count {
+        let entity = scene.add_object(new_object(i, count));
+        scene.get_world_mut().insert(entity, Velocity(velocity(i))).ok();
+    }
+    scene.add_system(|world: &mut World, frame: &Frame| {
+        let delta = frame.time.delta_time.as_secs_f64();
+        world.join_mut::<Velocity, GameObject, _>(|_, velocity, object| object.transform.translate(velocity.0 * delta));
+    });
+    scene
+}
+
+///Scene with no GameObjects, only plain components moved by a system
+fn plain_scene(count: usize) -> Scene {
+    let mut scene = new_scene();
+    let world = scene.get_world_mut();
+    for i in 0..count {
+        let entity = world.spawn();
+        world.insert(entity, Position(grid_position(i, count))).ok();
+        world.insert(entity, Velocity(velocity(i))).ok();
+    }
+    scene.add_system(|world: &mut World, frame: &Frame| {
+        let delta = frame.time.delta_time.as_secs_f64();
+        world.join_mut::<Velocity, Position, _>(|_, velocity, position| position.0 += velocity.0 * delta);
+    });
+    scene
+}
+
+fn time<F: FnMut()>(name: &str, count: usize, mut f: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        f();
+    }
+    let elapsed = start.elapsed() / FRAMES;
+    println!("{:<32} {:>8} objects {:>10.3} ms/frame", name, count, elapsed.as_secs_f64() * 1000.0);
+    elapsed
+}
+
+fn main() {
+    let mut frame = Frame::headless((1920, 1080));
+    frame.time.delta_time = Duration::from_secs_f64(1.0 / 60.0);
+
+    for count in OBJECT_COUNTS {
+        let mut scene = component_scene(count);
+        let components = time("tick GameObject components", count, || scene.tick(black_box(&frame)));
+
+        let mut scene = system_scene(count);
+        let systems = time("tick system over GameObjects", count, || scene.tick(black_box(&frame)));
+
+        let mut scene = plain_scene(count);
+        time("tick system over components", count, || scene.tick(black_box(&frame)));
+
+        println!("system speedup over GameObject components: {:.1}x", components.as_secs_f64() / systems.as_secs_f64());
+
+        //Culling and sorting is the part of rendering that grows with the object count before any draw calls
+        let scene = component_scene(count);
+        let mut camera = Camera::new(Transform::origin(), 3.0);
+        let mut visible = 0;
+        time("find visible meshes", count, || visible = black_box(camera.find_visible_meshes(&scene, 9.0 / 16.0)).len());
+        println!("{} of {} meshes visible\n", visible, count);
+    }
+}
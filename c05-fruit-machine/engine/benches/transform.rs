@@ -0,0 +1,77 @@
+//Compares the stack allocated Transform against the old ndarray version
+//Run with `cargo bench --bench transform`
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use engine::Transform;
+use ndarray::{arr2, Array2};
+
+const ITERATIONS: u32 = 1_000_000;
+
+///The Transform implementation before it was replaced, kept as a reference point
+#[derive(Clone)]
+struct OldTransform {
+    matrix: Array2<f64>,
+}
+
+impl OldTransform {
+    fn from_pos(x: f64, y: f64, z: f64) -> Self {
+        OldTransform { matrix: arr2(&[
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [x, y, z, 1.0]
+        ])}
+    }
+
+    fn from_scale(x: f64, y: f64, z: f64) -> Self {
+        OldTransform { matrix: arr2(&[
+            [x, 0.0, 0.0, 0.0],
+            [0.0, y, 0.0, 0.0],
+            [0.0, 0.0, z, 0.0],
+            [0.0, 0.0, 0.0, 1.0]
+        ])}
+    }
+
+    fn to_array(&self) -> [[f32; 4]; 4] {
+        let mut out = [[0.0; 4]; 4];
+        for (i, x) in self.matrix.iter().enumerate() {
+            out[i / 4][i % 4] = *x as f32;
+        }
+        out
+    }
+}
+
+impl std::ops::Mul for OldTransform {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        OldTransform { matrix: self.matrix.dot(&rhs.matrix) }
+    }
+}
+
+fn time<F: FnMut(u32)>(name: &str, mut f: F) -> Duration {
+    let start = Instant::now();
+    for i in 0..ITERATIONS {
+        f(i);
+    }
+    let elapsed = start.elapsed();
+    println!("{:<24} {:>10.2} ns/iter", name, elapsed.as_nanos() as f64 / ITERATIONS as f64);
+    elapsed
+}
+
+fn main() {
+    let old = time("ndarray multiply", |i| {
+        let a = OldTransform::from_scale(2.0, 2.0, 1.0);
+        let b = OldTransform::from_pos(i as f64, 1.0, 0.0);
+        black_box((black_box(a) * black_box(b)).to_array());
+    });
+
+    let new = time("Transform multiply", |i| {
+        let a = Transform::from_scale(2.0, 2.0, 1.0);
+        let b = Transform::from_pos(i as f64, 1.0, 0.0);
+        black_box((black_box(a) * black_box(b)).to_array());
+    });
+
+    println!("speedup: {:.1}x", old.as_secs_f64() / new.as_secs_f64());
+}
@@ -0,0 +1,749 @@
+use std::cell::RefCell;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::rc::Weak;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::MeshData;
+use crate::ObjLoadError;
+use crate::{Sound, SoundLoadError};
+use crate::shaders::Texture;
+use crate::shaders::shader_priv::{TexturePriv, TextureLoadError};
+
+use crate::AssetPack;
+
+use self::assets_priv::{AssetData, AssetPriv, AssetStore, LoadJob, LoadResult};
+
+pub(crate) mod assets_priv {
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use crate::MeshData;
+    use crate::ObjLoadError;
+    use crate::{Sound, SoundLoadError};
+    use crate::shaders::Texture;
+    use crate::shaders::shader_priv::{TextureLoadError, TextureSource};
+
+    use super::{Assets, AssetLoadError, LoadState};
+
+    ///Where the loader thread should read an asset from
+    #[derive(Debug, Clone)]
+    pub enum AssetData {
+        File(PathBuf),
+        ///The contents of a file in a mounted pack
+        Bytes(Arc<[u8]>),
+    }
+
+    ///A request sent to the loader thread
+    #[derive(Clone)]
+    pub enum LoadJob {
+        Texture(usize, AssetData),
+        Mesh(usize, AssetData),
+        Sound(usize, AssetData),
+        ///Turns watching loaded files for changes on or off
+        Watch(bool),
+    }
+
+    ///The result of a request, sent back from the loader thread
+    pub enum LoadResult {
+        Texture(usize, Result<image::RgbaImage, TextureLoadError>),
+        Mesh(usize, Result<MeshData, ObjLoadError>),
+        Sound(usize, Result<Sound, SoundLoadError>),
+    }
+
+    impl LoadJob {
+        ///Loads the file requested by the job
+        ///Returns None for jobs that do not load anything
+        pub fn run(&self) -> Option<LoadResult> {
+            match self {
+                LoadJob::Texture(id, data) => Some(LoadResult::Texture(*id, Texture::decode(&data.texture_source()))),
+                LoadJob::Mesh(id, AssetData::File(path)) => Some(LoadResult::Mesh(*id, MeshData::from_obj(&path.to_string_lossy()))),
+                LoadJob::Mesh(id, AssetData::Bytes(bytes)) => Some(LoadResult::Mesh(*id, MeshData::from_obj_bytes(bytes))),
+                LoadJob::Sound(id, AssetData::File(path)) => Some(LoadResult::Sound(*id, Sound::new(&path.to_string_lossy()))),
+                LoadJob::Sound(id, AssetData::Bytes(bytes)) => Some(LoadResult::Sound(*id, Sound::from_bytes(bytes))),
+                LoadJob::Watch(_) => None,
+            }
+        }
+
+        ///Gets the path of the file the job loads, if it loads from a file
+        pub fn path(&self) -> Option<&PathBuf> {
+            match self {
+                LoadJob::Texture(_, AssetData::File(path))
+                | LoadJob::Mesh(_, AssetData::File(path))
+                | LoadJob::Sound(_, AssetData::File(path)) => Some(path),
+                _ => None,
+            }
+        }
+    }
+
+    impl AssetData {
+        pub fn texture_source(&self) -> TextureSource {
+            match self {
+                AssetData::File(path) => TextureSource::Path(path.to_string_lossy().to_string()),
+                AssetData::Bytes(bytes) => TextureSource::Bytes(bytes.clone()),
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct AssetEntry<T> {
+        pub path: PathBuf,
+        pub data: AssetData,
+        pub state: LoadState,
+        pub asset: Option<T>,
+        pub error: Option<AssetLoadError>,
+    }
+
+    ///Storage for every asset of one type
+    #[derive(Debug)]
+    pub struct AssetStore<T> {
+        pub paths: HashMap<PathBuf, usize>,
+        pub entries: Vec<AssetEntry<T>>,
+    }
+
+    impl<T> Default for AssetStore<T> {
+        fn default() -> Self {
+            AssetStore {
+                paths: HashMap::new(),
+                entries: Vec::new(),
+            }
+        }
+    }
+
+    pub trait AssetPriv: Sized + 'static {
+        fn store(assets: &Assets) -> &AssetStore<Self>;
+        fn store_mut(assets: &mut Assets) -> &mut AssetStore<Self>;
+        fn job(id: usize, data: AssetData) -> LoadJob;
+    }
+}
+
+///A type that can be loaded by Assets
+pub trait Asset: AssetPriv {}
+
+///A typed reference to an asset in an Assets registry
+pub struct Handle<T: Asset> {
+    id: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Asset> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T: Asset> Copy for Handle<T> {}
+
+impl<T: Asset> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+impl<T: Asset> Eq for Handle<T> {}
+
+impl<T: Asset> Hash for Handle<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl<T: Asset> Debug for Handle<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Handle({})", self.id)
+    }
+}
+
+///The state of an asset being loaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadState {
+    ///The asset is being loaded on the loader thread, or is waiting to be uploaded to the gpu
+    Loading,
+    ///The asset is ready to use
+    Loaded,
+    ///The asset could not be loaded
+    ///The error can be retrieved with Assets::get_error
+    Failed,
+}
+
+///Represents an error that can occur while loading an asset
+#[derive(Debug)]
+pub enum AssetLoadError {
+    Texture(TextureLoadError),
+    Mesh(ObjLoadError),
+    Sound(SoundLoadError),
+    ///The loader thread has stopped, such as after a decoder panicked, so no more assets can be loaded
+    LoaderStopped,
+}
+
+///How often the loader thread checks watched files for changes
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+///Runs on the loader thread, loading files as jobs arrive
+///While watching, also polls every loaded file and loads it again when it changes on disk
+///Results are sent with a flag which is true if they are reloads rather than requested loads
+fn loader_thread(jobs: Receiver<LoadJob>, results: Sender<(LoadResult, bool)>) {
+    let mut watching = false;
+    //Every job that has been run, with the modification time of its file when it was loaded
+    let mut watched: Vec<(LoadJob, Option<SystemTime>)> = Vec::new();
+
+    let modified = |path: &PathBuf| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+
+    loop {
+        let job = if watching {
+            match jobs.recv_timeout(WATCH_INTERVAL) {
+                Ok(j) => Some(j),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => return
+            }
+        }
+        else {
+            match jobs.recv() {
+                Ok(j) => Some(j),
+                Err(_) => return
+            }
+        };
+
+        match job {
+            Some(LoadJob::Watch(w)) => watching = w,
+            Some(job) => {
+                //Read the time before loading, so a change made while the file loads is still noticed
+                //Only files on disk can change, so assets from packs are not watched
+                let time = job.path().map(modified);
+                if let Some(result) = job.run() {
+                    if results.send((result, false)).is_err() {
+                        return;
+                    }
+                }
+                if let Some(time) = time {
+                    watched.push((job, time));
+                }
+            },
+            //Timed out waiting for a job, so check for changed files
+            None => {
+                for (job, time) in watched.iter_mut() {
+                    let new_time = modified(job.path().unwrap());
+                    if new_time != *time {
+                        *time = new_time;
+                        if let Some(result) = job.run() {
+                            if results.send((result, true)).is_err() {
+                                return;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+///A registry of assets loaded from files
+///
+///Files are loaded on a background thread, and textures are uploaded to the gpu on the main thread
+///at the start of the next frame. Loading the same path twice returns the same handle.
+///
+///Mounted packs are searched for a path before the asset root on disk.
+#[derive(Debug)]
+pub struct Assets {
+    root: PathBuf,
+    packs: Vec<AssetPack>,
+
+    textures: AssetStore<Texture>,
+    meshes: AssetStore<MeshData>,
+    sounds: AssetStore<Sound>,
+
+    ///Number of jobs sent to the loader thread that have not been processed yet
+    pending: usize,
+    jobs: Sender<LoadJob>,
+    results: Receiver<(LoadResult, bool)>,
+
+    hot_reload: bool,
+    ///Meshes that have been reloaded since the window last checked
+    reloaded_meshes: Vec<Handle<MeshData>>,
+    ///Errors from reloads since the window last checked
+    reload_errors: Vec<AssetLoadError>,
+}
+
+impl Assets {
+    ///Creates a new registry, with paths resolved relative to root
+    pub fn new(root: PathBuf) -> Self {
+        let (jobs, job_receiver) = channel::<LoadJob>();
+        let (result_sender, results) = channel::<(LoadResult, bool)>();
+
+        //Thread exits when the job sender is dropped along with the registry
+        thread::spawn(move || loader_thread(job_receiver, result_sender));
+
+        Assets {
+            root,
+            packs: Vec::new(),
+            textures: Default::default(),
+            meshes: Default::default(),
+            sounds: Default::default(),
+            pending: 0,
+            jobs,
+            results,
+
+            hot_reload: false,
+            reloaded_meshes: Vec::new(),
+            reload_errors: Vec::new(),
+        }
+    }
+
+    ///Sets whether loaded files are watched for changes
+    ///Changed files are reloaded into their existing textures and meshes at the start of the next frame
+    ///If the loader thread has stopped, hot reloading stays off and the error is reported like a failed reload
+    pub fn set_hot_reload(&mut self, enabled: bool) {
+        match self.jobs.send(LoadJob::Watch(enabled)) {
+            Ok(()) => self.hot_reload = enabled,
+            Err(_) => {
+                self.hot_reload = false;
+                self.reload_errors.push(AssetLoadError::LoaderStopped);
+            }
+        }
+    }
+
+    ///Gets whether loaded files are watched for changes
+    pub fn get_hot_reload(&self) -> bool {
+        self.hot_reload
+    }
+
+    ///Gets the directory that asset paths are resolved relative to
+    pub fn get_root(&self) -> &Path {
+        &self.root
+    }
+
+    ///Sets the directory that asset paths are resolved relative to
+    ///Only affects assets loaded after the call
+    pub fn set_root(&mut self, root: PathBuf) {
+        self.root = root;
+    }
+
+    ///Resolves a path relative to the asset root
+    pub fn resolve(&self, path: &str) -> PathBuf {
+        self.root.join(path)
+    }
+
+    ///Mounts a pack so that its files are loaded instead of files on disk
+    ///Packs mounted later take priority over earlier ones
+    pub fn mount(&mut self, pack: AssetPack) {
+        self.packs.push(pack);
+    }
+
+    ///Gets the contents of a file from the mounted packs, or from disk if no pack contains it
+    ///Useful for files that are not loaded as assets, such as the window icon
+    pub fn read(&self, path: &str) -> Result<Vec<u8>, std::io::Error> {
+        for pack in self.packs.iter().rev() {
+            if let Some(bytes) = pack.get(path) {
+                return Ok(bytes.to_vec());
+            }
+        }
+        std::fs::read(self.resolve(path))
+    }
+
+    ///Starts loading an asset, or returns the existing handle if the path has already been loaded
+    ///The asset fails with AssetLoadError::LoaderStopped if the loader thread has stopped
+    pub fn load<T: Asset>(&mut self, name: &str) -> Handle<T> {
+        let path = self.resolve(name);
+
+        if let Some(id) = T::store(self).paths.get(&path) {
+            return Handle { id: *id, _marker: PhantomData };
+        }
+
+        let data = match self.packs.iter().rev().find_map(|p| p.get_shared(name)) {
+            Some(bytes) => AssetData::Bytes(bytes),
+            None => AssetData::File(path.clone())
+        };
+
+        let store = T::store_mut(self);
+        let id = store.entries.len();
+        store.paths.insert(path.clone(), id);
+        store.entries.push(assets_priv::AssetEntry {
+            path,
+            data: data.clone(),
+            state: LoadState::Loading,
+            asset: None,
+            error: None,
+        });
+
+        match self.jobs.send(T::job(id, data)) {
+            Ok(()) => self.pending += 1,
+            Err(_) => {
+                let entry = &mut T::store_mut(self).entries[id];
+                entry.state = LoadState::Failed;
+                entry.error = Some(AssetLoadError::LoaderStopped);
+            }
+        }
+
+        Handle { id, _marker: PhantomData }
+    }
+
+    ///Starts loading a texture
+    pub fn load_texture(&mut self, path: &str) -> Handle<Texture> {
+        self.load(path)
+    }
+
+    ///Starts loading an obj file
+    pub fn load_mesh(&mut self, path: &str) -> Handle<MeshData> {
+        self.load(path)
+    }
+
+    ///Starts loading a wav or ogg file
+    pub fn load_sound(&mut self, path: &str) -> Handle<Sound> {
+        self.load(path)
+    }
+
+    ///Gets an asset if it has finished loading
+    pub fn get<T: Asset>(&self, handle: Handle<T>) -> Option<&T> {
+        T::store(self).entries[handle.id].asset.as_ref()
+    }
+
+    ///Gets the load state of an asset
+    pub fn get_load_state<T: Asset>(&self, handle: Handle<T>) -> LoadState {
+        T::store(self).entries[handle.id].state
+    }
+
+    ///Gets the error that caused an asset to fail to load
+    pub fn get_error<T: Asset>(&self, handle: Handle<T>) -> Option<&AssetLoadError> {
+        T::store(self).entries[handle.id].error.as_ref()
+    }
+
+    ///Gets the resolved path an asset was loaded from
+    pub fn get_path<T: Asset>(&self, handle: Handle<T>) -> &Path {
+        &T::store(self).entries[handle.id].path
+    }
+
+    ///Gets the handle of an asset that has already been loaded with a name
+    pub fn find<T: Asset>(&self, name: &str) -> Option<Handle<T>> {
+        T::store(self).paths.get(&self.resolve(name)).map(|id| Handle { id: *id, _marker: PhantomData })
+    }
+
+    ///Gets the name an asset was loaded with, relative to the asset root
+    ///None if it was loaded from outside of the root
+    pub fn get_name<T: Asset>(&self, handle: Handle<T>) -> Option<String> {
+        self.name_of(self.get_path(handle))
+    }
+
+    ///Gets the name of the asset a texture was loaded from, if it was loaded by this registry
+    pub(crate) fn find_texture_name(&self, texture: &Texture) -> Option<String> {
+        let entry = self.textures.entries.iter().find(|e| e.asset.as_ref().is_some_and(|t| t.ptr_eq(texture)))?;
+        self.name_of(&entry.path)
+    }
+
+    fn name_of(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        let parts: Vec<_> = relative.components().map(|c| c.as_os_str().to_string_lossy()).collect();
+        Some(parts.join("/"))
+    }
+
+    ///Returns true if no assets are waiting to be loaded
+    pub fn is_idle(&self) -> bool {
+        self.pending == 0
+    }
+
+    ///Handles results from the loader thread without blocking
+    ///Textures are uploaded to the gpu here, so this must be called on the main thread
+    pub(crate) fn process(&mut self, display: &glium::Display, registry: &RefCell<Vec<Weak<TexturePriv>>>) {
+        self.receive(false, Some((display, registry)));
+    }
+
+    ///Blocks until every pending asset has been loaded
+    pub(crate) fn wait(&mut self, display: &glium::Display, registry: &RefCell<Vec<Weak<TexturePriv>>>) {
+        self.receive(true, Some((display, registry)));
+    }
+
+    ///Handles results from the loader thread, blocking until nothing is pending if block is set
+    ///Textures can only be handled with a display, which tests that don't load textures go without
+    fn receive(&mut self, block: bool, gpu: Option<(&glium::Display, &RefCell<Vec<Weak<TexturePriv>>>)>) {
+        loop {
+            let received = match block {
+                true if self.pending == 0 => return,
+                true => self.results.recv().map_err(|_| TryRecvError::Disconnected),
+                false => self.results.try_recv()
+            };
+            match received {
+                Ok((result, reload)) => self.handle_result(result, reload, gpu),
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => {
+                    self.loader_stopped();
+                    return;
+                }
+            }
+        }
+    }
+
+    ///Fails every asset still waiting for the loader thread, as it has stopped
+    fn loader_stopped(&mut self) {
+        self.pending = 0;
+        fn fail<T>(store: &mut AssetStore<T>) {
+            for entry in store.entries.iter_mut().filter(|e| e.state == LoadState::Loading) {
+                entry.state = LoadState::Failed;
+                entry.error = Some(AssetLoadError::LoaderStopped);
+            }
+        }
+        fail(&mut self.textures);
+        fail(&mut self.meshes);
+        fail(&mut self.sounds);
+    }
+
+    ///Takes the meshes that have been reloaded since the last call
+    pub(crate) fn take_reloaded_meshes(&mut self) -> Vec<Handle<MeshData>> {
+        std::mem::take(&mut self.reloaded_meshes)
+    }
+
+    ///Takes the errors from reloads since the last call
+    pub(crate) fn take_reload_errors(&mut self) -> Vec<AssetLoadError> {
+        std::mem::take(&mut self.reload_errors)
+    }
+
+    fn handle_result(&mut self, result: LoadResult, reload: bool, gpu: Option<(&glium::Display, &RefCell<Vec<Weak<TexturePriv>>>)>) {
+        if !reload {
+            self.pending -= 1;
+        }
+        match result {
+            LoadResult::Texture(id, image) => {
+                let Some((display, registry)) = gpu else {
+                    return;
+                };
+                let entry = &mut self.textures.entries[id];
+                //Reuse the existing texture if there is one, so that everything sharing it sees the change
+                let texture = match (image, &entry.asset) {
+                    (Ok(image), Some(t)) => t.replace_image(image, display).map(|_| t.clone()),
+                    (Ok(image), None) => Texture::from_image(entry.data.texture_source(), image, display, registry),
+                    (Err(e), _) => Err(e)
+                };
+                match texture {
+                    Ok(t) => {
+                        entry.asset = Some(t);
+                        entry.state = LoadState::Loaded;
+                        entry.error = None;
+                    },
+                    //A failed reload keeps the previously loaded texture
+                    Err(e) if reload => self.reload_errors.push(AssetLoadError::Texture(e)),
+                    Err(e) => {
+                        entry.error = Some(AssetLoadError::Texture(e));
+                        entry.state = LoadState::Failed;
+                    }
+                }
+            },
+            LoadResult::Mesh(id, data) => {
+                let entry = &mut self.meshes.entries[id];
+                match data {
+                    Ok(d) => {
+                        entry.asset = Some(d);
+                        entry.state = LoadState::Loaded;
+                        entry.error = None;
+                        if reload {
+                            self.reloaded_meshes.push(Handle { id, _marker: PhantomData });
+                        }
+                    },
+                    //A failed reload keeps the previously loaded mesh
+                    Err(e) if reload => self.reload_errors.push(AssetLoadError::Mesh(e)),
+                    Err(e) => {
+                        entry.error = Some(AssetLoadError::Mesh(e));
+                        entry.state = LoadState::Failed;
+                    }
+                }
+            },
+            LoadResult::Sound(id, sound) => {
+                let entry = &mut self.sounds.entries[id];
+                match sound {
+                    //Sounds that are already playing keep their old data
+                    Ok(s) => {
+                        entry.asset = Some(s);
+                        entry.state = LoadState::Loaded;
+                        entry.error = None;
+                    },
+                    Err(e) if reload => self.reload_errors.push(AssetLoadError::Sound(e)),
+                    Err(e) => {
+                        entry.error = Some(AssetLoadError::Sound(e));
+                        entry.state = LoadState::Failed;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Default for Assets {
+    ///Creates a registry with paths resolved relative to the directory containing the executable
+    fn default() -> Self {
+        let root = match std::env::current_exe() {
+            Ok(p) => p.parent().map(|p| p.to_path_buf()).unwrap_or_default(),
+            Err(_) => PathBuf::new()
+        };
+        Assets::new(root)
+    }
+}
+
+impl AssetPriv for Texture {
+    fn store(assets: &Assets) -> &AssetStore<Self> {
+        &assets.textures
+    }
+    fn store_mut(assets: &mut Assets) -> &mut AssetStore<Self> {
+        &mut assets.textures
+    }
+    fn job(id: usize, data: AssetData) -> LoadJob {
+        LoadJob::Texture(id, data)
+    }
+}
+impl Asset for Texture {}
+
+impl AssetPriv for MeshData {
+    fn store(assets: &Assets) -> &AssetStore<Self> {
+        &assets.meshes
+    }
+    fn store_mut(assets: &mut Assets) -> &mut AssetStore<Self> {
+        &mut assets.meshes
+    }
+    fn job(id: usize, data: AssetData) -> LoadJob {
+        LoadJob::Mesh(id, data)
+    }
+}
+impl Asset for MeshData {}
+
+impl AssetPriv for Sound {
+    fn store(assets: &Assets) -> &AssetStore<Self> {
+        &assets.sounds
+    }
+    fn store_mut(assets: &mut Assets) -> &mut AssetStore<Self> {
+        &mut assets.sounds
+    }
+    fn job(id: usize, data: AssetData) -> LoadJob {
+        LoadJob::Sound(id, data)
+    }
+}
+impl Asset for Sound {}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::time::Instant;
+
+    use super::*;
+
+    ///Creates the contents of a mono wav file with a number of silent samples
+    fn wav(samples: usize) -> Vec<u8> {
+        let spec = hound::WavSpec { channels: 1, sample_rate: 1000, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
+        let mut bytes = Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut bytes, spec).unwrap();
+        for _ in 0..samples {
+            writer.write_sample(0i16).unwrap();
+        }
+        writer.finalize().unwrap();
+        bytes.into_inner()
+    }
+
+    ///Creates an empty directory for a test's files
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("engine-assets-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn loads_from_packs() {
+        let mut pack = AssetPack::new();
+        pack.insert("sounds/tick.wav", &wav(500));
+        pack.insert("sounds/broken.wav", b"not a sound");
+        let mut assets = Assets::new(PathBuf::from("root"));
+        assets.mount(pack);
+
+        let tick = assets.load_sound("sounds/tick.wav");
+        let broken = assets.load_sound("sounds/broken.wav");
+        assert_eq!(assets.load_sound("sounds/tick.wav"), tick);
+        assert_eq!(assets.get_load_state(tick), LoadState::Loading);
+        assert!(!assets.is_idle());
+
+        assets.receive(true, None);
+        assert!(assets.is_idle());
+        assert_eq!(assets.get_load_state(tick), LoadState::Loaded);
+        assert_eq!(assets.get(tick).unwrap().duration(), Duration::from_millis(500));
+        assert_eq!(assets.find::<Sound>("sounds/tick.wav"), Some(tick));
+        assert_eq!(assets.get_name(tick).as_deref(), Some("sounds/tick.wav"));
+
+        assert_eq!(assets.get_load_state(broken), LoadState::Failed);
+        assert!(assets.get(broken).is_none());
+        assert!(matches!(assets.get_error(broken), Some(AssetLoadError::Sound(SoundLoadError::UnsupportedFormat))));
+    }
+
+    #[test]
+    fn missing_files_fail() {
+        let mut assets = Assets::new(test_dir("missing"));
+        let missing = assets.load_sound("missing.wav");
+        assets.receive(true, None);
+        assert_eq!(assets.get_load_state(missing), LoadState::Failed);
+        assert!(matches!(assets.get_error(missing), Some(AssetLoadError::Sound(SoundLoadError::Io(_)))));
+    }
+
+    #[test]
+    fn hot_reload_replaces_changed_files() {
+        let dir = test_dir("reload");
+        let path = dir.join("loop.wav");
+        std::fs::write(&path, wav(100)).unwrap();
+
+        let mut assets = Assets::new(dir.clone());
+        let sound = assets.load_sound("loop.wav");
+        assets.set_hot_reload(true);
+        assets.receive(true, None);
+        assert_eq!(assets.get(sound).unwrap().duration(), Duration::from_millis(100));
+
+        //Wait for the loader thread to notice each change
+        //Each file is written in full with its time set before being moved into place, so the loader only sees one change
+        let staged = dir.join("loop.wav.tmp");
+        let wait_for_change = |assets: &mut Assets, bytes: &[u8], modified: SystemTime| {
+            std::fs::write(&staged, bytes).unwrap();
+            std::fs::File::options().write(true).open(&staged).unwrap().set_modified(modified).unwrap();
+            std::fs::rename(&staged, &path).unwrap();
+            let start = Instant::now();
+            while start.elapsed() < WATCH_INTERVAL * 10 {
+                assets.receive(false, None);
+                if assets.get(sound).unwrap().duration() != Duration::from_millis(100) || !assets.reload_errors.is_empty() {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+        };
+
+        //A broken file keeps the previous sound and reports the error
+        wait_for_change(&mut assets, b"not a sound", SystemTime::UNIX_EPOCH + Duration::from_secs(1000));
+        assert!(matches!(assets.take_reload_errors()[..], [AssetLoadError::Sound(_)]));
+        assert_eq!(assets.get_load_state(sound), LoadState::Loaded);
+
+        wait_for_change(&mut assets, &wav(200), SystemTime::UNIX_EPOCH + Duration::from_secs(2000));
+        assert_eq!(assets.get(sound).unwrap().duration(), Duration::from_millis(200));
+        assert!(assets.take_reload_errors().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn stopped_loader_fails_assets() {
+        let mut pack = AssetPack::new();
+        pack.insert("a.wav", &wav(10));
+        pack.insert("b.wav", &wav(10));
+        let mut assets = Assets::new(PathBuf::from("root"));
+        assets.mount(pack);
+
+        //Stands in for a loader thread that panicked before sending its result
+        let (jobs, job_receiver) = channel();
+        let (_, results) = channel();
+        assets.jobs = jobs;
+        assets.results = results;
+        let a = assets.load_sound("a.wav");
+        drop(job_receiver);
+        let b = assets.load_sound("b.wav");
+        assert_eq!(assets.get_load_state(b), LoadState::Failed);
+        assert!(matches!(assets.get_error(b), Some(AssetLoadError::LoaderStopped)));
+
+        assets.receive(true, None);
+        assert!(assets.is_idle());
+        assert!(matches!(assets.get_error(a), Some(AssetLoadError::LoaderStopped)));
+
+        assets.set_hot_reload(true);
+        assert!(!assets.get_hot_reload());
+        assert!(matches!(assets.take_reload_errors()[..], [AssetLoadError::LoaderStopped]));
+    }
+}
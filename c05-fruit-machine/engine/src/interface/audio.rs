@@ -0,0 +1,550 @@
+use std::fmt::Debug;
+use std::io::Cursor;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+///Sample rate that sounds are mixed at
+pub const MIX_SAMPLE_RATE: u32 = 44100;
+///Number of channels that sounds are mixed into
+pub const MIX_CHANNELS: u16 = 2;
+
+#[derive(Debug)]
+pub enum SoundLoadError {
+    Io(std::io::Error),
+    ///The file is not a wav or ogg file
+    UnsupportedFormat,
+    InvalidFile
+}
+
+#[derive(Debug)]
+struct SoundData {
+    ///Interleaved samples in the range -1..1
+    samples: Vec<f32>,
+    channels: u16,
+    sample_rate: u32,
+}
+
+///Decoded audio data that can be played any number of times
+///Cloning a sound does not copy its data
+#[derive(Debug, Clone)]
+pub struct Sound(Arc<SoundData>);
+
+impl Sound {
+    ///Loads a wav or ogg file
+    pub fn new(path: &str) -> Result<Self, SoundLoadError> {
+        match std::fs::read(path) {
+            Ok(bytes) => Sound::from_bytes(&bytes),
+            Err(e) => Err(SoundLoadError::Io(e))
+        }
+    }
+
+    ///Loads a sound from the contents of a wav or ogg file
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SoundLoadError> {
+        if bytes.starts_with(b"RIFF") {
+            Sound::from_wav(bytes)
+        }
+        else if bytes.starts_with(b"OggS") {
+            Sound::from_ogg(bytes)
+        }
+        else {
+            Err(SoundLoadError::UnsupportedFormat)
+        }
+    }
+
+    ///Creates a sound from interleaved samples in the range -1..1
+    pub fn from_samples(samples: Vec<f32>, channels: u16, sample_rate: u32) -> Self {
+        Sound(Arc::new(SoundData {
+            samples,
+            channels: channels.max(1),
+            sample_rate,
+        }))
+    }
+
+    fn from_wav(bytes: &[u8]) -> Result<Self, SoundLoadError> {
+        let mut reader = match hound::WavReader::new(Cursor::new(bytes)) {
+            Ok(r) => r,
+            Err(_) => return Err(SoundLoadError::InvalidFile)
+        };
+        let spec = reader.spec();
+
+        let samples: Result<Vec<f32>, hound::Error> = match spec.sample_format {
+            hound::SampleFormat::Float => reader.samples::<f32>().collect(),
+            hound::SampleFormat::Int => {
+                let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+                reader.samples::<i32>().map(|s| s.map(|s| s as f32 / scale)).collect()
+            }
+        };
+
+        match samples {
+            Ok(s) => Ok(Sound::from_samples(s, spec.channels, spec.sample_rate)),
+            Err(_) => Err(SoundLoadError::InvalidFile)
+        }
+    }
+
+    fn from_ogg(bytes: &[u8]) -> Result<Self, SoundLoadError> {
+        let mut reader = match lewton::inside_ogg::OggStreamReader::new(Cursor::new(bytes)) {
+            Ok(r) => r,
+            Err(_) => return Err(SoundLoadError::InvalidFile)
+        };
+
+        let mut samples = Vec::new();
+        loop {
+            match reader.read_dec_packet_itl() {
+                Ok(Some(packet)) => samples.extend(packet.iter().map(|s| *s as f32 / 32768.0)),
+                Ok(None) => break,
+                Err(_) => return Err(SoundLoadError::InvalidFile)
+            }
+        }
+
+        Ok(Sound::from_samples(samples, reader.ident_hdr.audio_channels as u16, reader.ident_hdr.audio_sample_rate))
+    }
+
+    ///Gets the length of the sound when played at normal pitch
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.frames() as f64 / self.0.sample_rate as f64)
+    }
+
+    fn frames(&self) -> usize {
+        self.0.samples.len() / self.0.channels as usize
+    }
+
+    ///Gets a sample, mapping output channels onto the sound's channels
+    fn sample(&self, frame: usize, channel: u16) -> f32 {
+        let channel = channel.min(self.0.channels - 1) as usize;
+        self.0.samples[frame * self.0.channels as usize + channel]
+    }
+}
+
+///A group of sounds whose volume can be set together
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Bus(usize);
+
+impl Bus {
+    ///The bus every other bus feeds into
+    ///Its volume applies to every sound
+    pub const MASTER: Bus = Bus(0);
+}
+
+///Refers to a sound that has been played, to change it while it is playing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SoundHandle(u64);
+
+///Settings for playing a sound
+#[derive(Debug, Clone, Copy)]
+pub struct PlayParams {
+    pub volume: f32,
+    ///Playback speed, where 1.0 is normal and 2.0 is an octave higher
+    pub pitch: f32,
+    pub looping: bool,
+    pub bus: Bus,
+}
+
+impl Default for PlayParams {
+    fn default() -> Self {
+        PlayParams {
+            volume: 1.0,
+            pitch: 1.0,
+            looping: false,
+            bus: Bus::MASTER,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Voice {
+    handle: SoundHandle,
+    sound: Sound,
+    ///Position in the sound in frames, fractional because of pitch
+    position: f64,
+    params: PlayParams,
+}
+
+///Mixes every playing sound together
+#[derive(Debug)]
+pub(crate) struct Mixer {
+    voices: Vec<Voice>,
+    ///Volume of each bus, indexed by bus number
+    buses: Vec<f32>,
+    next_handle: u64,
+}
+
+impl Mixer {
+    fn new() -> Self {
+        Mixer {
+            voices: Vec::new(),
+            buses: vec![1.0],
+            next_handle: 0,
+        }
+    }
+
+    ///Fills a buffer of interleaved samples at MIX_SAMPLE_RATE with MIX_CHANNELS channels
+    ///Voices that finish are removed
+    pub(crate) fn mix(&mut self, out: &mut [f32]) {
+        out.iter_mut().for_each(|s| *s = 0.0);
+        let master = self.get_bus_volume(Bus::MASTER);
+
+        for voice in self.voices.iter_mut() {
+            let frames = voice.sound.frames();
+            if frames == 0 {
+                voice.position = f64::INFINITY;
+                continue;
+            }
+
+            let gain = voice.params.volume * master * match voice.params.bus {
+                Bus::MASTER => 1.0,
+                bus => bus_volume(&self.buses, bus),
+            };
+            let step = voice.params.pitch.max(0.0) as f64 * voice.sound.0.sample_rate as f64 / MIX_SAMPLE_RATE as f64;
+
+            for frame in out.chunks_mut(MIX_CHANNELS as usize) {
+                if voice.position >= frames as f64 {
+                    if voice.params.looping {
+                        voice.position %= frames as f64;
+                    }
+                    else {
+                        break;
+                    }
+                }
+
+                //Linearly interpolate between the two nearest frames
+                let index = voice.position as usize;
+                let next = if index + 1 < frames {index + 1} else if voice.params.looping {0} else {index};
+                let t = (voice.position - index as f64) as f32;
+                for (channel, sample) in frame.iter_mut().enumerate() {
+                    let a = voice.sound.sample(index, channel as u16);
+                    let b = voice.sound.sample(next, channel as u16);
+                    *sample += (a + (b - a) * t) * gain;
+                }
+
+                voice.position += step;
+            }
+        }
+
+        //Empty sounds have nothing to loop, so they finish straight away
+        self.voices.retain(|v| v.sound.frames() > 0 && (v.params.looping || v.position < v.sound.frames() as f64));
+    }
+
+    fn get_bus_volume(&self, bus: Bus) -> f32 {
+        bus_volume(&self.buses, bus)
+    }
+
+    fn voice_mut(&mut self, handle: SoundHandle) -> Option<&mut Voice> {
+        self.voices.iter_mut().find(|v| v.handle == handle)
+    }
+}
+
+///Gets a bus's volume, or full volume for a bus that was created by another Audio
+fn bus_volume(buses: &[f32], bus: Bus) -> f32 {
+    buses.get(bus.0).copied().unwrap_or(1.0)
+}
+
+///Controls sound playback
+///
+///Sounds are mixed together in software and sent to an output device. Cloning an Audio
+///controls the same sounds, so it can be shared with callbacks through Frame.
+#[derive(Clone)]
+pub struct Audio {
+    pub(crate) mixer: Arc<Mutex<Mixer>>,
+}
+
+impl Debug for Audio {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Audio {{ playing: {} }}", self.mixer.lock().unwrap().voices.len())
+    }
+}
+
+impl Audio {
+    ///Creates a new audio controller with no sounds playing
+    ///No sound is heard until it is connected to an output device
+    pub fn new() -> Self {
+        Audio {
+            mixer: Arc::new(Mutex::new(Mixer::new())),
+        }
+    }
+
+    ///Plays a sound with the given settings
+    pub fn play(&self, sound: &Sound, params: PlayParams) -> SoundHandle {
+        let mut mixer = self.mixer.lock().unwrap();
+        let handle = SoundHandle(mixer.next_handle);
+        mixer.next_handle += 1;
+        mixer.voices.push(Voice {
+            handle,
+            sound: sound.clone(),
+            position: 0.0,
+            params,
+        });
+        handle
+    }
+
+    ///Plays a sound once at full volume
+    pub fn play_one_shot(&self, sound: &Sound) -> SoundHandle {
+        self.play(sound, Default::default())
+    }
+
+    ///Plays a sound on a loop until it is stopped
+    pub fn play_looping(&self, sound: &Sound, bus: Bus) -> SoundHandle {
+        self.play(sound, PlayParams { looping: true, bus, ..Default::default() })
+    }
+
+    ///Stops a sound
+    pub fn stop(&self, handle: SoundHandle) {
+        self.mixer.lock().unwrap().voices.retain(|v| v.handle != handle);
+    }
+
+    ///Stops every sound
+    pub fn stop_all(&self) {
+        self.mixer.lock().unwrap().voices.clear();
+    }
+
+    ///Returns true if a sound has not finished or been stopped
+    pub fn is_playing(&self, handle: SoundHandle) -> bool {
+        self.mixer.lock().unwrap().voice_mut(handle).is_some()
+    }
+
+    ///Sets the volume of a playing sound
+    pub fn set_volume(&self, handle: SoundHandle, volume: f32) {
+        if let Some(voice) = self.mixer.lock().unwrap().voice_mut(handle) {
+            voice.params.volume = volume;
+        }
+    }
+
+    ///Sets the pitch of a playing sound
+    pub fn set_pitch(&self, handle: SoundHandle, pitch: f32) {
+        if let Some(voice) = self.mixer.lock().unwrap().voice_mut(handle) {
+            voice.params.pitch = pitch;
+        }
+    }
+
+    ///Creates a new bus at full volume
+    pub fn create_bus(&self) -> Bus {
+        let mut mixer = self.mixer.lock().unwrap();
+        mixer.buses.push(1.0);
+        Bus(mixer.buses.len() - 1)
+    }
+
+    ///Sets the volume of a bus
+    ///Setting the volume of Bus::MASTER changes the volume of every sound
+    ///Buses created by another Audio are ignored, and sounds played on them use full volume
+    pub fn set_bus_volume(&self, bus: Bus, volume: f32) {
+        if let Some(bus_volume) = self.mixer.lock().unwrap().buses.get_mut(bus.0) {
+            *bus_volume = volume;
+        }
+    }
+
+    pub fn get_bus_volume(&self, bus: Bus) -> f32 {
+        self.mixer.lock().unwrap().get_bus_volume(bus)
+    }
+
+    ///Mixes the next samples of every playing sound into a buffer, advancing playback
+    ///The buffer is interleaved with MIX_CHANNELS channels at MIX_SAMPLE_RATE
+    ///Output devices call this, and it can be used to inspect output without a device
+    pub fn mix(&self, out: &mut [f32]) {
+        self.mixer.lock().unwrap().mix(out);
+    }
+}
+
+impl Default for Audio {
+    fn default() -> Self {
+        Audio::new()
+    }
+}
+
+///Somewhere that mixed sound is sent to
+pub trait AudioDevice: Debug {
+    ///Called every frame with the time since the last frame
+    fn update(&mut self, _audio: &Audio, _delta_time: Duration) {}
+}
+
+///An output device that discards sound
+///Playback still advances in time with the window, so sounds finish as they would with a real device
+#[derive(Debug, Default)]
+pub struct NullAudioDevice {
+    ///Fractional frames left over from previous updates
+    remainder: f64,
+    buffer: Vec<f32>,
+}
+
+impl AudioDevice for NullAudioDevice {
+    fn update(&mut self, audio: &Audio, delta_time: Duration) {
+        let frames = delta_time.as_secs_f64() * MIX_SAMPLE_RATE as f64 + self.remainder;
+        self.remainder = frames.fract();
+        self.buffer.resize(frames as usize * MIX_CHANNELS as usize, 0.0);
+        audio.mix(&mut self.buffer);
+    }
+}
+
+///Opens the system's default output device, or a NullAudioDevice if there isn't one
+///Always a NullAudioDevice unless the audio_device feature is enabled
+pub fn open_default_audio_device(audio: &Audio) -> Box<dyn AudioDevice> {
+    #[cfg(feature = "audio_device")]
+    if let Some(device) = system_device::SystemAudioDevice::new(audio) {
+        return Box::new(device);
+    }
+    let _ = audio;
+    Box::new(NullAudioDevice::default())
+}
+
+#[cfg(feature = "audio_device")]
+mod system_device {
+    use super::{Audio, AudioDevice, MIX_CHANNELS, MIX_SAMPLE_RATE};
+
+    ///Number of frames mixed at a time for the output stream
+    const BUFFER_FRAMES: usize = 512;
+
+    ///Plays mixed sound through the system's default output device
+    pub struct SystemAudioDevice {
+        _stream: rodio::OutputStream,
+        _handle: rodio::OutputStreamHandle,
+    }
+
+    impl std::fmt::Debug for SystemAudioDevice {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "SystemAudioDevice")
+        }
+    }
+
+    impl SystemAudioDevice {
+        pub fn new(audio: &Audio) -> Option<Self> {
+            let (stream, handle) = rodio::OutputStream::try_default().ok()?;
+            let source = MixerSource {
+                audio: audio.clone(),
+                buffer: vec![0.0; BUFFER_FRAMES * MIX_CHANNELS as usize],
+                position: usize::MAX,
+            };
+            handle.play_raw(source).ok()?;
+            Some(SystemAudioDevice {
+                _stream: stream,
+                _handle: handle,
+            })
+        }
+    }
+
+    //Sound is pulled by the output stream's thread, so nothing needs doing every frame
+    impl AudioDevice for SystemAudioDevice {}
+
+    ///Endless source of samples that mixes more whenever its buffer runs out
+    struct MixerSource {
+        audio: Audio,
+        buffer: Vec<f32>,
+        position: usize,
+    }
+
+    impl Iterator for MixerSource {
+        type Item = f32;
+        fn next(&mut self) -> Option<f32> {
+            if self.position >= self.buffer.len() {
+                self.audio.mix(&mut self.buffer);
+                self.position = 0;
+            }
+            self.position += 1;
+            Some(self.buffer[self.position - 1])
+        }
+    }
+
+    impl rodio::Source for MixerSource {
+        fn current_frame_len(&self) -> Option<usize> {
+            None
+        }
+        fn channels(&self) -> u16 {
+            MIX_CHANNELS
+        }
+        fn sample_rate(&self) -> u32 {
+            MIX_SAMPLE_RATE
+        }
+        fn total_duration(&self) -> Option<std::time::Duration> {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ///Creates a mono sound at the mix sample rate whose samples count up from 0.0 in steps of 0.1
+    fn ramp(frames: usize) -> Sound {
+        Sound::from_samples((0..frames).map(|i| i as f32 * 0.1).collect(), 1, MIX_SAMPLE_RATE)
+    }
+
+    ///Mixes a number of frames, returning the first channel of each
+    fn mix_frames(audio: &Audio, frames: usize) -> Vec<f32> {
+        let mut buffer = vec![0.0; frames * MIX_CHANNELS as usize];
+        audio.mix(&mut buffer);
+        buffer.chunks(MIX_CHANNELS as usize).map(|f| f[0]).collect()
+    }
+
+    fn assert_samples(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-5, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn one_shot_voices_end() {
+        let audio = Audio::new();
+        let handle = audio.play_one_shot(&ramp(3));
+        assert_samples(&mix_frames(&audio, 5), &[0.0, 0.1, 0.2, 0.0, 0.0]);
+        assert!(!audio.is_playing(handle));
+    }
+
+    #[test]
+    fn looping_voices_wrap() {
+        let audio = Audio::new();
+        let handle = audio.play_looping(&ramp(3), Bus::MASTER);
+        assert_samples(&mix_frames(&audio, 7), &[0.0, 0.1, 0.2, 0.0, 0.1, 0.2, 0.0]);
+        assert!(audio.is_playing(handle));
+    }
+
+    #[test]
+    fn empty_voices_end_even_when_looping() {
+        let audio = Audio::new();
+        let one_shot = audio.play_one_shot(&ramp(0));
+        let looping = audio.play_looping(&ramp(0), Bus::MASTER);
+        assert_samples(&mix_frames(&audio, 2), &[0.0, 0.0]);
+        assert!(!audio.is_playing(one_shot));
+        assert!(!audio.is_playing(looping));
+    }
+
+    #[test]
+    fn bus_and_master_volumes_scale() {
+        let audio = Audio::new();
+        let bus = audio.create_bus();
+        audio.set_bus_volume(bus, 0.5);
+        audio.set_bus_volume(Bus::MASTER, 0.5);
+        audio.play(&ramp(3), PlayParams { volume: 2.0, bus, ..Default::default() });
+        assert_samples(&mix_frames(&audio, 3), &[0.0, 0.05, 0.1]);
+
+        //A bus from another Audio plays at full volume rather than panicking
+        let other = Audio::new();
+        other.create_bus();
+        let other_bus = other.create_bus();
+        audio.set_bus_volume(other_bus, 0.0);
+        assert_eq!(audio.get_bus_volume(other_bus), 1.0);
+        audio.play(&ramp(3), PlayParams { bus: other_bus, ..Default::default() });
+        assert_samples(&mix_frames(&audio, 3), &[0.0, 0.05, 0.1]);
+    }
+
+    #[test]
+    fn pitch_changes_the_step() {
+        let audio = Audio::new();
+        let handle = audio.play(&ramp(5), PlayParams { pitch: 2.0, ..Default::default() });
+        assert_samples(&mix_frames(&audio, 3), &[0.0, 0.2, 0.4]);
+        assert!(!audio.is_playing(handle));
+
+        //Half pitch interpolates between frames
+        audio.play(&ramp(2), PlayParams { pitch: 0.5, ..Default::default() });
+        assert_samples(&mix_frames(&audio, 4), &[0.0, 0.05, 0.1, 0.1]);
+    }
+
+    #[test]
+    fn null_device_advances_in_real_time() {
+        let audio = Audio::new();
+        let mut device = NullAudioDevice::default();
+        let handle = audio.play_one_shot(&ramp(MIX_SAMPLE_RATE as usize / 10));
+        device.update(&audio, Duration::from_millis(60));
+        assert!(audio.is_playing(handle));
+        device.update(&audio, Duration::from_millis(60));
+        assert!(!audio.is_playing(handle));
+    }
+}
@@ -0,0 +1,265 @@
+use crate::{Mat4, Vec3};
+
+///An axis aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Aabb { min, max }
+    }
+
+    ///Gets the smallest box containing every point
+    ///Returns None if there are no points
+    pub fn from_points<I: IntoIterator<Item = Vec3>>(points: I) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        let mut bounds = Aabb::new(first, first);
+        for p in points {
+            bounds.min = Vec3::new(bounds.min.x.min(p.x), bounds.min.y.min(p.y), bounds.min.z.min(p.z));
+            bounds.max = Vec3::new(bounds.max.x.max(p.x), bounds.max.y.max(p.y), bounds.max.z.max(p.z));
+        }
+        Some(bounds)
+    }
+
+    pub fn get_centre(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    ///Gets the distance from the centre to the edge along each axis
+    pub fn get_extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        point.x >= self.min.x && point.x <= self.max.x &&
+        point.y >= self.min.y && point.y <= self.max.y &&
+        point.z >= self.min.z && point.z <= self.max.z
+    }
+
+    ///Gets the smallest box containing both boxes
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::new(
+            Vec3::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y), self.min.z.min(other.min.z)),
+            Vec3::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y), self.max.z.max(other.max.z)),
+        )
+    }
+
+    ///Gets a box containing this box after it has been moved by a matrix
+    ///The result is axis aligned in the new space, so may be larger than the original box
+    pub fn transformed(&self, matrix: &Mat4) -> Aabb {
+        let centre = matrix.transform_point(self.get_centre());
+        let e = self.get_extents();
+        let r = &matrix.rows;
+        let extents = Vec3::new(
+            (e.x * r[0][0]).abs() + (e.y * r[1][0]).abs() + (e.z * r[2][0]).abs(),
+            (e.x * r[0][1]).abs() + (e.y * r[1][1]).abs() + (e.z * r[2][1]).abs(),
+            (e.x * r[0][2]).abs() + (e.y * r[1][2]).abs() + (e.z * r[2][2]).abs(),
+        );
+        Aabb::new(centre - extents, centre + extents)
+    }
+}
+
+///A bounding sphere
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BoundingSphere {
+    pub centre: Vec3,
+    pub radius: f64,
+}
+
+impl BoundingSphere {
+    pub fn new(centre: Vec3, radius: f64) -> Self {
+        BoundingSphere { centre, radius }
+    }
+
+    ///Gets a sphere containing every point, centred on their bounding box
+    ///Returns None if there are no points
+    pub fn from_points<I: IntoIterator<Item = Vec3> + Clone>(points: I) -> Option<Self> {
+        let centre = Aabb::from_points(points.clone())?.get_centre();
+        let radius = points.into_iter().map(|p| (p - centre).length()).fold(0.0, f64::max);
+        Some(BoundingSphere::new(centre, radius))
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        (point - self.centre).length() <= self.radius
+    }
+
+    ///Gets a sphere containing this sphere after it has been moved by a matrix
+    ///Non uniform scales use the largest axis
+    pub fn transformed(&self, matrix: &Mat4) -> BoundingSphere {
+        let scale = matrix.transform_vector(Vec3::X).length()
+            .max(matrix.transform_vector(Vec3::Y).length())
+            .max(matrix.transform_vector(Vec3::Z).length());
+        BoundingSphere::new(matrix.transform_point(self.centre), self.radius * scale)
+    }
+}
+
+///A plane, where points on the plane satisfy `normal.dot(p) + distance = 0`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plane {
+    pub normal: Vec3,
+    pub distance: f64,
+}
+
+impl Plane {
+    ///Gets the signed distance from the plane to a point, positive on the side the normal points to
+    pub fn distance_to(&self, point: Vec3) -> f64 {
+        self.normal.dot(point) + self.distance
+    }
+}
+
+///The volume that a camera can see, as six planes facing inwards
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    ///Gets the frustum of a matrix that moves points into clip space
+    ///Uses the row vector convention like Mat4, so the matrix is world * view * projection
+    pub fn from_matrix(matrix: &Mat4) -> Self {
+        let r = &matrix.rows;
+        let column = |j: usize| [r[0][j], r[1][j], r[2][j], r[3][j]];
+        let (x, y, z, w) = (column(0), column(1), column(2), column(3));
+
+        //A point is visible when -w <= x, y, z <= w in clip space
+        let plane = |a: [f64; 4], sign: f64| {
+            let normal = Vec3::new(w[0] + sign * a[0], w[1] + sign * a[1], w[2] + sign * a[2]);
+            let length = normal.length();
+            let distance = w[3] + sign * a[3];
+            if length == 0.0 {
+                return Plane { normal, distance };
+            }
+            Plane { normal: normal * (1.0 / length), distance: distance / length }
+        };
+
+        Frustum { planes: [
+            plane(x, 1.0), plane(x, -1.0),
+            plane(y, 1.0), plane(y, -1.0),
+            plane(z, 1.0), plane(z, -1.0),
+        ]}
+    }
+
+    ///Checks if any part of a box might be inside the frustum
+    ///May return true for boxes just outside the corners of the frustum
+    pub fn intersects_aabb(&self, bounds: &Aabb) -> bool {
+        let centre = bounds.get_centre();
+        let extents = bounds.get_extents();
+        self.planes.iter().all(|plane| {
+            let radius = extents.x * plane.normal.x.abs() + extents.y * plane.normal.y.abs() + extents.z * plane.normal.z.abs();
+            plane.distance_to(centre) >= -radius
+        })
+    }
+
+    ///Checks if any part of a sphere might be inside the frustum
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes.iter().all(|plane| plane.distance_to(sphere.centre) >= -sphere.radius)
+    }
+
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        self.planes.iter().all(|plane| plane.distance_to(point) >= 0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Quat;
+
+    const EPSILON: f64 = 1e-9;
+
+    fn assert_vec3_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < EPSILON, "{:?} != {:?}", a, b);
+    }
+
+    ///A frustum looking down +z with a 90 degree field of view, like the 3D shaders' projection,
+    ///so points are visible when |x| <= z, |y| <= z and 1 <= z <= 100
+    fn frustum() -> Frustum {
+        let (near, far) = (1.0, 100.0);
+        Frustum::from_matrix(&Mat4::from_rows([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, (far + near) / (far - near), 1.0],
+            [0.0, 0.0, -(2.0 * far * near) / (far - near), 0.0],
+        ]))
+    }
+
+    fn cube(centre: Vec3, half_size: f64) -> Aabb {
+        Aabb::new(centre - Vec3::ONE * half_size, centre + Vec3::ONE * half_size)
+    }
+
+    #[test]
+    fn planes_are_normalised_and_face_inwards() {
+        let frustum = frustum();
+        for plane in frustum.planes.iter() {
+            assert!((plane.normal.length() - 1.0).abs() < EPSILON);
+            assert!(plane.distance_to(Vec3::new(0.0, 0.0, 10.0)) > 0.0);
+        }
+        //The side planes are at 45 degrees, so a point's distance from them is its distance from the edge over root 2
+        assert!((frustum.planes[0].distance_to(Vec3::new(0.0, 0.0, 10.0)) - 10.0 / 2f64.sqrt()).abs() < EPSILON);
+        assert!(frustum.contains_point(Vec3::new(9.0, -9.0, 10.0)));
+        assert!(!frustum.contains_point(Vec3::new(11.0, 0.0, 10.0)));
+        assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, 0.5)));
+        assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, 101.0)));
+    }
+
+    #[test]
+    fn boxes_inside_outside_and_straddling() {
+        let frustum = frustum();
+        assert!(frustum.intersects_aabb(&cube(Vec3::new(0.0, 0.0, 10.0), 1.0)));
+
+        assert!(!frustum.intersects_aabb(&cube(Vec3::new(0.0, 0.0, -10.0), 1.0)));
+        assert!(!frustum.intersects_aabb(&cube(Vec3::new(30.0, 0.0, 10.0), 1.0)));
+        assert!(!frustum.intersects_aabb(&cube(Vec3::new(0.0, -30.0, 10.0), 1.0)));
+        assert!(!frustum.intersects_aabb(&cube(Vec3::new(0.0, 0.0, 200.0), 1.0)));
+
+        //Straddling a side plane, the near plane and the far plane
+        assert!(frustum.intersects_aabb(&cube(Vec3::new(11.0, 0.0, 10.0), 2.0)));
+        assert!(frustum.intersects_aabb(&cube(Vec3::new(0.0, 0.0, 0.5), 1.0)));
+        assert!(frustum.intersects_aabb(&cube(Vec3::new(0.0, 0.0, 100.5), 1.0)));
+    }
+
+    #[test]
+    fn spheres_inside_outside_and_straddling() {
+        let frustum = frustum();
+        assert!(frustum.intersects_sphere(&BoundingSphere::new(Vec3::new(0.0, 0.0, 10.0), 1.0)));
+        assert!(!frustum.intersects_sphere(&BoundingSphere::new(Vec3::new(0.0, 0.0, -10.0), 1.0)));
+        assert!(!frustum.intersects_sphere(&BoundingSphere::new(Vec3::new(20.0, 0.0, 10.0), 1.0)));
+        assert!(frustum.intersects_sphere(&BoundingSphere::new(Vec3::new(11.0, 0.0, 10.0), 2.0)));
+    }
+
+    #[test]
+    fn transformed_boxes_contain_the_rotated_box() {
+        let bounds = Aabb::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(2.0, 1.0, 1.0));
+
+        //A quarter turn around z moves +x to +y and +y to -x
+        let quarter_turn = Mat4::from_rotation(Quat::from_axis_angle(Vec3::Z, std::f64::consts::FRAC_PI_2));
+        let turned = bounds.transformed(&quarter_turn);
+        assert_vec3_near(turned.min, Vec3::new(-1.0, 0.0, 0.0));
+        assert_vec3_near(turned.max, Vec3::new(0.0, 2.0, 1.0));
+
+        //An eighth turn grows the box to fit the rotated corners, then it is moved
+        let matrix = Mat4::from_rotation(Quat::from_axis_angle(Vec3::Z, std::f64::consts::FRAC_PI_4)) * Mat4::from_translation(Vec3::new(0.0, 0.0, 5.0));
+        let turned = bounds.transformed(&matrix);
+        //Corners touch the edges, so allow for rounding
+        let margin = Vec3::ONE * EPSILON;
+        let with_margin = Aabb::new(turned.min - margin, turned.max + margin);
+        for corner in [0.0, 2.0].iter().flat_map(|&x| [0.0, 1.0].map(|y| Vec3::new(x, y, 0.5))) {
+            assert!(with_margin.contains(matrix.transform_point(corner)));
+        }
+        let half_diagonal = 1.5 / 2f64.sqrt();
+        assert_vec3_near(turned.get_extents(), Vec3::new(half_diagonal, half_diagonal, 0.5));
+    }
+
+    #[test]
+    fn unions_and_points() {
+        let a = Aabb::new(Vec3::new(-1.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0));
+        let b = Aabb::new(Vec3::new(0.0, -2.0, 0.5), Vec3::new(3.0, 0.0, 0.5));
+        assert_eq!(a.union(&b), Aabb::new(Vec3::new(-1.0, -2.0, 0.0), Vec3::new(3.0, 1.0, 1.0)));
+        assert_eq!(Aabb::from_points([Vec3::new(1.0, 5.0, -1.0), Vec3::new(-1.0, 2.0, 3.0)]), Some(Aabb::new(Vec3::new(-1.0, 2.0, -1.0), Vec3::new(1.0, 5.0, 3.0))));
+        assert_eq!(Aabb::from_points(Vec::new()), None);
+    }
+}
@@ -1,6 +1,15 @@
+use std::cmp::Ordering;
 use std::fmt::Debug;
-
-use glium::Frame;
+use std::mem::take;
+use std::rc::Rc;
+
+use glium::framebuffer::DepthRenderBuffer;
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::texture::CubeLayer;
+use glium::texture::DepthFormat;
+use glium::texture::SrgbCubemap;
+use glium::texture::SrgbTexture2d;
+use glium::BlitTarget;
 use glium::IndexBuffer;
 use glium::Program;
 use glium::Rect;
@@ -9,158 +18,816 @@ use glium::VertexBuffer;
 use glium::uniform;
 
 use crate::Scene;
+use crate::Entity;
 use crate::Transform;
+use crate::Mat4;
+use crate::Frustum;
+use crate::Viewport;
+use crate::ViewportHit;
+use crate::InstanceVertex;
 use crate::Window;
 use crate::shaders;
-use crate::shaders::shader_priv::ShaderUniforms;
+use crate::shaders::BlendMode;
+use crate::shaders::Texture;
+use crate::shaders::RenderError;
+use crate::shaders::shader_priv::{AssetCreationError, ShaderUniforms};
 
+///Cameras are much larger than split views, but a scene only holds one Renderable so it isn't boxed
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum Renderable {
     Camera(Camera),
     SplitView(SplitView),
 }
 
 impl Renderable {
-    pub(crate) fn render(&mut self, frame: &mut Frame, scene: &mut Scene, window: &Window, x_start: f32, x_end: f32, y_start: f32, y_end: f32) {
+    pub(crate) fn render<S: Surface>(&mut self, frame: &mut S, scene: &mut Scene, window: &Window, viewport: Viewport) -> Result<(), RenderError> {
+        match self {
+            Renderable::Camera(c) => c.render(frame, scene, window, viewport),
+            Renderable::SplitView(s) => s.render(frame, scene, window, viewport)
+        }
+    }
+
+    ///Gets the number of meshes drawn and culled in the last render, added up over every camera
+    pub fn get_render_stats(&self) -> RenderStats {
+        match self {
+            Renderable::Camera(c) => c.get_render_stats(),
+            Renderable::SplitView(s) => {
+                let mut stats = RenderStats::default();
+                for view in s.views.iter() {
+                    stats += view.renderable.get_render_stats();
+                }
+                stats
+            }
+        }
+    }
+
+    ///Drops any gpu assets so that they are recreated on the next render
+    pub(crate) fn invalidate_assets(&mut self) {
+        match self {
+            Renderable::Camera(c) => c.invalidate_assets(),
+            Renderable::SplitView(s) => {
+                for view in s.views.iter_mut() {
+                    view.renderable.invalidate_assets();
+                }
+            }
+        }
+    }
+
+    ///Finds which view a point is in, where the point is from (0, 0) at the bottom left of the surface to (1, 1) at the top right
+    ///surface is the size of the surface in pixels, used by views with a fixed aspect ratio
+    ///Returns None if the point isn't over any view
+    pub fn get_view_at(&self, point: (f32, f32), surface: (u32, u32)) -> Option<ViewportHit> {
+        self.view_at(point, Viewport::FULL, surface)
+    }
+
+    pub(crate) fn view_at(&self, point: (f32, f32), viewport: Viewport, surface: (u32, u32)) -> Option<ViewportHit> {
         match self {
-            Renderable::Camera(c) => c.render(frame, scene, window, x_start, x_end, y_start, y_end),
-            Renderable::SplitView(s) => s.render(frame, scene, window, x_start, x_end, y_start, y_end)
+            Renderable::Camera(_) => match viewport.contains(point) {
+                true => Some(ViewportHit { path: Vec::new(), position: viewport.to_local(point) }),
+                false => None
+            },
+            Renderable::SplitView(s) => {
+                //Views drawn last are on top, so check them first
+                for index in s.draw_order().into_iter().rev() {
+                    let view = &s.views[index];
+                    if let Some(mut hit) = view.renderable.view_at(point, view.get_rect(viewport, surface), surface) {
+                        hit.path.insert(0, index);
+                        return Some(hit);
+                    }
+                }
+                None
+            }
         }
     }
 }
 
+///Counts of meshes that a camera drew or skipped, for profiling
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    pub drawn: usize,
+    pub culled: usize,
+    ///Meshes skipped because they or their object are hidden, or their object is on layers the camera doesn't draw
+    pub hidden: usize,
+}
+
+impl std::ops::AddAssign for RenderStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.drawn += rhs.drawn;
+        self.culled += rhs.culled;
+        self.hidden += rhs.hidden;
+    }
+}
+
+///A set of up to 32 render layers, stored as a bit mask
+///Objects are drawn by a camera if they share at least one layer with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RenderLayers(pub u32);
+
+impl RenderLayers {
+    pub const NONE: RenderLayers = RenderLayers(0);
+    pub const ALL: RenderLayers = RenderLayers(u32::MAX);
+    ///The layer objects are on when created
+    pub const DEFAULT: RenderLayers = RenderLayers(1);
+
+    ///Gets the set containing only one layer
+    ///Panics if layer is not below 32
+    pub const fn layer(layer: u32) -> Self {
+        assert!(layer < 32, "there are only 32 render layers");
+        RenderLayers(1 << layer)
+    }
+
+    ///Adds a layer to the set
+    pub const fn with(self, layer: u32) -> Self {
+        RenderLayers(self.0 | RenderLayers::layer(layer).0)
+    }
+
+    ///Removes a layer from the set
+    pub const fn without(self, layer: u32) -> Self {
+        RenderLayers(self.0 & !RenderLayers::layer(layer).0)
+    }
+
+    pub const fn contains(self, layer: u32) -> bool {
+        self.0 & RenderLayers::layer(layer).0 != 0
+    }
+
+    ///Checks if the two sets share any layers
+    pub const fn intersects(self, other: RenderLayers) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+impl Default for RenderLayers {
+    fn default() -> Self {
+        RenderLayers::DEFAULT
+    }
+}
+
+impl std::ops::BitOr for RenderLayers {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self {
+        RenderLayers(self.0 | rhs.0)
+    }
+}
+
+///What a camera draws over its part of the screen before drawing meshes
+///Every mode except None clears depth, so the camera's meshes are drawn over anything already there
+#[derive(Debug, Clone, Default)]
+pub enum ClearMode {
+    ///Keeps colour and depth, so meshes can be hidden behind what other cameras drew
+    None,
+    ///Keeps colour but clears depth, for drawing on top of another camera
+    #[default]
+    Depth,
+    ///Fills with a colour
+    Colour([f32; 4]),
+    ///Fills with a vertical gradient
+    Gradient { top: [f32; 4], bottom: [f32; 4] },
+    ///Draws a cubemap around the camera, which turns with the camera but never moves
+    Skybox(Skybox),
+}
+
+impl ClearMode {
+    ///Gets the colour the camera's part of the surface is filled with, if the mode fills with one
+    fn get_fill_colour(&self) -> Option<[f32; 4]> {
+        match self {
+            ClearMode::Colour(c) => Some(*c),
+            _ => None
+        }
+    }
+
+    ///Whether the mode clears anything, which always includes depth
+    fn clears_depth(&self) -> bool {
+        !matches!(self, ClearMode::None)
+    }
+
+    ///Whether a background is drawn over the camera's part of the surface after clearing
+    fn draws_background(&self) -> bool {
+        matches!(self, ClearMode::Gradient { .. } | ClearMode::Skybox(_))
+    }
+}
+
+///Six textures forming a cube around the camera
+#[derive(Debug, Clone)]
+pub struct Skybox {
+    ///Faces in the order +x, -x, +y, -y, +z, -z, or right, left, top, bottom, front, back
+    pub faces: [Texture; 6],
+}
+
+impl Skybox {
+    ///Creates a skybox from square textures of the same size
+    ///Faces are in the order +x, -x, +y, -y, +z, -z, or right, left, top, bottom, front, back
+    pub fn new(faces: [Texture; 6]) -> Self {
+        Skybox { faces }
+    }
+
+    ///Copies the faces into a cubemap
+    fn create_cubemap(&self, display: &glium::Display) -> Result<SrgbCubemap, RenderError> {
+        const LAYERS: [CubeLayer; 6] = [
+            CubeLayer::PositiveX, CubeLayer::NegativeX,
+            CubeLayer::PositiveY, CubeLayer::NegativeY,
+            CubeLayer::PositiveZ, CubeLayer::NegativeZ,
+        ];
+
+        let size = self.faces[0].get_dimensions().0;
+        let cubemap = SrgbCubemap::empty(display, size).map_err(AssetCreationError::from)?;
+        for (face, layer) in self.faces.iter().zip(LAYERS) {
+            let framebuffer = SimpleFrameBuffer::new(display, cubemap.main_level().image(layer))?;
+            //Textures are stored bottom row first but cubemap faces top row first, so flip them
+            let face = face.get();
+            let source = SimpleFrameBuffer::new(display, &*face)?;
+            source.blit_whole_color_to(&framebuffer,
+                &BlitTarget { left: 0, bottom: size, width: size as i32, height: -(size as i32) },
+                glium::uniforms::MagnifySamplerFilter::Linear);
+        }
+        Ok(cubemap)
+    }
+}
+
+///A skybox's cubemap, with the face textures it was made from so it can be remade when they change
+struct SkyboxCubemap {
+    faces: Vec<Rc<SrgbTexture2d>>,
+    cubemap: SrgbCubemap,
+}
+
+impl Debug for SkyboxCubemap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SkyboxCubemap").finish_non_exhaustive()
+    }
+}
+
 #[derive(Debug)]
 pub struct Camera {
-    clear_colour: Option<[f32; 4]>,
+    clear_mode: ClearMode,
     pub transform: Transform,
     pub fov: f32,
     pub znear: f32,
     pub zfar: f32,
+    ///Whether to skip meshes whose bounds are outside of the camera's view
+    pub culling: bool,
+    ///Which layers the camera draws
+    pub layers: RenderLayers,
+    stats: RenderStats,
+    ///Height / width aspect ratio used in the last render
+    aspect_ratio: f32,
+    ///Texture to draw into instead of the window
+    target: Option<Texture>,
+    target_depth: Option<TargetDepth>,
 
     #[doc(hidden)]
     clear_vertices: Option<VertexBuffer<shaders::ClearVertex>>,
     clear_program: Option<Program>,
     clear_indices: Option<IndexBuffer<u32>>,
+    skybox_program: Option<Program>,
+    skybox_cubemap: Option<SkyboxCubemap>,
 }
 
 
 impl Camera {
 
+    ///Fills the camera's part of the screen with a colour, or only clears depth if None
     pub fn set_clear_colour (&mut self, colour: Option<[f32; 4]>) {
-        self.clear_colour = colour;
+        self.set_clear_mode(match colour {
+            Some(c) => ClearMode::Colour(c),
+            None => ClearMode::Depth
+        });
+    }
+
+    pub fn set_clear_mode(&mut self, mode: ClearMode) {
+        self.clear_mode = mode;
+        self.skybox_cubemap = None;
+    }
+
+    pub fn get_clear_mode(&self) -> &ClearMode {
+        &self.clear_mode
+    }
+
+    ///Drops any gpu assets so that they are recreated on the next render
+    pub(crate) fn invalidate_assets(&mut self) {
         self.clear_vertices = None;
         self.clear_program = None;
         self.clear_indices = None;
+        self.skybox_program = None;
+        self.skybox_cubemap = None;
+        self.target_depth = None;
+    }
+
+    ///Makes the camera draw into a texture instead of the window, or into the window again if None
+    ///The texture can then be used by any shader, for example for mirrors or minimaps.
+    ///A camera with a target always draws to the whole texture, wherever it is in a SplitView.
+    ///Cameras that only draw to textures can be added to a scene with Scene::add_offscreen_camera
+    pub fn set_target(&mut self, target: Option<Texture>) {
+        self.target = target;
+        self.target_depth = None;
+    }
+
+    pub fn get_target(&self) -> &Option<Texture> {
+        &self.target
     }
 
     pub fn new(transform: Transform, fov: f32) -> Self {
         Camera { 
-            clear_colour: None,
-            transform: transform,
-            fov: fov,
+            clear_mode: ClearMode::Depth,
+            transform,
+            fov,
             znear: 0.1,
             zfar: 1024.0,
-
+            culling: true,
+            layers: RenderLayers::ALL,
+            stats: RenderStats::default(),
+            aspect_ratio: 1.0,
+            target: None,
+            target_depth: None,
 
             clear_vertices: None,
             clear_program: None,
             clear_indices: None,
+            skybox_program: None,
+            skybox_cubemap: None,
         }
     }
 
-    fn render(&mut self, frame: &mut Frame, scene: &mut Scene, window: &Window, x_start: f32, x_end: f32, y_start: f32, y_end: f32) {
-        
-        frame.clear_depth(1.0);
+    ///Gets the number of meshes drawn and culled in the last render
+    pub fn get_render_stats(&self) -> RenderStats {
+        self.stats
+    }
 
-        if let Some(clear_colour) = self.clear_colour {
-            //Create program to draw single colour rectangle to screen
-            if self.clear_program.is_none() {
-                self.clear_program = Some(glium::Program::from_source(&window.display, shaders::CLEAR_VERTEX_SHADER, shaders::CLEAR_FRAGMENT_SHADER, None).unwrap());
-                self.clear_vertices = Some(glium::VertexBuffer::new(&window.display, &shaders::CLEAR_VERTICES).unwrap());
-                self.clear_indices = Some(glium::IndexBuffer::new(&window.display, glium::index::PrimitiveType::TrianglesList, &shaders::CLEAR_INDICES).unwrap());
+    ///Gets the height / width aspect ratio the camera drew with in the last render
+    ///Follows the size of the camera's part of the window, so changes when the window is resized
+    pub fn get_aspect_ratio(&self) -> f32 {
+        self.aspect_ratio
+    }
+
+    ///Checks if a mesh could be seen by the camera
+    fn is_visible(&self, view: &Option<Mat4>, projection: [[f32; 4]; 4], world: &Mat4, mesh: &crate::Mesh) -> bool {
+        //A camera that can't be inverted is drawn as is by the shaders, so don't cull with it
+        let view = match view {
+            Some(v) => v,
+            None => return true
+        };
+        let projection = Mat4::from_rows(projection.map(|row| row.map(|x| x as f64)));
+        let frustum = Frustum::from_matrix(&(*view * projection));
+        frustum.intersects_aabb(&mesh.get_bounds().transformed(world))
+    }
+
+    ///Clears the camera's part of the surface with its clear mode
+    ///viewport is the camera's part of the surface, and rect is the same area in pixels
+    fn clear<S: Surface>(&mut self, frame: &mut S, window: &Window, viewport: Viewport, rect: &Rect, aspect_ratio: f32) -> Result<(), RenderError> {
+        if !self.clear_mode.clears_depth() {
+            return Ok(());
+        }
+        let colour = self.clear_mode.get_fill_colour().map(|c| (c[0], c[1], c[2], c[3]));
+        frame.clear(Some(rect), colour, true, Some(1.0), None);
+        if !self.clear_mode.draws_background() {
+            return Ok(());
+        }
+
+        //Gradients and skyboxes are drawn as a rectangle over the camera's part of the screen
+        if self.clear_vertices.is_none() {
+            self.clear_vertices = Some(glium::VertexBuffer::new(&window.display, &shaders::CLEAR_VERTICES).map_err(AssetCreationError::from)?);
+            self.clear_indices = Some(glium::IndexBuffer::new(&window.display, glium::index::PrimitiveType::TrianglesList, &shaders::CLEAR_INDICES).map_err(AssetCreationError::from)?);
+        }
+        let (Some(vertices), Some(indices)) = (&self.clear_vertices, &self.clear_indices) else {
+            return Ok(());
+        };
+        let (x_start, x_end, y_start, y_end) = viewport.to_ndc();
+        let positions = [
+            [x_end, y_end, 0.0, 1.0],
+            [x_start, y_end, 0.0, 1.0],
+            [x_end, y_start, 0.0, 1.0],
+            [x_start, y_start, 0.0, 1.0]
+        ];
+        let params = glium::DrawParameters {
+            scissor: Some(*rect),
+            .. Default::default()
+        };
+
+        match &self.clear_mode {
+            ClearMode::Gradient { top, bottom } => {
+                if self.clear_program.is_none() {
+                    self.clear_program = Some(glium::Program::from_source(&window.display, shaders::CLEAR_VERTEX_SHADER, shaders::GRADIENT_FRAGMENT_SHADER, None).map_err(AssetCreationError::from)?);
+                }
+                let Some(program) = &self.clear_program else {
+                    return Ok(());
+                };
+                let uniforms = uniform! {
+                    positions: positions,
+                    top_colour: *top,
+                    bottom_colour: *bottom,
+                };
+                frame.draw(vertices, indices, program, &uniforms, &params)?;
+            },
+            ClearMode::Skybox(skybox) => {
+                //Remake the cubemap if the face textures have been reloaded
+                let faces: Vec<Rc<SrgbTexture2d>> = skybox.faces.iter().map(|f| f.get()).collect();
+                let up_to_date = self.skybox_cubemap.as_ref().is_some_and(|s| {
+                    s.faces.iter().zip(faces.iter()).all(|(a, b)| Rc::ptr_eq(a, b))
+                });
+                if !up_to_date {
+                    self.skybox_cubemap = Some(SkyboxCubemap { cubemap: skybox.create_cubemap(&window.display)?, faces });
+                }
+                if self.skybox_program.is_none() {
+                    self.skybox_program = Some(glium::Program::from_source(&window.display, shaders::CLEAR_VERTEX_SHADER, shaders::SKYBOX_FRAGMENT_SHADER, None).map_err(AssetCreationError::from)?);
+                }
+                let (Some(cubemap), Some(program)) = (&self.skybox_cubemap, &self.skybox_program) else {
+                    return Ok(());
+                };
+
+                //Matches the projection used by the 3D shaders
+                let f = 1.0 / ((std::f32::consts::PI / self.fov) / 2.0).tan();
+                let uniforms = uniform! {
+                    positions: positions,
+                    skybox: cubemap.cubemap.sampled(),
+                    view_scale: [1.0 / (f * aspect_ratio), 1.0 / f],
+                    camera_rotation: Transform::from_rotation(self.transform.get_rotation()).to_array(),
+                };
+                frame.draw(vertices, indices, program, &uniforms, &params)?;
+            },
+            _ => {}
+        }
+        Ok(())
+    }
+
+    ///Finds the meshes the camera would draw in a frame with this height / width aspect ratio, in the order they would be drawn
+    ///Returns the entity of each mesh's GameObject with the mesh's index, and updates the render stats as drawing does
+    ///Useful for profiling culling and sorting without drawing
+    pub fn find_visible_meshes(&mut self, scene: &Scene, aspect_ratio: f32) -> Vec<(Entity, usize)> {
+        let entities = scene.object_entities();
+        self.find_draws(scene, aspect_ratio).iter().map(|d| (entities[d.object], d.mesh)).collect()
+    }
+
+    ///Culls and sorts the scene's meshes into the order they are drawn
+    fn find_draws(&mut self, scene: &Scene, aspect_ratio: f32) -> Vec<MeshDraw> {
+        self.stats = RenderStats::default();
+        let view = self.transform.to_matrix().inverse();
+        let camera_position = self.transform.get_position();
+
+        //Find every mesh that needs drawing, with what is needed to sort them
+        let mut draws = Vec::new();
+        for (object_index, object) in scene.objects().iter().enumerate() {
+            if !object.visible || !object.layers.intersects(self.layers) {
+                self.stats.hidden += object.meshes.len();
+                continue;
+            }
+            let object_matrix = object.transform.to_matrix();
+            for (mesh_index, mesh) in object.meshes.iter().enumerate() {
+                if !mesh.1.visible {
+                    self.stats.hidden += 1;
+                    continue;
+                }
+                if mesh.1.instances.as_ref().is_some_and(|i| i.is_empty()) {
+                    continue;
+                }
+
+                let world = mesh.0.to_matrix() * object_matrix;
+                if self.culling {
+                    let projection = mesh.1.shader.get_projection(self.fov, aspect_ratio, self.zfar, self.znear);
+                    if !self.is_visible(&view, projection, &world, &mesh.1) {
+                        self.stats.culled += 1;
+                        continue;
+                    }
+                }
+                self.stats.drawn += 1;
+
+                draws.push(MeshDraw {
+                    object: object_index,
+                    mesh: mesh_index,
+                    blend_mode: mesh.1.shader.get_blend_mode(),
+                    sort_order: mesh.1.sort_order,
+                    distance: (world.transform_point(mesh.1.get_bounds().get_centre()) - camera_position).length(),
+                });
             }
+        }
 
-            let uniforms = uniform! {
-                positions: [
-                    [x_end, y_end, 0.0, 1.0],
-                    [x_start, y_end, 0.0, 1.0],
-                    [x_end, y_start, 0.0, 1.0],
-                    [x_start, y_start, 0.0, 1.0]
-                ],
-                clear_colour:clear_colour,             
-            };
+        //Opaque meshes go first so that transparent meshes can blend with them,
+        //then transparent meshes from back to front so that they blend with each other
+        draws.sort_by(|a, b| {
+            a.blend_mode.is_transparent().cmp(&b.blend_mode.is_transparent())
+                .then(a.sort_order.cmp(&b.sort_order))
+                .then_with(|| match a.blend_mode.is_transparent() {
+                    true => b.distance.total_cmp(&a.distance),
+                    false => Ordering::Equal
+                })
+        });
+
+        draws
+    }
 
-            frame.draw(self.clear_vertices.as_ref().unwrap(), self.clear_indices.as_ref().unwrap(), self.clear_program.as_ref().unwrap(), &uniforms, &Default::default()).unwrap();
+    pub(crate) fn render<S: Surface>(&mut self, frame: &mut S, scene: &mut Scene, window: &Window, viewport: Viewport) -> Result<(), RenderError> {
+        match self.target.clone() {
+            Some(target) => self.render_to_texture(&target, scene, window),
+            None => self.render_to_surface(frame, scene, window, viewport)
         }
+    }
+
+    fn render_to_texture(&mut self, target: &Texture, scene: &mut Scene, window: &Window) -> Result<(), RenderError> {
+        let texture = target.get();
+        let (width, height) = texture.dimensions();
+
+        //Keep the depth buffer between frames, unless the target has changed size
+        let depth = match take(&mut self.target_depth) {
+            Some(d) if d.0.get_dimensions() == (width, height) => d,
+            _ => TargetDepth(DepthRenderBuffer::new(&window.display, DepthFormat::I24, width, height).map_err(AssetCreationError::from)?)
+        };
+
+        let result = SimpleFrameBuffer::with_depth_buffer(&window.display, &*texture, &depth.0)
+            .map_err(RenderError::from)
+            .and_then(|mut surface| {
+                surface.clear_color(0.0, 0.0, 0.0, 0.0);
+                self.render_to_surface(&mut surface, scene, window, Viewport::FULL)
+            });
+
+        self.target_depth = Some(depth);
+        result
+    }
 
+    fn render_to_surface<S: Surface>(&mut self, frame: &mut S, scene: &mut Scene, window: &Window, viewport: Viewport) -> Result<(), RenderError> {
         let (width, height) = frame.get_dimensions();
 
+        let (x_start, x_end, y_start, y_end) = viewport.to_ndc();
         let x_start_mapped = ((x_start + 1.0) / 2.0 * width as f32) as u32;
         let x_end_mapped = ((x_end + 1.0) / 2.0 * width as f32) as u32;
         let y_start_mapped = ((y_start + 1.0) / 2.0 * height as f32) as u32;
         let y_end_mapped = ((y_end + 1.0) / 2.0 * height as f32) as u32;
+        let rect = Rect{left: x_start_mapped, bottom: y_start_mapped, width: x_end_mapped - x_start_mapped, height: y_end_mapped - y_start_mapped};
 
-        let aspect_ratio = ((y_end_mapped - y_start_mapped) as f32) / ((x_end_mapped - x_start_mapped) as f32);
+        let mut aspect_ratio = ((y_end_mapped - y_start_mapped) as f32) / ((x_end_mapped - x_start_mapped) as f32);
+        //Textures are always drawn at their own size, only the window follows the scaling policy
+        if self.target.is_none() {
+            aspect_ratio = window.scaling_policy.adjust_aspect_ratio(aspect_ratio, (width, height));
+        }
+        self.aspect_ratio = aspect_ratio;
 
-        for object in &mut scene.objects {
-            for mesh in &mut object.meshes {
+        self.clear(frame, window, viewport, &rect, aspect_ratio)?;
 
-                let positions = glium::VertexBuffer::new(&window.display, &mesh.1.vertices).unwrap();
-                let indices = glium::IndexBuffer::new(&window.display, glium::index::PrimitiveType::TrianglesList,
-                                                    &mesh.1.indices).unwrap();
+        let draws = self.find_draws(scene, aspect_ratio);
 
+        for draw in draws {
+            let object = &mut scene.objects_mut()[draw.object];
+            let object_transform = object.transform;
+            let mesh = &mut object.meshes[draw.mesh];
 
-                let params = glium::DrawParameters {
-                    depth: glium::Depth {
-                        test: glium::draw_parameters::DepthTest::IfLess,
-                        write: true,
-                        .. Default::default()
-                    },
-                    backface_culling: glium::draw_parameters::BackfaceCullingMode::CullClockwise,
-                    viewport: Some(Rect{left: x_start_mapped, bottom: y_start_mapped, width: x_end_mapped - x_start_mapped, height: y_end_mapped - y_start_mapped}),
-                    scissor: Some(Rect{left: x_start_mapped, bottom: y_start_mapped, width: x_end_mapped - x_start_mapped, height: y_end_mapped - y_start_mapped}),
-                    .. Default::default()
-                };
+            //Meshes without instances are drawn as a single instance that doesn't move them
+            let instances: Vec<InstanceVertex> = match &mesh.1.instances {
+                Some(instances) => instances.iter().map(InstanceVertex::from).collect(),
+                None => vec![InstanceVertex::default()]
+            };
+            let instances = glium::VertexBuffer::new(&window.display, &instances).map_err(AssetCreationError::from)?;
+            let positions = glium::VertexBuffer::new(&window.display, &mesh.1.vertices).map_err(AssetCreationError::from)?;
+            let indices = glium::IndexBuffer::new(&window.display, glium::index::PrimitiveType::TrianglesList,
+                                                &mesh.1.indices).map_err(AssetCreationError::from)?;
 
-                let (program, uniforms) = {
-                    let shader = &mut mesh.1.shader;
-                    let mut uniforms= ShaderUniforms(vec![]);
 
-                    shader.get_uniforms(&self.transform, &mesh.0, self.fov, aspect_ratio, self.zfar, self.znear, &object.transform, &mut uniforms);
+            let params = glium::DrawParameters {
+                depth: glium::Depth {
+                    test: glium::draw_parameters::DepthTest::IfLess,
+                    //Transparent meshes shouldn't hide what is behind them
+                    write: !draw.blend_mode.is_transparent(),
+                    .. Default::default()
+                },
+                blend: draw.blend_mode.to_blend(),
+                backface_culling: glium::draw_parameters::BackfaceCullingMode::CullClockwise,
+                viewport: Some(rect),
+                scissor: Some(rect),
+                .. Default::default()
+            };
 
-                    shader.create_assets(&window.display).unwrap();
+            let (program, uniforms) = {
+                let shader = &mut mesh.1.shader;
+                let mut uniforms= ShaderUniforms(vec![]);
 
-                    let program = shader.get_program();
-                    (program, uniforms)
-                };
+                shader.get_uniforms(&self.transform, &mesh.0, self.fov, aspect_ratio, self.zfar, self.znear, &object_transform, &mut uniforms);
 
-                frame.draw(&positions, &indices, &program.as_ref().unwrap(), &ShaderUniforms::from(uniforms), &params).unwrap();
+                shader.create_assets(&window.display)?;
 
-            }
+                let program = shader.get_program();
+                (program, uniforms)
+            };
+            let Some(program) = program else {
+                continue;
+            };
+            //Only fails without instancing support, which OpenGL 3.3 always has
+            let Ok(instances) = instances.per_instance() else {
+                continue;
+            };
+
+            frame.draw((&positions, instances), &indices, program, &uniforms, &params)?;
         }
+        Ok(())
     }
 }
 
+///The depth buffer used when a camera draws into a texture
+struct TargetDepth(DepthRenderBuffer);
+
+impl Debug for TargetDepth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "depth buffer: {:?}", self.0.get_dimensions())
+    }
+}
+
+///A mesh that a camera is going to draw this frame
+struct MeshDraw {
+    object: usize,
+    mesh: usize,
+    blend_mode: BlendMode,
+    sort_order: i32,
+    ///Distance from the camera to the centre of the mesh's bounds
+    distance: f64,
+}
+
 /// A struct allowing multiple cameras in a scene to draw to one window
 #[derive(Debug)]
 pub struct SplitView {
-    pub views: Vec<(Renderable, f32, f32, f32, f32)>
+    pub views: Vec<View>
+}
+
+///A renderable drawn to part of a SplitView
+#[derive(Debug)]
+pub struct View {
+    pub renderable: Renderable,
+    ///The part of the SplitView the renderable draws to
+    pub viewport: Viewport,
+    ///Views with a higher priority are drawn later, so they appear over views with a lower priority
+    ///Views with the same priority are drawn in order
+    pub priority: i32,
+    ///Keeps the view at a width / height aspect ratio by shrinking it and centring it within its viewport
+    pub aspect_ratio: Option<f32>,
+}
+
+impl View {
+    pub fn new(renderable: Renderable, viewport: Viewport) -> Self {
+        View { renderable, viewport, priority: 0, aspect_ratio: None }
+    }
+
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn with_aspect_ratio(mut self, aspect_ratio: f32) -> Self {
+        self.aspect_ratio = Some(aspect_ratio);
+        self
+    }
+
+    ///Gets the part of the surface the view draws to, given the part its SplitView draws to
+    fn get_rect(&self, parent: Viewport, surface: (u32, u32)) -> Viewport {
+        let rect = self.viewport.within(parent);
+        match self.aspect_ratio {
+            Some(a) => rect.letterboxed(a, surface),
+            None => rect
+        }
+    }
 }
 
 impl SplitView {
-    //TODO: test this logic
-    fn render(&mut self, frame: &mut Frame, scene: &mut Scene, window: &Window, x_start: f32, x_end: f32, y_start: f32, y_end: f32) {
-        let mx = (x_end - x_start) / 2.0;
-        let cx = (x_end + x_start) / 2.0;
-        let my = (y_end - y_start) / 2.0;
-        let cy = (y_end + y_start) / 2.0;
-        for view in self.views.iter_mut() {
-            let new_x_start = mx * view.1 + cx;
-            let new_x_end = mx * view.2 + cx;
-            let new_y_start = my * view.3 + cy;
-            let new_y_end = my * view.4 + cy;
-
-            view.0.render(frame, scene, window, new_x_start, new_x_end, new_y_start, new_y_end);
-        }
-    }
-}
\ No newline at end of file
+    pub fn new(views: Vec<View>) -> Self {
+        SplitView { views }
+    }
+
+    ///Places renderables side by side, from left to right
+    pub fn horizontal(renderables: Vec<Renderable>) -> Self {
+        let viewports = Viewport::horizontal_split(renderables.len());
+        SplitView::from_viewports(renderables, viewports)
+    }
+
+    ///Places renderables on top of each other, from top to bottom
+    pub fn vertical(renderables: Vec<Renderable>) -> Self {
+        let viewports = Viewport::vertical_split(renderables.len());
+        SplitView::from_viewports(renderables, viewports)
+    }
+
+    ///Places renderables in a grid with a number of columns, going along each row from the top left
+    pub fn grid(columns: usize, renderables: Vec<Renderable>) -> Self {
+        let columns = columns.max(1);
+        let rows = renderables.len().div_ceil(columns);
+        let viewports = Viewport::grid(columns, rows);
+        SplitView::from_viewports(renderables, viewports)
+    }
+
+    ///Draws main over the whole view, then inset over it, for example a minimap in a corner made with Viewport::inset
+    pub fn picture_in_picture(main: Renderable, inset: Renderable, inset_viewport: Viewport) -> Self {
+        SplitView::new(vec![
+            View::new(main, Viewport::FULL),
+            View::new(inset, inset_viewport).with_priority(1),
+        ])
+    }
+
+    ///Draws a renderable at a fixed width / height aspect ratio, leaving bars where it doesn't fit
+    pub fn letterboxed(renderable: Renderable, aspect_ratio: f32) -> Self {
+        SplitView::new(vec![View::new(renderable, Viewport::FULL).with_aspect_ratio(aspect_ratio)])
+    }
+
+    fn from_viewports(renderables: Vec<Renderable>, viewports: Vec<Viewport>) -> Self {
+        SplitView::new(renderables.into_iter().zip(viewports).map(|(r, v)| View::new(r, v)).collect())
+    }
+
+    ///Gets the indices of the views in the order they are drawn
+    fn draw_order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.views.len()).collect();
+        order.sort_by_key(|&i| self.views[i].priority);
+        order
+    }
+
+    fn render<S: Surface>(&mut self, frame: &mut S, scene: &mut Scene, window: &Window, viewport: Viewport) -> Result<(), RenderError> {
+        let surface = frame.get_dimensions();
+        for index in self.draw_order() {
+            let view = &mut self.views[index];
+            let rect = view.get_rect(viewport, surface);
+            view.renderable.render(frame, scene, window, rect)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{GameObject, Mesh};
+
+    const COLOUR: [f32; 4] = [0.1, 0.2, 0.3, 1.0];
+
+    #[test]
+    fn clear_modes_choose_what_is_cleared() {
+        let gradient = ClearMode::Gradient { top: COLOUR, bottom: [0.0, 0.0, 0.0, 1.0] };
+        for (mode, fill, depth, background) in [
+            (ClearMode::None, None, false, false),
+            (ClearMode::Depth, None, true, false),
+            (ClearMode::Colour(COLOUR), Some(COLOUR), true, false),
+            (gradient, None, true, true),
+        ] {
+            assert_eq!(mode.get_fill_colour(), fill, "{:?}", mode);
+            assert_eq!(mode.clears_depth(), depth, "{:?}", mode);
+            assert_eq!(mode.draws_background(), background, "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn clear_colour_picks_mode() {
+        let mut camera = Camera::new(Transform::origin(), 3.0);
+        assert!(matches!(camera.get_clear_mode(), ClearMode::Depth));
+
+        camera.set_clear_colour(Some(COLOUR));
+        assert!(matches!(camera.get_clear_mode(), ClearMode::Colour(c) if *c == COLOUR));
+        camera.set_clear_colour(None);
+        assert!(matches!(camera.get_clear_mode(), ClearMode::Depth));
+    }
+
+    fn cube_object(name: &str, z: f64) -> GameObject {
+        let mut object = GameObject::new(None, name.to_string(), Transform::from_pos(0.0, 0.0, z));
+        object.meshes.push((Transform::origin(), Mesh::cube(Box::new(crate::shaders::Unshaded3D::new(COLOUR)))));
+        object
+    }
+
+    #[test]
+    fn find_draws_skips_hidden_meshes_and_other_layers() {
+        let camera = Camera::new(Transform::origin(), 3.0);
+        let mut scene = Scene::new(Box::new(crate::SceneCallbackDefault {}), Renderable::Camera(camera));
+
+        let mut shown = cube_object("shown", 10.0);
+        let mut hidden_mesh = cube_object("hidden mesh", 10.0).meshes.remove(0);
+        hidden_mesh.1.visible = false;
+        shown.meshes.push(hidden_mesh);
+        let shown = scene.add_object(shown);
+
+        let mut hidden = cube_object("hidden", 10.0);
+        hidden.visible = false;
+        scene.add_object(hidden);
+
+        let mut other_layer = cube_object("other layer", 10.0);
+        other_layer.layers = RenderLayers::layer(1);
+        scene.add_object(other_layer);
+
+        scene.add_object(cube_object("behind", -10.0));
+
+        let mut camera = Camera::new(Transform::origin(), 3.0);
+        camera.layers = RenderLayers::DEFAULT;
+        assert_eq!(camera.find_visible_meshes(&scene, 1.0), [(shown, 0)]);
+        assert_eq!(camera.get_render_stats(), RenderStats { drawn: 1, culled: 1, hidden: 3 });
+
+        //Drawing every layer brings the other layer's object back
+        camera.layers = RenderLayers::ALL;
+        assert_eq!(camera.find_visible_meshes(&scene, 1.0).len(), 2);
+        assert_eq!(camera.get_render_stats(), RenderStats { drawn: 2, culled: 1, hidden: 2 });
+    }
+
+    #[test]
+    fn find_draws_groups_instances_into_one_draw() {
+        let camera = Camera::new(Transform::origin(), 3.0);
+        let mut scene = Scene::new(Box::new(crate::SceneCallbackDefault {}), Renderable::Camera(camera));
+
+        //The object is behind the camera, but its instances are in front of it
+        let mut instanced = cube_object("instanced", -10.0);
+        let instances = (0..100).map(|i| crate::Instance::new(Transform::from_pos(i as f64 - 50.0, 0.0, 30.0))).collect();
+        instanced.meshes[0].1 = Mesh::cube(Box::new(crate::shaders::Unshaded3D::new(COLOUR))).with_instances(instances);
+        let instanced = scene.add_object(instanced);
+
+        let mut empty = cube_object("no instances", 10.0);
+        empty.meshes[0].1.instances = Some(Vec::new());
+        scene.add_object(empty);
+
+        let mut camera = Camera::new(Transform::origin(), 3.0);
+        assert_eq!(camera.find_visible_meshes(&scene, 1.0), [(instanced, 0)]);
+        assert_eq!(camera.get_render_stats(), RenderStats { drawn: 1, culled: 0, hidden: 0 });
+    }
+}
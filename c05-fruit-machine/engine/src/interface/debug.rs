@@ -0,0 +1,975 @@
+use std::{cell::RefCell, f64::consts::TAU, fmt::Debug, mem::take, rc::Rc};
+
+use glium::{implement_vertex, Display, Program, Rect, Surface, VertexBuffer};
+
+use crate::{Aabb, Camera, Frame, Input, KeyCode, Mat4, Quat, Renderable, Scene, Transform, Vec3, Window};
+use crate::shaders::{RenderError, Unshaded2D, Unshaded3D};
+use crate::shaders::shader_priv::{AssetCreationError, ShaderPriv};
+
+const DEBUG_VERTEX_SHADER: &str = r#"
+    #version 140
+
+    in vec4 position;
+    in vec4 colour;
+
+    out vec4 v_colour;
+
+    void main() {
+        v_colour = colour;
+        gl_Position = position;
+    }
+"#;
+
+const DEBUG_FRAGMENT_SHADER: &str = r#"
+    #version 140
+
+    in vec4 v_colour;
+
+    out vec4 colour_out;
+
+    void main() {
+        colour_out = v_colour;
+    }
+"#;
+
+///Width of a glyph in font units
+const GLYPH_WIDTH: f32 = 4.0;
+///Distance from the start of one glyph to the next in font units
+const GLYPH_ADVANCE: f32 = 6.0;
+///Distance from the top of one line of text to the next in font units
+const LINE_HEIGHT: f32 = 9.0;
+///Height of capital letters in font units
+const GLYPH_HEIGHT: f32 = 6.0;
+
+///Gets the lines making up a character, as "x1y1x2y2" groups on a 4 by 6 grid starting at the bottom left
+///Lowercase letters are drawn as capitals, and unknown characters as a question mark
+fn glyph(c: char) -> &'static str {
+    match c.to_ascii_uppercase() {
+        'A' => "0004 0426 2644 4440 0343",
+        'B' => "0006 0636 3645 4544 4433 0333 3342 4241 4130 3000",
+        'C' => "4606 0600 0040",
+        'D' => "0006 0626 2644 4442 4220 2000",
+        'E' => "4606 0600 0040 0333",
+        'F' => "4606 0600 0333",
+        'G' => "4606 0600 0040 4043 4323",
+        'H' => "0006 4046 0343",
+        'I' => "0646 2620 0040",
+        'J' => "4640 4000 0002",
+        'K' => "0006 4603 0340",
+        'L' => "0600 0040",
+        'M' => "0006 0623 2346 4640",
+        'N' => "0006 0640 4046",
+        'O' => "0006 0646 4640 4000",
+        'P' => "0006 0646 4643 4303",
+        'Q' => "0006 0646 4640 4000 2240",
+        'R' => "0006 0646 4643 4303 2340",
+        'S' => "4606 0603 0343 4340 4000",
+        'T' => "0646 2620",
+        'U' => "0600 0040 4046",
+        'V' => "0620 2046",
+        'W' => "0610 1023 2330 3046",
+        'X' => "0046 0640",
+        'Y' => "0623 4623 2320",
+        'Z' => "0646 4600 0040",
+        '0' => "0006 0646 4640 4000 0046",
+        '1' => "1526 2620 1030",
+        '2' => "0646 4643 4303 0300 0040",
+        '3' => "0646 4640 4000 1343",
+        '4' => "0603 0343 4640",
+        '5' => "4606 0603 0343 4340 4000",
+        '6' => "4606 0600 0040 4043 4303",
+        '7' => "0646 4610",
+        '8' => "0006 0646 4640 4000 0343",
+        '9' => "4303 0306 0646 4640 4000",
+        '.' => "2021",
+        ',' => "2110",
+        ':' => "2122 2425",
+        '-' => "1333",
+        '+' => "1333 2224",
+        '=' => "1232 1434",
+        '_' => "0040",
+        '/' => "0046",
+        '(' => "3625 2521 2130",
+        ')' => "1625 2521 2110",
+        '[' => "3616 1610 1030",
+        ']' => "1636 3630 3010",
+        '>' => "1533 3311",
+        '<' => "3513 1331",
+        '%' => "0046 0605 4041",
+        '\'' => "2624",
+        '!' => "2622 2021",
+        ' ' => "",
+        _ => "0516 1636 3645 4523 2322 2021",
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct DebugVertex {
+    position: [f32; 4],
+    colour: [f32; 4],
+}
+implement_vertex!(DebugVertex, position, colour);
+
+///Lines and filled rectangles collected over a frame, drawn with one draw call for each
+///Points are given in clip space, so anything can be drawn in one batch whatever projection it uses
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LineBatch {
+    lines: Vec<DebugVertex>,
+    triangles: Vec<DebugVertex>,
+}
+
+impl LineBatch {
+    pub(crate) fn clear(&mut self) {
+        self.lines.clear();
+        self.triangles.clear();
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.lines.is_empty() && self.triangles.is_empty()
+    }
+
+    ///Adds a line between two points in clip space
+    pub(crate) fn line_clip(&mut self, a: [f64; 4], b: [f64; 4], colour: [f32; 4]) {
+        self.lines.push(DebugVertex { position: a.map(|x| x as f32), colour });
+        self.lines.push(DebugVertex { position: b.map(|x| x as f32), colour });
+    }
+
+    ///Adds a line between two points, moved into clip space by matrix
+    ///Lines are cut where they pass behind the camera, which would otherwise flip them across the screen
+    pub(crate) fn line(&mut self, a: Vec3, b: Vec3, matrix: &Mat4, colour: [f32; 4]) {
+        let (mut a, mut b) = (matrix.transform_point4(a), matrix.transform_point4(b));
+        if a[3] < NEAR_W && b[3] < NEAR_W {
+            return;
+        }
+        if a[3] < NEAR_W {
+            a = clip_to_near(b, a);
+        }
+        else if b[3] < NEAR_W {
+            b = clip_to_near(a, b);
+        }
+        self.line_clip(a, b, colour);
+    }
+
+    ///Adds the twelve edges of a box
+    pub(crate) fn wire_box(&mut self, bounds: &Aabb, matrix: &Mat4, colour: [f32; 4]) {
+        let (min, max) = (bounds.min, bounds.max);
+        let corner = |i: usize| Vec3::new(
+            if i & 1 == 0 { min.x } else { max.x },
+            if i & 2 == 0 { min.y } else { max.y },
+            if i & 4 == 0 { min.z } else { max.z },
+        );
+        //Each edge joins two corners that differ along one axis
+        for i in 0..8 {
+            for axis in [1, 2, 4] {
+                if i & axis == 0 {
+                    self.line(corner(i), corner(i | axis), matrix, colour);
+                }
+            }
+        }
+    }
+
+    ///Adds a filled rectangle, in pixels from the top left of a surface
+    pub(crate) fn rect(&mut self, position: (f32, f32), size: (f32, f32), colour: [f32; 4], surface: (u32, u32)) {
+        let corner = |x: f32, y: f32| DebugVertex { position: pixel_to_clip((x, y), surface), colour };
+        let (x, y, w, h) = (position.0, position.1, size.0, size.1);
+        self.triangles.extend([
+            corner(x, y), corner(x + w, y), corner(x, y + h),
+            corner(x + w, y), corner(x + w, y + h), corner(x, y + h),
+        ]);
+    }
+
+    ///Adds text with its top left at a position in pixels from the top left of a surface
+    ///scale is the size of one font unit in pixels, where capital letters are six units tall
+    pub(crate) fn text(&mut self, text: &str, position: (f32, f32), scale: f32, colour: [f32; 4], surface: (u32, u32)) {
+        let mut x = position.0;
+        let mut y = position.1;
+        for c in text.chars() {
+            if c == '\n' {
+                x = position.0;
+                y += LINE_HEIGHT * scale;
+                continue;
+            }
+
+            let segments = glyph(c).as_bytes();
+            for segment in segments.split(|b| *b == b' ').filter(|s| s.len() == 4) {
+                //Font units go up from the baseline, but pixels go down from the top
+                let point = |gx: u8, gy: u8| {
+                    let pixel = (x + (gx - b'0') as f32 * scale, y + (GLYPH_HEIGHT - (gy - b'0') as f32) * scale);
+                    pixel_to_clip(pixel, surface).map(|v| v as f64)
+                };
+                self.line_clip(point(segment[0], segment[1]), point(segment[2], segment[3]), colour);
+            }
+            x += GLYPH_ADVANCE * scale;
+        }
+    }
+}
+
+///Gets the size of text in pixels when drawn at a scale
+pub(crate) fn text_size(text: &str, scale: f32) -> (f32, f32) {
+    let lines = text.lines().count().max(1);
+    let longest = text.lines().map(|l| l.chars().count()).max().unwrap_or(0);
+    let width = match longest {
+        0 => 0.0,
+        n => ((n - 1) as f32 * GLYPH_ADVANCE + GLYPH_WIDTH) * scale
+    };
+    (width, ((lines - 1) as f32 * LINE_HEIGHT + GLYPH_HEIGHT) * scale)
+}
+
+///Smallest w in clip space that is in front of the camera
+const NEAR_W: f64 = 1e-5;
+
+///Gets the point where a line from a point in front of the camera to one behind it crosses NEAR_W
+fn clip_to_near(inside: [f64; 4], outside: [f64; 4]) -> [f64; 4] {
+    let t = (inside[3] - NEAR_W) / (inside[3] - outside[3]);
+    [0, 1, 2, 3].map(|i| inside[i] + (outside[i] - inside[i]) * t)
+}
+
+///Gets the scene's main camera and the matrix from world space to camera space
+///None if the main camera is a SplitView, which has no single camera to draw from
+fn main_camera_view(scene: &Scene) -> Option<(&Camera, Mat4)> {
+    match &scene.main_camera {
+        Renderable::Camera(camera) => camera.transform.to_matrix().inverse().map(|view| (camera, view)),
+        Renderable::SplitView(_) => None
+    }
+}
+
+///Gets the part of a surface the main camera draws into, in pixels
+fn policy_rect(window: &Window, surface: (u32, u32)) -> Rect {
+    let viewport = window.scaling_policy.get_viewport(surface);
+    Rect {
+        left: (viewport.x * surface.0 as f32) as u32,
+        bottom: (viewport.y * surface.1 as f32) as u32,
+        width: (viewport.width * surface.0 as f32) as u32,
+        height: (viewport.height * surface.1 as f32) as u32,
+    }
+}
+
+fn pixel_to_clip(pixel: (f32, f32), surface: (u32, u32)) -> [f32; 4] {
+    [pixel.0 / surface.0 as f32 * 2.0 - 1.0, 1.0 - pixel.1 / surface.1 as f32 * 2.0, 0.0, 1.0]
+}
+
+///Draws line batches
+#[derive(Default)]
+pub(crate) struct LineRenderer {
+    program: Option<Program>,
+}
+
+impl Debug for LineRenderer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LineRenderer").finish_non_exhaustive()
+    }
+}
+
+impl LineRenderer {
+    ///Drops any gpu assets so that they are recreated on the next render
+    pub(crate) fn invalidate_assets(&mut self) {
+        self.program = None;
+    }
+
+    ///Draws a batch over everything already drawn, within a viewport or the whole surface if None
+    pub(crate) fn render<S: Surface>(&mut self, frame: &mut S, display: &Display, batch: &LineBatch, viewport: Option<Rect>) -> Result<(), RenderError> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        let program = match &mut self.program {
+            Some(program) => program,
+            program => program.insert(Program::from_source(display, DEBUG_VERTEX_SHADER, DEBUG_FRAGMENT_SHADER, None).map_err(AssetCreationError::from)?),
+        };
+
+        let params = glium::DrawParameters {
+            blend: glium::Blend::alpha_blending(),
+            viewport,
+            scissor: viewport,
+            .. Default::default()
+        };
+
+        //Triangles are backgrounds, so go first
+        for (vertices, primitive) in [(&batch.triangles, glium::index::PrimitiveType::TrianglesList), (&batch.lines, glium::index::PrimitiveType::LinesList)] {
+            if vertices.is_empty() {
+                continue;
+            }
+            let buffer = VertexBuffer::new(display, vertices).map_err(AssetCreationError::from)?;
+            frame.draw(&buffer, glium::index::NoIndices(primitive), program, &glium::uniforms::EmptyUniforms, &params)?;
+        }
+        Ok(())
+    }
+}
+
+///How the debug overlay's arrow keys change the selected object's transform
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TransformEditMode {
+    #[default]
+    Move,
+    Rotate,
+    Scale,
+}
+
+impl TransformEditMode {
+    fn next(self) -> Self {
+        match self {
+            TransformEditMode::Move => TransformEditMode::Rotate,
+            TransformEditMode::Rotate => TransformEditMode::Scale,
+            TransformEditMode::Scale => TransformEditMode::Move,
+        }
+    }
+}
+
+///A change to the selected object's transform asked for by the overlay's keys
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TransformEdit {
+    mode: TransformEditMode,
+    ///-1, 0 or 1 along each axis
+    direction: Vec3,
+    ///Whether shift is held, editing ten times faster
+    fast: bool,
+}
+
+impl TransformEdit {
+    ///Edits a transform by delta_time seconds' worth of the edit
+    fn apply(self, transform: &mut Transform, delta_time: f64) {
+        let delta = delta_time * if self.fast { 10.0 } else { 1.0 };
+        let direction = self.direction;
+        match self.mode {
+            //One unit per second
+            TransformEditMode::Move => transform.translate(direction * delta),
+            //Quarter of a turn per second
+            TransformEditMode::Rotate => {
+                let angle = std::f64::consts::FRAC_PI_2 * delta;
+                transform.rotate(Quat::from_axis_angle(Vec3::X, direction.y * angle) *
+                    Quat::from_axis_angle(Vec3::Y, direction.x * angle) *
+                    Quat::from_axis_angle(Vec3::Z, direction.z * angle));
+            },
+            //Doubles in size every second
+            TransformEditMode::Scale => {
+                let factor = |d: f64| 2f64.powf(d * delta);
+                transform.set_scale(transform.get_scale().scale(Vec3::new(factor(direction.x), factor(direction.y), factor(direction.z))));
+            },
+        }
+    }
+}
+
+///An overlay showing performance and the scene's objects, drawn over the scene
+///While it is shown:
+///- Page up and page down select an object
+///- Tab switches between moving, rotating and scaling the selected object
+///- The arrow keys, comma and full stop edit the selected object along x, y and z, faster while shift is held
+///- 1, 2 and 3 toggle axes, bounding boxes and wireframes
+///
+///Gizmos are only drawn when the scene's main camera is a single camera
+pub struct DebugOverlay {
+    pub enabled: bool,
+    ///Key that shows and hides the overlay, or None to only change it from code
+    pub toggle_key: Option<KeyCode>,
+    ///Draws each object's x, y and z axes in red, green and blue
+    pub show_axes: bool,
+    ///Draws a box around each mesh
+    pub show_bounds: bool,
+    ///Draws the edges of each mesh's triangles
+    pub show_wireframe: bool,
+    selected: usize,
+    edit_mode: TransformEditMode,
+
+    gizmos: LineBatch,
+    text: LineBatch,
+    renderer: LineRenderer,
+}
+
+impl Default for DebugOverlay {
+    fn default() -> Self {
+        DebugOverlay {
+            enabled: false,
+            toggle_key: Some(KeyCode::F3),
+            show_axes: true,
+            show_bounds: true,
+            show_wireframe: false,
+            selected: 0,
+            edit_mode: TransformEditMode::Move,
+            gizmos: LineBatch::default(),
+            text: LineBatch::default(),
+            renderer: LineRenderer::default(),
+        }
+    }
+}
+
+impl Debug for DebugOverlay {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DebugOverlay")
+            .field("enabled", &self.enabled)
+            .field("toggle_key", &self.toggle_key)
+            .field("selected", &self.selected)
+            .field("edit_mode", &self.edit_mode)
+            .finish_non_exhaustive()
+    }
+}
+
+///Text size of the overlay, in pixels per font unit
+const TEXT_SCALE: f32 = 2.0;
+const TEXT_COLOUR: [f32; 4] = [0.8, 1.0, 0.8, 1.0];
+const SELECTED_COLOUR: [f32; 4] = [1.0, 0.9, 0.2, 1.0];
+const PANEL_COLOUR: [f32; 4] = [0.0, 0.0, 0.0, 0.7];
+
+impl DebugOverlay {
+    ///Gets the index of the selected object in the scene
+    pub fn get_selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn get_edit_mode(&self) -> TransformEditMode {
+        self.edit_mode
+    }
+
+    pub(crate) fn invalidate_assets(&mut self) {
+        self.renderer.invalidate_assets();
+    }
+
+    ///Handles the overlay's keys, editing the scene's objects
+    pub(crate) fn update(&mut self, frame: &Frame, scene: &mut Scene) {
+        let count = scene.objects().len();
+        if let Some(edit) = self.handle_keys(&frame.input, count) {
+            edit.apply(&mut scene.objects_mut()[self.selected].transform, frame.time.delta_time.as_secs_f64());
+        }
+    }
+
+    ///Shows, hides and changes the overlay's settings from this frame's keys, given how many objects there are to select from
+    ///Gets how to edit the selected object, or None if no edit keys are held
+    fn handle_keys(&mut self, input: &Input, count: usize) -> Option<TransformEdit> {
+        if let Some(key) = self.toggle_key {
+            if input.is_key_pressed_this_frame(key) {
+                self.enabled = !self.enabled;
+            }
+        }
+        if !self.enabled {
+            return None;
+        }
+
+        if input.is_key_pressed_this_frame(KeyCode::Key1) { self.show_axes = !self.show_axes; }
+        if input.is_key_pressed_this_frame(KeyCode::Key2) { self.show_bounds = !self.show_bounds; }
+        if input.is_key_pressed_this_frame(KeyCode::Key3) { self.show_wireframe = !self.show_wireframe; }
+        if input.is_key_pressed_this_frame(KeyCode::Tab) { self.edit_mode = self.edit_mode.next(); }
+
+        if count == 0 {
+            return None;
+        }
+        if input.is_key_pressed_this_frame(KeyCode::PageDown) { self.selected += 1; }
+        if input.is_key_pressed_this_frame(KeyCode::PageUp) { self.selected = self.selected.wrapping_sub(1); }
+        self.selected = match self.selected {
+            usize::MAX => count - 1,
+            s => s % count
+        };
+
+        let axis = |negative: KeyCode, positive: KeyCode| {
+            input.is_key_pressed(positive) as i32 as f64 - input.is_key_pressed(negative) as i32 as f64
+        };
+        let direction = Vec3::new(axis(KeyCode::Left, KeyCode::Right), axis(KeyCode::Down, KeyCode::Up), axis(KeyCode::Comma, KeyCode::Period));
+        if direction == Vec3::ZERO {
+            return None;
+        }
+        let fast = input.is_key_pressed(KeyCode::LShift) || input.is_key_pressed(KeyCode::RShift);
+        Some(TransformEdit { mode: self.edit_mode, direction, fast })
+    }
+
+    ///Draws the overlay over the scene
+    pub(crate) fn render<S: Surface>(&mut self, frame: &mut S, scene: &Scene, window: &Window) -> Result<(), RenderError> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let surface = frame.get_dimensions();
+        if surface.0 == 0 || surface.1 == 0 {
+            return Ok(());
+        }
+
+        self.gizmos.clear();
+        self.add_gizmos(scene);
+        self.renderer.render(frame, &window.display, &self.gizmos, Some(policy_rect(window, surface)))?;
+
+        self.text.clear();
+        self.add_text(scene, &window.frame, surface);
+        self.renderer.render(frame, &window.display, &self.text, None)
+    }
+
+    fn add_gizmos(&mut self, scene: &Scene) {
+        let (camera, view) = match main_camera_view(scene) {
+            Some(c) => c,
+            None => return
+        };
+
+        for (index, object) in scene.objects().iter().enumerate() {
+            if !object.visible {
+                continue;
+            }
+            let object_matrix = object.transform.to_matrix();
+            let highlight = index == self.selected;
+
+            for (mesh_index, mesh) in object.meshes.iter().enumerate() {
+                //Use the mesh's own projection so the gizmos line up with what was drawn
+                let projection = mesh.1.shader.get_projection(camera.fov, camera.get_aspect_ratio(), camera.zfar, camera.znear);
+                let projection = Mat4::from_rows(projection.map(|row| row.map(|x| x as f64)));
+                let view_projection = view * projection;
+
+                if mesh_index == 0 && self.show_axes {
+                    let matrix = object_matrix * view_projection;
+                    self.gizmos.line(Vec3::ZERO, Vec3::X, &matrix, [1.0, 0.0, 0.0, 1.0]);
+                    self.gizmos.line(Vec3::ZERO, Vec3::Y, &matrix, [0.0, 1.0, 0.0, 1.0]);
+                    self.gizmos.line(Vec3::ZERO, Vec3::Z, &matrix, [0.0, 0.0, 1.0, 1.0]);
+                }
+                if !mesh.1.visible {
+                    continue;
+                }
+                if self.show_bounds {
+                    if let Some(bounds) = object.get_mesh_bounds(mesh_index) {
+                        let colour = if highlight { SELECTED_COLOUR } else { [0.0, 1.0, 1.0, 1.0] };
+                        self.gizmos.wire_box(&bounds, &view_projection, colour);
+                    }
+                }
+                if self.show_wireframe {
+                    let matrix = mesh.0.to_matrix() * object_matrix * view_projection;
+                    let position = |i: u32| {
+                        let p = mesh.1.vertices[i as usize].position;
+                        Vec3::new(p.0 as f64, p.1 as f64, p.2 as f64)
+                    };
+                    for triangle in mesh.1.indices.chunks_exact(3) {
+                        for (a, b) in [(triangle[0], triangle[1]), (triangle[1], triangle[2]), (triangle[2], triangle[0])] {
+                            self.gizmos.line(position(a), position(b), &matrix, [1.0, 0.4, 1.0, 0.6]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn add_text(&mut self, scene: &Scene, frame: &Frame, surface: (u32, u32)) {
+        let stats = &frame.time.stats;
+        let render_stats = scene.get_render_stats();
+        let on_off = |b: bool| if b { "ON" } else { "OFF" };
+
+        let mut lines = vec![
+            (format!("FPS {:.1}  FRAME {:.1}MS  99% {:.1}MS",
+                stats.get_fps(),
+                stats.get_average_frame_time().as_secs_f64() * 1000.0,
+                stats.get_frame_time_percentile(0.99).as_secs_f64() * 1000.0), TEXT_COLOUR),
+            (format!("UPDATE {:.2}MS  RENDER {:.2}MS  DRAW CALLS {}  CULLED {}  HIDDEN {}",
+                stats.get_average_update_time().as_secs_f64() * 1000.0,
+                stats.get_average_render_time().as_secs_f64() * 1000.0,
+                render_stats.drawn, render_stats.culled, render_stats.hidden), TEXT_COLOUR),
+            (format!("1 AXES {}  2 BOUNDS {}  3 WIRE {}  TAB {:?}",
+                on_off(self.show_axes), on_off(self.show_bounds), on_off(self.show_wireframe), self.edit_mode), TEXT_COLOUR),
+            (String::new(), TEXT_COLOUR),
+        ];
+
+        //Only list as many objects as fit on screen
+        let line_height = LINE_HEIGHT * TEXT_SCALE;
+        let room = ((surface.1 as f32 - 16.0) / line_height) as usize;
+        let shown = room.saturating_sub(lines.len());
+        let first = self.selected.saturating_sub(shown.saturating_sub(1));
+        for (index, object) in scene.objects().iter().enumerate().skip(first).take(shown) {
+            let p = object.transform.get_position();
+            let s = object.transform.get_scale();
+            let selected = index == self.selected;
+            lines.push((format!("{}{}  MESHES {}  POS {:.2} {:.2} {:.2}  SCALE {:.2} {:.2} {:.2}",
+                if selected { "> " } else { "  " }, object.name, object.meshes.len(), p.x, p.y, p.z, s.x, s.y, s.z),
+                if selected { SELECTED_COLOUR } else { TEXT_COLOUR }));
+        }
+
+        let width = lines.iter().map(|l| text_size(&l.0, TEXT_SCALE).0).fold(0.0, f32::max);
+        self.text.rect((4.0, 4.0), (width + 8.0, lines.len() as f32 * line_height + 4.0), PANEL_COLOUR, surface);
+        for (i, (text, colour)) in lines.iter().enumerate() {
+            self.text.text(text, (8.0, 8.0 + i as f32 * line_height), TEXT_SCALE, *colour, surface);
+        }
+    }
+}
+
+///Which projection the debug drawing API draws with, to match the shaders of what it draws over
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DebugProjection {
+    ///Matches the 3D shaders
+    #[default]
+    Perspective,
+    ///Matches the 2D shaders
+    Orthographic,
+}
+
+impl DebugProjection {
+    fn get_matrix(self, camera: &Camera) -> Mat4 {
+        let projection = match self {
+            DebugProjection::Perspective => Unshaded3D::new([1.0; 4]).get_projection(camera.fov, camera.get_aspect_ratio(), camera.zfar, camera.znear),
+            DebugProjection::Orthographic => Unshaded2D::new([1.0; 4]).get_projection(camera.fov, camera.get_aspect_ratio(), camera.zfar, camera.znear),
+        };
+        Mat4::from_rows(projection.map(|row| row.map(|x| x as f64)))
+    }
+}
+
+#[derive(Debug, Clone)]
+enum DebugShape {
+    Line(Vec3, Vec3),
+    Text(Vec3, String),
+}
+
+#[derive(Debug, Clone, Default)]
+struct DebugDrawState {
+    shapes: Vec<(DebugShape, [f32; 4], DebugProjection)>,
+}
+
+///Draws lines and text in world space for a single frame, over everything else the scene draws
+///Everything added during a frame is drawn from the scene's main camera in one draw call, then removed
+///Nothing is drawn when the main camera is a SplitView
+///Points are in world space, so a GameObject's points need moving by its transform first
+#[derive(Clone, Default)]
+pub struct DebugDraw {
+    state: Rc<RefCell<DebugDrawState>>,
+    projection: DebugProjection,
+}
+
+impl Debug for DebugDraw {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DebugDraw {{ shapes: {}, projection: {:?} }}", self.state.borrow().shapes.len(), self.projection)
+    }
+}
+
+///Number of lines making up each circle of a sphere
+const SPHERE_SEGMENTS: usize = 24;
+///Size of world space text, in pixels per font unit
+const WORLD_TEXT_SCALE: f32 = 2.0;
+
+impl DebugDraw {
+    ///Gets a DebugDraw that adds to the same frame, but draws with another projection
+    ///Use Orthographic when drawing over meshes with 2D shaders
+    pub fn with_projection(&self, projection: DebugProjection) -> DebugDraw {
+        DebugDraw { state: self.state.clone(), projection }
+    }
+
+    pub fn get_projection(&self) -> DebugProjection {
+        self.projection
+    }
+
+    pub fn line(&self, a: Vec3, b: Vec3, colour: [f32; 4]) {
+        self.state.borrow_mut().shapes.push((DebugShape::Line(a, b), colour, self.projection));
+    }
+
+    ///Draws a line from origin along direction, so its length shows the direction's length
+    pub fn ray(&self, origin: Vec3, direction: Vec3, colour: [f32; 4]) {
+        self.line(origin, origin + direction, colour);
+    }
+
+    ///Draws the edges of a box
+    pub fn wire_box(&self, bounds: &Aabb, colour: [f32; 4]) {
+        let (min, max) = (bounds.min, bounds.max);
+        let corner = |i: usize| Vec3::new(
+            if i & 1 == 0 { min.x } else { max.x },
+            if i & 2 == 0 { min.y } else { max.y },
+            if i & 4 == 0 { min.z } else { max.z },
+        );
+        for i in 0..8 {
+            for axis in [1, 2, 4] {
+                if i & axis == 0 {
+                    self.line(corner(i), corner(i | axis), colour);
+                }
+            }
+        }
+    }
+
+    ///Draws a sphere as three circles, one around each axis
+    pub fn sphere(&self, centre: Vec3, radius: f64, colour: [f32; 4]) {
+        for (u, v) in [(Vec3::X, Vec3::Y), (Vec3::Y, Vec3::Z), (Vec3::Z, Vec3::X)] {
+            let point = |i: usize| {
+                let angle = TAU * i as f64 / SPHERE_SEGMENTS as f64;
+                centre + (u * angle.cos() + v * angle.sin()) * radius
+            };
+            for i in 0..SPHERE_SEGMENTS {
+                self.line(point(i), point(i + 1), colour);
+            }
+        }
+    }
+
+    ///Draws a grid of cells centred on a point
+    ///axes are the sides of one cell, and cells is how many cells there are along each of them
+    pub fn grid(&self, centre: Vec3, axes: (Vec3, Vec3), cells: (usize, usize), colour: [f32; 4]) {
+        let (u, v) = axes;
+        let start = centre - u * (cells.0 as f64 / 2.0) - v * (cells.1 as f64 / 2.0);
+        for i in 0..=cells.0 {
+            let a = start + u * i as f64;
+            self.line(a, a + v * cells.1 as f64, colour);
+        }
+        for i in 0..=cells.1 {
+            let a = start + v * i as f64;
+            self.line(a, a + u * cells.0 as f64, colour);
+        }
+    }
+
+    ///Draws text with its top left at a point, always facing the screen and the same size however far away it is
+    pub fn text(&self, position: Vec3, text: &str, colour: [f32; 4]) {
+        self.state.borrow_mut().shapes.push((DebugShape::Text(position, text.to_string()), colour, self.projection));
+    }
+
+    ///Removes everything added this frame
+    pub fn clear(&self) {
+        self.state.borrow_mut().shapes.clear();
+    }
+
+    ///Draws everything added this frame from the scene's main camera, then removes it
+    pub(crate) fn render<S: Surface>(&self, frame: &mut S, scene: &Scene, window: &Window, renderer: &mut LineRenderer) -> Result<(), RenderError> {
+        let shapes = take(&mut self.state.borrow_mut().shapes);
+        let (camera, view) = match main_camera_view(scene) {
+            Some(c) => c,
+            None => return Ok(())
+        };
+        let surface = frame.get_dimensions();
+        let viewport = policy_rect(window, surface);
+        if shapes.is_empty() || viewport.width == 0 || viewport.height == 0 {
+            return Ok(());
+        }
+        let viewport_size = (viewport.width, viewport.height);
+
+        let perspective = view * DebugProjection::Perspective.get_matrix(camera);
+        let orthographic = view * DebugProjection::Orthographic.get_matrix(camera);
+        let mut batch = LineBatch::default();
+        for (shape, colour, projection) in shapes {
+            let matrix = match projection {
+                DebugProjection::Perspective => &perspective,
+                DebugProjection::Orthographic => &orthographic,
+            };
+            match shape {
+                DebugShape::Line(a, b) => batch.line(a, b, matrix, colour),
+                DebugShape::Text(position, text) => {
+                    let p = matrix.transform_point4(position);
+                    if p[3] < NEAR_W {
+                        continue;
+                    }
+                    let pixel = (
+                        ((p[0] / p[3] + 1.0) / 2.0 * viewport_size.0 as f64) as f32,
+                        ((1.0 - p[1] / p[3]) / 2.0 * viewport_size.1 as f64) as f32,
+                    );
+                    batch.text(&text, pixel, WORLD_TEXT_SCALE, colour, viewport_size);
+                }
+            }
+        }
+        renderer.render(frame, &window.display, &batch, Some(viewport))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: [f32; 4] = [1.0; 4];
+
+    ///Projection whose w is the point's z, so points with negative z are behind the camera
+    fn w_from_z() -> Mat4 {
+        Mat4::from_rows([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 1.0],
+            [0.0, 0.0, 0.0, 0.0],
+        ])
+    }
+
+    #[test]
+    fn line_batch_accumulates_and_clears() {
+        let mut batch = LineBatch::default();
+        assert!(batch.is_empty());
+
+        batch.line(Vec3::ZERO, Vec3::X, &Mat4::IDENTITY, WHITE);
+        assert_eq!(batch.lines.len(), 2);
+        assert_eq!(batch.lines[1].position, [1.0, 0.0, 0.0, 1.0]);
+
+        batch.wire_box(&Aabb::new(Vec3::ZERO, Vec3::ONE), &Mat4::IDENTITY, WHITE);
+        assert_eq!(batch.lines.len(), 2 + 12 * 2);
+
+        batch.rect((0.0, 0.0), (10.0, 10.0), WHITE, (100, 100));
+        assert_eq!(batch.triangles.len(), 6);
+        assert_eq!(batch.triangles[0].position, [-1.0, 1.0, 0.0, 1.0]);
+
+        //Spaces and newlines draw nothing, and each segment of "-" and "+" is one line
+        batch.text("- \n+", (0.0, 0.0), 1.0, WHITE, (100, 100));
+        assert_eq!(batch.lines.len(), 2 + 12 * 2 + 3 * 2);
+
+        batch.clear();
+        assert!(batch.is_empty());
+        assert!(batch.lines.is_empty() && batch.triangles.is_empty());
+    }
+
+    #[test]
+    fn line_batch_cuts_lines_behind_camera() {
+        let mut batch = LineBatch::default();
+        let matrix = w_from_z();
+
+        batch.line(Vec3::new(0.0, 0.0, -1.0), Vec3::new(1.0, 0.0, -2.0), &matrix, WHITE);
+        assert!(batch.is_empty());
+
+        batch.line(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0), &matrix, WHITE);
+        assert_eq!(batch.lines.len(), 2);
+        assert_eq!(batch.lines[0].position, [0.0, 0.0, 1.0, 1.0]);
+        assert!((batch.lines[1].position[3] as f64 - NEAR_W).abs() < 1e-9);
+    }
+
+    #[test]
+    fn text_size_measures_longest_line() {
+        assert_eq!(text_size("", 1.0), (0.0, GLYPH_HEIGHT));
+        assert_eq!(text_size("AB", 2.0), ((GLYPH_ADVANCE + GLYPH_WIDTH) * 2.0, GLYPH_HEIGHT * 2.0));
+        assert_eq!(text_size("A\nABC", 1.0), (2.0 * GLYPH_ADVANCE + GLYPH_WIDTH, LINE_HEIGHT + GLYPH_HEIGHT));
+    }
+
+    #[test]
+    fn debug_draw_accumulates_and_clears() {
+        let debug = DebugDraw::default();
+        let shapes = |debug: &DebugDraw| debug.state.borrow().shapes.len();
+
+        debug.line(Vec3::ZERO, Vec3::X, WHITE);
+        debug.ray(Vec3::ZERO, Vec3::Y, WHITE);
+        assert_eq!(shapes(&debug), 2);
+
+        debug.wire_box(&Aabb::new(Vec3::ZERO, Vec3::ONE), WHITE);
+        assert_eq!(shapes(&debug), 2 + 12);
+
+        debug.sphere(Vec3::ZERO, 1.0, WHITE);
+        assert_eq!(shapes(&debug), 2 + 12 + 3 * SPHERE_SEGMENTS);
+
+        debug.grid(Vec3::ZERO, (Vec3::X, Vec3::Z), (2, 3), WHITE);
+        assert_eq!(shapes(&debug), 2 + 12 + 3 * SPHERE_SEGMENTS + 3 + 4);
+
+        debug.text(Vec3::ZERO, "HI", WHITE);
+        assert!(matches!(&debug.state.borrow().shapes.last().unwrap().0, DebugShape::Text(_, text) if text == "HI"));
+
+        debug.clear();
+        assert_eq!(shapes(&debug), 0);
+    }
+
+    #[test]
+    fn debug_draw_projections_share_a_frame() {
+        let debug = DebugDraw::default();
+        let orthographic = debug.with_projection(DebugProjection::Orthographic);
+        assert_eq!(debug.get_projection(), DebugProjection::Perspective);
+        assert_eq!(orthographic.get_projection(), DebugProjection::Orthographic);
+
+        debug.line(Vec3::ZERO, Vec3::X, WHITE);
+        orthographic.line(Vec3::ZERO, Vec3::Y, WHITE);
+        let projections: Vec<_> = debug.state.borrow().shapes.iter().map(|s| s.2).collect();
+        assert_eq!(projections, [DebugProjection::Perspective, DebugProjection::Orthographic]);
+
+        orthographic.clear();
+        assert!(debug.state.borrow().shapes.is_empty());
+    }
+
+    const EPSILON: f64 = 1e-9;
+
+    fn assert_vec_eq(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < EPSILON, "{:?} != {:?}", a, b);
+    }
+
+    ///Input with keys pressed this frame, and keys held down from earlier frames
+    fn input(pressed: &[KeyCode], held: &[KeyCode]) -> Input {
+        let mut input = Input::default();
+        for key in pressed {
+            input.keycodes_this_frame.insert(*key, true);
+            input.keycodes.insert(*key, true);
+        }
+        for key in held {
+            input.keycodes.insert(*key, true);
+        }
+        input
+    }
+
+    fn enabled_overlay() -> DebugOverlay {
+        DebugOverlay { enabled: true, ..Default::default() }
+    }
+
+    #[test]
+    fn overlay_toggle_key_shows_and_hides() {
+        let mut overlay = DebugOverlay::default();
+        assert!(!overlay.enabled);
+
+        overlay.handle_keys(&input(&[KeyCode::F3], &[]), 3);
+        assert!(overlay.enabled);
+        overlay.handle_keys(&input(&[], &[KeyCode::F3]), 3);
+        assert!(overlay.enabled, "holding the key shouldn't toggle again");
+        overlay.handle_keys(&input(&[KeyCode::F3], &[]), 3);
+        assert!(!overlay.enabled);
+
+        overlay.toggle_key = None;
+        overlay.handle_keys(&input(&[KeyCode::F3], &[]), 3);
+        assert!(!overlay.enabled);
+    }
+
+    #[test]
+    fn hidden_overlay_ignores_keys() {
+        let mut overlay = DebugOverlay::default();
+        let edit = overlay.handle_keys(&input(&[KeyCode::Tab, KeyCode::PageDown, KeyCode::Key1], &[KeyCode::Right]), 3);
+        assert_eq!(edit, None);
+        assert_eq!(overlay.get_edit_mode(), TransformEditMode::Move);
+        assert_eq!(overlay.get_selected(), 0);
+        assert!(overlay.show_axes);
+    }
+
+    #[test]
+    fn overlay_number_keys_toggle_gizmos() {
+        let mut overlay = enabled_overlay();
+        overlay.handle_keys(&input(&[KeyCode::Key1, KeyCode::Key2, KeyCode::Key3], &[]), 0);
+        assert!(!overlay.show_axes && !overlay.show_bounds && overlay.show_wireframe);
+        overlay.handle_keys(&input(&[KeyCode::Key3], &[]), 0);
+        assert!(!overlay.show_wireframe);
+    }
+
+    #[test]
+    fn overlay_tab_cycles_edit_modes() {
+        let mut overlay = enabled_overlay();
+        let modes: Vec<_> = (0..3).map(|_| {
+            overlay.handle_keys(&input(&[KeyCode::Tab], &[]), 0);
+            overlay.get_edit_mode()
+        }).collect();
+        assert_eq!(modes, [TransformEditMode::Rotate, TransformEditMode::Scale, TransformEditMode::Move]);
+    }
+
+    #[test]
+    fn overlay_page_keys_select_and_wrap() {
+        let mut overlay = enabled_overlay();
+        let page_down = input(&[KeyCode::PageDown], &[]);
+        let page_up = input(&[KeyCode::PageUp], &[]);
+
+        overlay.handle_keys(&page_up, 3);
+        assert_eq!(overlay.get_selected(), 2);
+        overlay.handle_keys(&page_down, 3);
+        assert_eq!(overlay.get_selected(), 0);
+        overlay.handle_keys(&page_down, 3);
+        assert_eq!(overlay.get_selected(), 1);
+
+        //Removing objects keeps the selection in range
+        overlay.selected = 5;
+        overlay.handle_keys(&input(&[], &[]), 3);
+        assert_eq!(overlay.get_selected(), 2);
+
+        //Nothing to select in an empty scene
+        assert_eq!(overlay.handle_keys(&input(&[KeyCode::PageDown], &[KeyCode::Right]), 0), None);
+        assert_eq!(overlay.get_selected(), 2);
+    }
+
+    #[test]
+    fn overlay_arrow_keys_edit_selected_object() {
+        let mut overlay = enabled_overlay();
+        assert_eq!(overlay.handle_keys(&input(&[], &[]), 1), None);
+        //Opposite keys cancel out
+        assert_eq!(overlay.handle_keys(&input(&[], &[KeyCode::Left, KeyCode::Right]), 1), None);
+
+        let edit = overlay.handle_keys(&input(&[], &[KeyCode::Right, KeyCode::Down, KeyCode::Period]), 1).unwrap();
+        assert_eq!(edit, TransformEdit { mode: TransformEditMode::Move, direction: Vec3::new(1.0, -1.0, 1.0), fast: false });
+
+        let edit = overlay.handle_keys(&input(&[KeyCode::Tab], &[KeyCode::Comma, KeyCode::RShift]), 1).unwrap();
+        assert_eq!(edit, TransformEdit { mode: TransformEditMode::Rotate, direction: -Vec3::Z, fast: true });
+    }
+
+    #[test]
+    fn transform_edits_apply_over_time() {
+        let mut transform = Transform::origin();
+        TransformEdit { mode: TransformEditMode::Move, direction: Vec3::X, fast: false }.apply(&mut transform, 0.5);
+        assert_vec_eq(transform.get_position(), Vec3::new(0.5, 0.0, 0.0));
+        TransformEdit { mode: TransformEditMode::Move, direction: Vec3::Y, fast: true }.apply(&mut transform, 0.5);
+        assert_vec_eq(transform.get_position(), Vec3::new(0.5, 5.0, 0.0));
+
+        let mut transform = Transform::origin();
+        TransformEdit { mode: TransformEditMode::Scale, direction: Vec3::new(1.0, -1.0, 0.0), fast: false }.apply(&mut transform, 1.0);
+        assert_vec_eq(transform.get_scale(), Vec3::new(2.0, 0.5, 1.0));
+
+        //Right turns around y, a quarter turn each second
+        let mut transform = Transform::origin();
+        TransformEdit { mode: TransformEditMode::Rotate, direction: Vec3::X, fast: false }.apply(&mut transform, 1.0);
+        assert_vec_eq(transform.transform_vector(Vec3::X), Quat::from_axis_angle(Vec3::Y, std::f64::consts::FRAC_PI_2).rotate(Vec3::X));
+    }
+}
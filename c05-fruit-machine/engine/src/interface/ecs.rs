@@ -0,0 +1,395 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use crate::Frame;
+
+///A thing in a World, which is just an id that components are attached to
+///Ids are reused after an entity is despawned, but with a new generation so old handles stop matching
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+impl Entity {
+    ///Gets the entity's slot, which is shared with earlier entities that have been despawned
+    pub fn get_index(self) -> u32 {
+        self.index
+    }
+}
+
+///Stores one type of component, packed together so that iterating them is fast
+///Entities map to positions in the packed list through a sparse list indexed by entity
+pub struct SparseSet<T> {
+    ///Position of each entity's component in data, or None if it doesn't have one
+    sparse: Vec<Option<u32>>,
+    ///The entity owning each component in data
+    entities: Vec<Entity>,
+    data: Vec<T>,
+}
+
+impl<T> Default for SparseSet<T> {
+    fn default() -> Self {
+        SparseSet { sparse: Vec::new(), entities: Vec::new(), data: Vec::new() }
+    }
+}
+
+impl<T> SparseSet<T> {
+    fn position(&self, entity: Entity) -> Option<usize> {
+        let position = (*self.sparse.get(entity.index as usize)?)? as usize;
+        match self.entities[position] == entity {
+            true => Some(position),
+            false => None
+        }
+    }
+
+    ///Adds a component to an entity, returning the one it replaced
+    pub fn insert(&mut self, entity: Entity, component: T) -> Option<T> {
+        if let Some(position) = self.position(entity) {
+            return Some(std::mem::replace(&mut self.data[position], component));
+        }
+        let index = entity.index as usize;
+        if self.sparse.len() <= index {
+            self.sparse.resize(index + 1, None);
+        }
+        //An earlier entity in the same slot can still be here if the set is used outside of a World
+        if let Some(position) = self.sparse[index] {
+            self.swap_remove(position as usize);
+        }
+        self.sparse[index] = Some(self.data.len() as u32);
+        self.entities.push(entity);
+        self.data.push(component);
+        None
+    }
+
+    ///Removes an entity's component, moving the last component into its place
+    pub fn remove(&mut self, entity: Entity) -> Option<T> {
+        let position = self.position(entity)?;
+        Some(self.swap_remove(position))
+    }
+
+    ///Removes an entity's component, moving every later component back one place so the rest stay in the order they were added
+    ///Slower than remove, as every later component is moved
+    pub fn remove_ordered(&mut self, entity: Entity) -> Option<T> {
+        let position = self.position(entity)?;
+        self.sparse[entity.index as usize] = None;
+        self.entities.remove(position);
+        for (moved_position, moved) in self.entities.iter().enumerate().skip(position) {
+            self.sparse[moved.index as usize] = Some(moved_position as u32);
+        }
+        Some(self.data.remove(position))
+    }
+
+    fn swap_remove(&mut self, position: usize) -> T {
+        let removed = self.entities.swap_remove(position);
+        self.sparse[removed.index as usize] = None;
+        if let Some(moved) = self.entities.get(position) {
+            self.sparse[moved.index as usize] = Some(position as u32);
+        }
+        self.data.swap_remove(position)
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        self.position(entity).map(|p| &self.data[p])
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        self.position(entity).map(|p| &mut self.data[p])
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.position(entity).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    ///Gets the entities with this component, in the same order as the components
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    ///Gets the components packed together, in no particular order
+    ///Removing a component moves the last one into its place
+    pub fn components(&self) -> &[T] {
+        &self.data
+    }
+
+    pub fn components_mut(&mut self) -> &mut [T] {
+        &mut self.data
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.entities.iter().copied().zip(self.data.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.entities.iter().copied().zip(self.data.iter_mut())
+    }
+}
+
+///A SparseSet of any component type, so that a World can hold every type together
+trait Storage: Any {
+    fn remove_entity(&mut self, entity: Entity);
+}
+
+impl<T: 'static> Storage for SparseSet<T> {
+    fn remove_entity(&mut self, entity: Entity) {
+        self.remove(entity);
+    }
+}
+
+///Entities and their components, stored by type in sparse sets
+///Any 'static type can be a component, and each entity has at most one of each type
+///Every Scene has a World, with each of its GameObjects stored as a component of an entity
+///It is always used, even by scenes that add no entities or systems of their own
+#[derive(Default)]
+pub struct World {
+    ///Current generation of each entity slot, which changes when the slot's entity is despawned
+    generations: Vec<u32>,
+    ///Slots whose entities have been despawned, ready to be reused
+    free: Vec<u32>,
+    storages: HashMap<TypeId, Box<dyn Storage>>,
+}
+
+impl Debug for World {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("World").field("entities", &self.len()).field("component_types", &self.storages.len()).finish()
+    }
+}
+
+impl World {
+    pub fn new() -> Self {
+        World::default()
+    }
+
+    ///Creates an entity without any components
+    pub fn spawn(&mut self) -> Entity {
+        match self.free.pop() {
+            Some(index) => Entity { index, generation: self.generations[index as usize] },
+            None => {
+                self.generations.push(0);
+                Entity { index: self.generations.len() as u32 - 1, generation: 0 }
+            }
+        }
+    }
+
+    ///Removes an entity and drops its components, returning false if it had already been despawned
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        for storage in self.storages.values_mut() {
+            storage.remove_entity(entity);
+        }
+        self.generations[entity.index as usize] += 1;
+        self.free.push(entity.index);
+        true
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.generations.get(entity.index as usize) == Some(&entity.generation)
+    }
+
+    ///Gets the number of entities that haven't been despawned
+    pub fn len(&self) -> usize {
+        self.generations.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    ///Adds a component to an entity, replacing any component of the same type
+    ///Returns the component back if the entity has been despawned
+    pub fn insert<T: 'static>(&mut self, entity: Entity, component: T) -> Result<Option<T>, T> {
+        if !self.is_alive(entity) {
+            return Err(component);
+        }
+        Ok(self.storage_mut::<T>().insert(entity, component))
+    }
+
+    pub fn remove<T: 'static>(&mut self, entity: Entity) -> Option<T> {
+        self.storage_mut_existing::<T>()?.remove(entity)
+    }
+
+    ///Removes a component while keeping the rest of its type in the order they were added
+    pub fn remove_ordered<T: 'static>(&mut self, entity: Entity) -> Option<T> {
+        self.storage_mut_existing::<T>()?.remove_ordered(entity)
+    }
+
+    pub fn get<T: 'static>(&self, entity: Entity) -> Option<&T> {
+        self.storage::<T>()?.get(entity)
+    }
+
+    pub fn get_mut<T: 'static>(&mut self, entity: Entity) -> Option<&mut T> {
+        self.storage_mut_existing::<T>()?.get_mut(entity)
+    }
+
+    ///Gets every component of a type, or None if no entity has ever had one
+    pub fn storage<T: 'static>(&self) -> Option<&SparseSet<T>> {
+        let storage = self.storages.get(&TypeId::of::<T>())?;
+        (storage.as_ref() as &dyn Any).downcast_ref::<SparseSet<T>>()
+    }
+
+    ///Gets every component of a type mutably, creating the storage if there isn't one
+    pub fn storage_mut<T: 'static>(&mut self) -> &mut SparseSet<T> {
+        let storage = self.storages.entry(TypeId::of::<T>()).or_insert_with(|| Box::new(SparseSet::<T>::default()));
+        (storage.as_mut() as &mut dyn Any).downcast_mut::<SparseSet<T>>().unwrap()
+    }
+
+    fn storage_mut_existing<T: 'static>(&mut self) -> Option<&mut SparseSet<T>> {
+        let storage = self.storages.get_mut(&TypeId::of::<T>())?;
+        (storage.as_mut() as &mut dyn Any).downcast_mut::<SparseSet<T>>()
+    }
+
+    ///Iterates over every entity with a component of type T
+    pub fn query<T: 'static>(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.storage::<T>().into_iter().flat_map(|s| s.iter())
+    }
+
+    pub fn query_mut<T: 'static>(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.storage_mut_existing::<T>().into_iter().flat_map(|s| s.iter_mut())
+    }
+
+    ///Iterates over every entity with both an A and a B component
+    pub fn join<A: 'static, B: 'static>(&self) -> impl Iterator<Item = (Entity, &A, &B)> {
+        let b = self.storage::<B>();
+        self.query::<A>().filter_map(move |(entity, a)| Some((entity, a, b?.get(entity)?)))
+    }
+
+    ///Calls f for every entity with both an A and a B component, which can both be changed
+    ///Does nothing if A and B are the same type
+    pub fn join_mut<A: 'static, B: 'static, F: FnMut(Entity, &mut A, &mut B)>(&mut self, mut f: F) {
+        if TypeId::of::<A>() == TypeId::of::<B>() {
+            return;
+        }
+        let [Some(a), Some(b)] = self.storages.get_disjoint_mut([&TypeId::of::<A>(), &TypeId::of::<B>()]) else {
+            return;
+        };
+        let a = (a.as_mut() as &mut dyn Any).downcast_mut::<SparseSet<A>>().unwrap();
+        let b = (b.as_mut() as &mut dyn Any).downcast_mut::<SparseSet<B>>().unwrap();
+        for (entity, a) in a.iter_mut() {
+            if let Some(b) = b.get_mut(entity) {
+                f(entity, a, b);
+            }
+        }
+    }
+}
+
+///Logic that runs over a scene's World every frame, such as moving every entity with a velocity
+///Systems run in the order they were added to the scene, after the scene's on_tick and before its GameObjects tick
+pub trait System {
+    fn run(&mut self, world: &mut World, frame: &Frame);
+}
+
+impl<F: FnMut(&mut World, &Frame)> System for F {
+    fn run(&mut self, world: &mut World, frame: &Frame) {
+        self(world, frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Position(f64);
+    #[derive(Debug, PartialEq)]
+    struct Velocity(f64);
+
+    #[test]
+    fn entities_are_reused_with_new_generations() {
+        let mut world = World::new();
+        let a = world.spawn();
+        let b = world.spawn();
+        world.insert(a, Position(1.0)).unwrap();
+        assert!(world.despawn(a));
+        assert!(!world.despawn(a));
+        assert_eq!(world.len(), 1);
+
+        let c = world.spawn();
+        assert_eq!(c.get_index(), a.get_index());
+        assert!(!world.is_alive(a));
+        assert_eq!(world.get::<Position>(c), None);
+        assert_eq!(world.insert(a, Position(2.0)), Err(Position(2.0)));
+        assert!(world.is_alive(b) && world.is_alive(c));
+    }
+
+    #[test]
+    fn removing_keeps_components_packed() {
+        let mut world = World::new();
+        let entities: Vec<Entity> = (0..4).map(|i| {
+            let entity = world.spawn();
+            world.insert(entity, Position(i as f64)).unwrap();
+            entity
+        }).collect();
+
+        assert_eq!(world.remove::<Position>(entities[1]), Some(Position(1.0)));
+        assert_eq!(world.storage::<Position>().unwrap().len(), 3);
+        for &entity in [entities[0], entities[2], entities[3]].iter() {
+            assert_eq!(world.get::<Position>(entity), Some(&Position(entity.get_index() as f64)));
+        }
+        assert_eq!(world.insert(entities[2], Position(5.0)), Ok(Some(Position(2.0))));
+    }
+
+    #[test]
+    fn ordered_removal_keeps_insertion_order() {
+        let mut world = World::new();
+        let entities: Vec<Entity> = (0..5).map(|i| {
+            let entity = world.spawn();
+            world.insert(entity, Position(i as f64)).unwrap();
+            entity
+        }).collect();
+
+        assert_eq!(world.remove_ordered::<Position>(entities[1]), Some(Position(1.0)));
+        let order: Vec<f64> = world.query::<Position>().map(|(_, p)| p.0).collect();
+        assert_eq!(order, [0.0, 2.0, 3.0, 4.0]);
+        assert_eq!(world.storage::<Position>().unwrap().entities(), [entities[0], entities[2], entities[3], entities[4]]);
+        for &entity in entities.iter().skip(2) {
+            assert_eq!(world.get::<Position>(entity), Some(&Position(entity.get_index() as f64)));
+        }
+        assert_eq!(world.remove_ordered::<Position>(entities[1]), None);
+    }
+
+    #[test]
+    fn joining_a_type_with_itself_does_nothing() {
+        let mut world = World::new();
+        let entity = world.spawn();
+        world.insert(entity, Position(1.0)).unwrap();
+
+        let mut calls = 0;
+        world.join_mut::<Position, Position, _>(|_, _, _| calls += 1);
+        assert_eq!(calls, 0);
+        assert_eq!(world.get::<Position>(entity), Some(&Position(1.0)));
+    }
+
+    #[test]
+    fn joins_only_visit_entities_with_both() {
+        let mut world = World::new();
+        for i in 0..6 {
+            let entity = world.spawn();
+            world.insert(entity, Position(0.0)).unwrap();
+            if i % 2 == 0 {
+                world.insert(entity, Velocity(i as f64)).unwrap();
+            }
+        }
+
+        let mut system = |world: &mut World, _frame: &Frame| {
+            world.join_mut::<Position, Velocity, _>(|_, p, v| p.0 += v.0);
+        };
+        let frame = Frame::headless((1, 1));
+        system.run(&mut world, &frame);
+        system.run(&mut world, &frame);
+
+        let moved: Vec<f64> = world.join::<Position, Velocity>().map(|(_, p, _)| p.0).collect();
+        assert_eq!(moved, [0.0, 4.0, 8.0]);
+        assert_eq!(world.query::<Position>().filter(|(_, p)| p.0 == 0.0).count(), 4);
+    }
+}
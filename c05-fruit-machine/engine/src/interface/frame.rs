@@ -1,6 +1,7 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
 
-use crate::{ScanCode, KeyCode};
+use crate::{ScanCode, KeyCode, Audio, DebugDraw, ViewportHit, Monitor, FullscreenMode, VSync};
 
 #[derive(Debug, Clone, Default)]
 pub struct Input {
@@ -10,6 +11,9 @@ pub struct Input {
     pub(crate) scancodes_this_frame: HashMap<ScanCode, bool>,
     pub(crate) keycodes_this_frame: HashMap<KeyCode, bool>,
 
+    ///Cursor position in pixels from the top left of the window, None when the cursor is outside
+    pub(crate) cursor_position: Option<(f64, f64)>,
+    pub(crate) cursor_view: Option<ViewportHit>,
 }
 
 impl Input {
@@ -36,13 +40,25 @@ impl Input {
     pub fn is_key_released_this_frame(&self, keycode: KeyCode) -> bool {
         !self.keycodes_this_frame.get(&keycode).unwrap_or(&true).to_owned()
     }
+
+    ///Gets the cursor position in pixels from the top left of the window, or None if the cursor is outside the window
+    pub fn get_cursor_position(&self) -> Option<(f64, f64)> {
+        self.cursor_position
+    }
+
+    ///Gets which view of the scene's main camera the cursor is over, and where it is within that view
+    pub fn get_cursor_view(&self) -> Option<&ViewportHit> {
+        self.cursor_view.as_ref()
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Time {
     pub frames: u64,
     pub delta_time: std::time::Duration,
-    pub frame_time: std::time::Instant 
+    pub frame_time: std::time::Instant,
+    ///Timings of recent frames, up to the end of the previous frame
+    pub stats: FrameStats,
 }
 
 impl Default for Time {
@@ -51,14 +67,89 @@ impl Default for Time {
             frames: 0,
             delta_time: std::time::Duration::from_secs(0),
             frame_time: std::time::Instant::now(),
+            stats: Default::default(),
+        }
+    }
+}
+
+///Rolling timings of the last FrameStats::HISTORY frames, for measuring performance
+#[derive(Debug, Clone, Default)]
+pub struct FrameStats {
+    ///Time from the start of each frame to the start of the next, oldest first
+    frame_times: VecDeque<Duration>,
+    ///Time spent running callbacks each frame
+    update_times: VecDeque<Duration>,
+    ///Time spent issuing draw calls each frame
+    render_times: VecDeque<Duration>,
+}
+
+impl FrameStats {
+    ///Number of frames the stats are taken over
+    pub const HISTORY: usize = 120;
+
+    pub(crate) fn record(&mut self, frame_time: Duration, update_time: Duration, render_time: Duration) {
+        for (times, time) in [(&mut self.frame_times, frame_time), (&mut self.update_times, update_time), (&mut self.render_times, render_time)] {
+            if times.len() == FrameStats::HISTORY {
+                times.pop_front();
+            }
+            times.push_back(time);
         }
     }
+
+    ///Gets the average number of frames per second, or 0 before any frames have been timed
+    pub fn get_fps(&self) -> f64 {
+        let total: Duration = self.frame_times.iter().sum();
+        match total.is_zero() {
+            true => 0.0,
+            false => self.frame_times.len() as f64 / total.as_secs_f64()
+        }
+    }
+
+    pub fn get_average_frame_time(&self) -> Duration {
+        average(&self.frame_times)
+    }
+
+    ///Gets the frame time that a fraction of frames were faster than, for example 0.99 for the slowest 1% of frames
+    pub fn get_frame_time_percentile(&self, percentile: f64) -> Duration {
+        let mut times: Vec<Duration> = self.frame_times.iter().copied().collect();
+        if times.is_empty() {
+            return Duration::ZERO;
+        }
+        times.sort();
+        let index = (percentile.clamp(0.0, 1.0) * (times.len() - 1) as f64).round() as usize;
+        times[index]
+    }
+
+    ///Gets the average time spent running window, scene and object callbacks each frame
+    pub fn get_average_update_time(&self) -> Duration {
+        average(&self.update_times)
+    }
+
+    ///Gets the average time spent issuing draw calls each frame, not counting waiting for the gpu or vsync
+    pub fn get_average_render_time(&self) -> Duration {
+        average(&self.render_times)
+    }
+}
+
+fn average(times: &VecDeque<Duration>) -> Duration {
+    match times.len() {
+        0 => Duration::ZERO,
+        n => times.iter().sum::<Duration>() / n as u32
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Display {
+    ///Size of the window's drawable area in physical pixels
     pub resolution: (u32, u32),
-    pub position: (i32, i32)
+    pub position: (i32, i32),
+    ///Number of physical pixels per logical pixel
+    pub scale_factor: f64,
+    ///The monitor the window is on, if known
+    pub monitor: Option<Monitor>,
+    ///How the window covers its monitor, or None if it is windowed
+    pub fullscreen: Option<FullscreenMode>,
+    pub vsync: VSync,
 }
 
 impl Display {
@@ -72,4 +163,83 @@ pub struct Frame {
     pub input: Input,
     pub time: Time,
     pub display: Display,
-}
\ No newline at end of file
+    pub audio: Audio,
+    ///Draws lines and text for this frame only, for visualising what callbacks are doing
+    pub debug: DebugDraw,
+}
+
+impl Frame {
+    ///Creates a frame that isn't attached to a window, for ticking scenes in tests and benchmarks
+    pub fn headless(resolution: (u32, u32)) -> Self {
+        Frame {
+            input: Default::default(),
+            time: Default::default(),
+            display: Display {
+                resolution,
+                position: (0, 0),
+                scale_factor: 1.0,
+                monitor: None,
+                fullscreen: None,
+                vsync: VSync::default(),
+            },
+            audio: Audio::new(),
+            debug: Default::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn empty_history() {
+        let stats = FrameStats::default();
+        assert_eq!(stats.get_fps(), 0.0);
+        assert_eq!(stats.get_average_frame_time(), Duration::ZERO);
+        assert_eq!(stats.get_frame_time_percentile(0.99), Duration::ZERO);
+        assert_eq!(stats.get_average_update_time(), Duration::ZERO);
+        assert_eq!(stats.get_average_render_time(), Duration::ZERO);
+    }
+
+    #[test]
+    fn partly_filled_history() {
+        let mut stats = FrameStats::default();
+        for frame_time in [10, 30, 20, 40] {
+            stats.record(ms(frame_time), ms(frame_time / 10), ms(2));
+        }
+        assert!((stats.get_fps() - 40.0).abs() < 1e-9);
+        assert_eq!(stats.get_average_frame_time(), ms(25));
+        assert_eq!(stats.get_frame_time_percentile(0.0), ms(10));
+        assert_eq!(stats.get_frame_time_percentile(0.5), ms(30));
+        assert_eq!(stats.get_frame_time_percentile(1.0), ms(40));
+        assert_eq!(stats.get_frame_time_percentile(2.0), ms(40));
+        assert_eq!(stats.get_average_update_time(), Duration::from_micros(2500));
+        assert_eq!(stats.get_average_render_time(), ms(2));
+    }
+
+    #[test]
+    fn history_wraps_around() {
+        let mut stats = FrameStats::default();
+        //The slow frames are pushed out of the history by the later fast ones
+        for _ in 0..10 {
+            stats.record(ms(100), ms(50), ms(50));
+        }
+        for _ in 0..FrameStats::HISTORY {
+            stats.record(ms(10), ms(1), ms(2));
+        }
+        assert_eq!(stats.frame_times.len(), FrameStats::HISTORY);
+        assert!((stats.get_fps() - 100.0).abs() < 1e-9);
+        assert_eq!(stats.get_average_frame_time(), ms(10));
+        assert_eq!(stats.get_frame_time_percentile(1.0), ms(10));
+        assert_eq!(stats.get_average_update_time(), ms(1));
+
+        stats.record(ms(130), ms(1), ms(2));
+        assert_eq!(stats.get_frame_time_percentile(1.0), ms(130));
+        assert_eq!(stats.get_average_frame_time(), ms(11));
+    }
+}
@@ -1,11 +1,20 @@
+use std::any::Any;
 use std::fmt::Debug;
+use std::ops::Range;
 
 use crate::Transform;
 use crate::Scene;
 use crate::Mesh;
 use crate::Frame;
+use crate::Aabb;
+use crate::RenderLayers;
+use crate::ParticleEmitter;
+use crate::CallbackDescription;
 
-pub trait GameObjectCallback : Debug {
+///A behaviour attached to a GameObject, such as spinning or following another object
+///An object can have any number of components, which are called in the order they were added
+///While a component is being called it is taken out of the object, so it can find the others with get_component
+pub trait GameObjectCallback : Debug + Any {
     /// Called after the GameObject is added to a Scene
     fn on_load(&mut self, _object: &mut GameObject, _scene: &mut Scene) {}
     /// Called before the GameObject is removed from a Scene
@@ -15,6 +24,9 @@ pub trait GameObjectCallback : Debug {
     /// Called before the GameObject is destroyed
     /// Allows the GameObject to clean up state e.g. file handles
     fn on_destroy(&mut self, _object: &mut GameObject) {}
+    /// Gets the registered type name and parameters that recreate this callback when a scene file is loaded
+    /// None if the callback can't be saved, in which case the object is saved without one
+    fn describe(&self) -> Option<CallbackDescription> { None }
 }
 
 #[derive(Debug)]
@@ -24,21 +36,185 @@ impl GameObjectCallback for GameObjectCallbackDefault {}
 /// Struct representing an object in a scene
 #[derive(Debug)]
 pub struct GameObject {
-    pub(crate) callbacks: Option<Box<dyn GameObjectCallback>>,
+    ///Each slot is empty while its component is being called
+    pub(crate) components: Vec<Option<Box<dyn GameObjectCallback>>>,
     pub name: String,
     pub transform: Transform,
     pub meshes: Vec<(Transform, Mesh)>,
+    ///Whether the object's meshes are drawn
+    pub visible: bool,
+    ///Which layers the object is on, so cameras can choose which objects to draw
+    pub layers: RenderLayers,
+    ///Particle emitters updated every frame, each drawn by one of the meshes
+    pub emitters: Vec<ParticleEmitter>,
+    ///Meshes added by the components' on_load, which aren't saved in scene files as loading adds them again
+    pub(crate) callback_meshes: Range<usize>,
 }
 
 impl GameObject {
+    ///Creates an object, with callbacks as its first component if there is one
     pub fn new (callbacks: Option<Box<dyn GameObjectCallback>>, name: String, transform: Transform) -> Self {
-        let object = GameObject {
-            callbacks: callbacks,
-            name: name,
-            transform: transform,
-
-            meshes: Vec::new()
-        };
-        object
+        GameObject {
+            components: callbacks.into_iter().map(Some).collect(),
+            name,
+            transform,
+
+            meshes: Vec::new(),
+            visible: true,
+            layers: RenderLayers::DEFAULT,
+            emitters: Vec::new(),
+            callback_meshes: 0..0,
+        }
+    }
+
+    ///Adds a component after the object's others
+    ///Components should be added before the object is added to a scene, as that is when their on_load is called
+    pub fn add_component (&mut self, component: Box<dyn GameObjectCallback>) {
+        self.components.push(Some(component));
+    }
+
+    pub fn with_component (mut self, component: Box<dyn GameObjectCallback>) -> Self {
+        self.add_component(component);
+        self
+    }
+
+    ///Gets the first component of a type, or None if there isn't one
+    ///A component can't find itself while it is being called
+    pub fn get_component<T: GameObjectCallback> (&self) -> Option<&T> {
+        self.components.iter().flatten().find_map(|c| (c.as_ref() as &dyn Any).downcast_ref::<T>())
+    }
+
+    pub fn get_component_mut<T: GameObjectCallback> (&mut self) -> Option<&mut T> {
+        self.components.iter_mut().flatten().find_map(|c| (c.as_mut() as &mut dyn Any).downcast_mut::<T>())
+    }
+
+    ///Gets the number of components on the object
+    pub fn get_component_count (&self) -> usize {
+        self.components.len()
+    }
+
+    ///Calls a function on each component in order, with the component taken out of the object
+    ///Components added during the calls are called too
+    pub(crate) fn for_each_component<F: FnMut(&mut dyn GameObjectCallback, &mut GameObject)> (&mut self, mut f: F) {
+        let mut i = 0;
+        while i < self.components.len() {
+            if let Some(mut component) = self.components[i].take() {
+                f(component.as_mut(), self);
+                self.components[i] = Some(component);
+            }
+            i += 1;
+        }
+    }
+
+    ///Adds a particle emitter, with a mesh that is drawn once for each particle
+    ///Returns the index of the emitter in emitters
+    pub fn add_emitter(&mut self, mut emitter: ParticleEmitter, mut mesh: Mesh) -> usize {
+        mesh.instances = Some(emitter.get_instances());
+        emitter.mesh = Some(self.meshes.len());
+        self.meshes.push((Transform::origin(), mesh));
+        self.emitters.push(emitter);
+        self.emitters.len() - 1
+    }
+
+    ///Moves each emitter's particles forward and updates the meshes that draw them
+    pub(crate) fn update_emitters(&mut self, delta: f64) {
+        for emitter in self.emitters.iter_mut() {
+            emitter.update(delta);
+            if let Some(mesh) = emitter.mesh.and_then(|i| self.meshes.get_mut(i)) {
+                mesh.1.instances = Some(emitter.get_instances());
+            }
+        }
+    }
+
+    ///Gets the box containing a mesh in world space, following the object's transform
+    ///Returns None if there is no mesh at index
+    pub fn get_mesh_bounds(&self, index: usize) -> Option<Aabb> {
+        let mesh = self.meshes.get(index)?;
+        Some(mesh.1.get_bounds().transformed(&(mesh.0.to_matrix() * self.transform.to_matrix())))
+    }
+
+    ///Gets the box containing all of the object's meshes in world space
+    ///Returns None if the object has no meshes
+    pub fn get_bounds(&self) -> Option<Aabb> {
+        (0..self.meshes.len())
+            .filter_map(|i| self.get_mesh_bounds(i))
+            .reduce(|a, b| a.union(&b))
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct Counter(u32);
+    impl GameObjectCallback for Counter {}
+
+    #[derive(Debug)]
+    struct Label(&'static str);
+    impl GameObjectCallback for Label {}
+
+    fn object() -> GameObject {
+        GameObject::new(Some(Box::new(Counter(1))), "object".to_string(), Transform::origin())
+            .with_component(Box::new(Label("a")))
+            .with_component(Box::new(Counter(2)))
+    }
+
+    #[test]
+    fn get_component_finds_first_of_type() {
+        let mut object = object();
+        assert_eq!(object.get_component::<Counter>().map(|c| c.0), Some(1));
+        assert_eq!(object.get_component::<Label>().map(|l| l.0), Some("a"));
+        assert!(object.get_component::<GameObjectCallbackDefault>().is_none());
+
+        object.get_component_mut::<Counter>().unwrap().0 = 10;
+        assert_eq!(object.get_component::<Counter>().map(|c| c.0), Some(10));
+        assert!(object.get_component_mut::<GameObjectCallbackDefault>().is_none());
+    }
+
+    #[test]
+    fn get_component_skips_empty_slots() {
+        let mut object = object();
+        object.components[0] = None;
+        assert_eq!(object.get_component::<Counter>().map(|c| c.0), Some(2));
+        assert_eq!(object.get_component_mut::<Counter>().map(|c| c.0), Some(2));
+        //Empty slots still count, as they are only empty while their component is being called
+        assert_eq!(object.get_component_count(), 3);
+
+        object.components[2] = None;
+        assert!(object.get_component::<Counter>().is_none());
+        assert!(object.get_component_mut::<Counter>().is_none());
+    }
+
+    #[test]
+    fn for_each_component_takes_and_restores_components() {
+        let mut object = object();
+        let mut seen = Vec::new();
+        object.for_each_component(|component, object| {
+            let component = component as &mut dyn Any;
+            //The component being called is taken out, so only the others can be found
+            let others = (object.get_component::<Counter>().map(|c| c.0), object.get_component::<Label>().map(|l| l.0));
+            if let Some(counter) = component.downcast_mut::<Counter>() {
+                counter.0 += 100;
+                seen.push(format!("counter {:?}", others));
+            }
+            else if component.is::<Label>() {
+                seen.push(format!("label {:?}", others));
+                //Components added during the calls are called too
+                object.add_component(Box::new(GameObjectCallbackDefault {}));
+            }
+            else {
+                seen.push("default".to_string());
+            }
+        });
+
+        assert_eq!(seen, [
+            "counter (Some(2), Some(\"a\"))",
+            "label (Some(101), None)",
+            "counter (Some(101), Some(\"a\"))",
+            "default",
+        ]);
+        assert!(object.components.iter().all(Option::is_some));
+        assert_eq!(object.get_component_count(), 4);
+        assert_eq!(object.get_component::<Counter>().map(|c| c.0), Some(101));
     }
 }
@@ -0,0 +1,99 @@
+use std::ffi::{c_void, CString};
+use std::rc::Rc;
+
+use glium::backend::{Backend, Context};
+use glium::SwapBuffersError;
+use glutin_egl_sys::egl;
+
+///EGL_PLATFORM_SURFACELESS_MESA, which isn't in glutin_egl_sys's bindings
+const PLATFORM_SURFACELESS: egl::types::EGLenum = 0x31DD;
+
+struct EglBackend {
+    egl: egl::Egl,
+    display: egl::types::EGLDisplay,
+    context: egl::types::EGLContext,
+    ///Declared last so that libEGL stays loaded until the context has been destroyed
+    _library: libloading::Library,
+}
+
+unsafe impl Backend for EglBackend {
+    fn swap_buffers(&self) -> Result<(), SwapBuffersError> {
+        Ok(())
+    }
+
+    unsafe fn get_proc_address(&self, symbol: &str) -> *const c_void {
+        let symbol = CString::new(symbol).unwrap();
+        self.egl.GetProcAddress(symbol.as_ptr()) as *const c_void
+    }
+
+    fn get_framebuffer_dimensions(&self) -> (u32, u32) {
+        (1, 1)
+    }
+
+    fn is_current(&self) -> bool {
+        unsafe { self.egl.GetCurrentContext() == self.context }
+    }
+
+    unsafe fn make_current(&self) {
+        self.egl.MakeCurrent(self.display, egl::NO_SURFACE, egl::NO_SURFACE, self.context);
+    }
+}
+
+impl Drop for EglBackend {
+    fn drop(&mut self) {
+        unsafe {
+            if self.is_current() {
+                self.egl.MakeCurrent(self.display, egl::NO_SURFACE, egl::NO_SURFACE, egl::NO_CONTEXT);
+            }
+            self.egl.DestroyContext(self.display, self.context);
+        }
+    }
+}
+
+///Creates a new OpenGL 3.3 core context without a window
+///None if the platform has no surfaceless EGL, in which case tests that need a context should be skipped
+pub(crate) fn create_context() -> Option<Rc<Context>> {
+    unsafe {
+        let library = libloading::Library::new("libEGL.so.1").ok()?;
+        let egl = egl::Egl::load_with(|name| {
+            let name = CString::new(name).unwrap();
+            library.get::<*const c_void>(name.as_bytes_with_nul()).map(|s| *s).unwrap_or(std::ptr::null())
+        });
+        if !egl.GetPlatformDisplay.is_loaded() {
+            return None;
+        }
+
+        let display = egl.GetPlatformDisplay(PLATFORM_SURFACELESS, std::ptr::null_mut(), std::ptr::null());
+        if display == egl::NO_DISPLAY {
+            return None;
+        }
+        let (mut major, mut minor) = (0, 0);
+        if egl.Initialize(display, &mut major, &mut minor) == 0 || egl.BindAPI(egl::OPENGL_API) == 0 {
+            return None;
+        }
+
+        //Surfaceless displays have no window configs, which are the ones chosen by default
+        let config_attributes = [egl::SURFACE_TYPE as i32, egl::PBUFFER_BIT as i32, egl::RENDERABLE_TYPE as i32, egl::OPENGL_BIT as i32, egl::NONE as i32];
+        let mut config: egl::types::EGLConfig = std::ptr::null();
+        let mut configs = 0;
+        if egl.ChooseConfig(display, config_attributes.as_ptr(), &mut config, 1, &mut configs) == 0 || configs == 0 {
+            return None;
+        }
+
+        let context_attributes = [
+            egl::CONTEXT_MAJOR_VERSION as i32, 3,
+            egl::CONTEXT_MINOR_VERSION as i32, 3,
+            egl::CONTEXT_OPENGL_PROFILE_MASK as i32, egl::CONTEXT_OPENGL_CORE_PROFILE_BIT as i32,
+            egl::NONE as i32,
+        ];
+        let context = egl.CreateContext(display, config, egl::NO_CONTEXT, context_attributes.as_ptr());
+        if context == egl::NO_CONTEXT {
+            return None;
+        }
+
+        let backend = EglBackend { egl, display, context, _library: library };
+        backend.make_current();
+        //Several contexts are made in the same thread, so glium has to check which is current before using one
+        Context::new(backend, true, Default::default()).ok()
+    }
+}
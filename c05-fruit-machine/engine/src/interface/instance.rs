@@ -0,0 +1,98 @@
+use glium::implement_vertex;
+
+use crate::Transform;
+
+///One copy of a mesh drawn by instancing
+///Every instance of a mesh is drawn in a single draw call, so many identical meshes can be drawn cheaply
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Instance {
+    ///Where the instance is, relative to the mesh's transform
+    pub transform: Transform,
+    ///Multiplied with the colour from the shader
+    pub colour: [f32; 4],
+    ///Added to the mesh's uvs after scaling, for picking a frame from a texture atlas
+    pub uv_offset: [f32; 2],
+    pub uv_scale: [f32; 2],
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        Instance {
+            transform: Transform::origin(),
+            colour: [1.0, 1.0, 1.0, 1.0],
+            uv_offset: [0.0, 0.0],
+            uv_scale: [1.0, 1.0],
+        }
+    }
+}
+
+impl Instance {
+    pub fn new(transform: Transform) -> Self {
+        Instance { transform, ..Default::default() }
+    }
+
+    pub fn with_colour(mut self, colour: [f32; 4]) -> Self {
+        self.colour = colour;
+        self
+    }
+
+    ///Sets which part of the texture the instance uses, where the whole texture is from (0, 0) to (1, 1)
+    pub fn with_uv_rect(mut self, offset: [f32; 2], scale: [f32; 2]) -> Self {
+        self.uv_offset = offset;
+        self.uv_scale = scale;
+        self
+    }
+}
+
+///The per instance data given to the shaders
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct InstanceVertex {
+    instance_matrix: [[f32; 4]; 4],
+    instance_colour: [f32; 4],
+    ///Offset in xy and scale in zw
+    instance_uv: [f32; 4],
+}
+implement_vertex!(InstanceVertex, instance_matrix, instance_colour, instance_uv);
+
+impl From<&Instance> for InstanceVertex {
+    fn from(instance: &Instance) -> Self {
+        InstanceVertex {
+            instance_matrix: instance.transform.to_array(),
+            instance_colour: instance.colour,
+            instance_uv: [instance.uv_offset[0], instance.uv_offset[1], instance.uv_scale[0], instance.uv_scale[1]],
+        }
+    }
+}
+
+impl Default for InstanceVertex {
+    ///A single instance that draws the mesh unchanged
+    fn default() -> Self {
+        InstanceVertex::from(&Instance::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Mat4, Quat, Vec3};
+
+    #[test]
+    fn instance_vertex_holds_instance_data() {
+        let transform = Transform::new(Vec3::new(1.0, 2.0, 3.0), Quat::from_euler(0.1, 0.2, 0.3), Vec3::new(2.0, 2.0, 2.0));
+        let instance = Instance::new(transform)
+            .with_colour([0.5, 0.25, 1.0, 0.75])
+            .with_uv_rect([0.5, 0.25], [0.125, 0.5]);
+        let vertex = InstanceVertex::from(&instance);
+        assert_eq!(vertex.instance_matrix, transform.to_array());
+        assert_eq!(vertex.instance_colour, [0.5, 0.25, 1.0, 0.75]);
+        assert_eq!(vertex.instance_uv, [0.5, 0.25, 0.125, 0.5]);
+    }
+
+    #[test]
+    fn default_instance_vertex_leaves_mesh_unchanged() {
+        let vertex = InstanceVertex::default();
+        assert_eq!(vertex.instance_matrix, Mat4::IDENTITY.to_f32_array());
+        assert_eq!(vertex.instance_colour, [1.0; 4]);
+        assert_eq!(vertex.instance_uv, [0.0, 0.0, 1.0, 1.0]);
+    }
+}
@@ -0,0 +1,568 @@
+use std::ops::{Add, Sub, Mul, Neg, MulAssign, AddAssign, SubAssign};
+
+use serde::{Deserialize, Serialize};
+
+///A 3d vector
+///Saved in scene files as [x, y, z]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(from = "[f64; 3]", into = "[f64; 3]")]
+pub struct Vec3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Vec3 {
+    pub const ZERO: Vec3 = Vec3 { x: 0.0, y: 0.0, z: 0.0 };
+    pub const ONE: Vec3 = Vec3 { x: 1.0, y: 1.0, z: 1.0 };
+    pub const X: Vec3 = Vec3 { x: 1.0, y: 0.0, z: 0.0 };
+    pub const Y: Vec3 = Vec3 { x: 0.0, y: 1.0, z: 0.0 };
+    pub const Z: Vec3 = Vec3 { x: 0.0, y: 0.0, z: 1.0 };
+
+    pub const fn new(x: f64, y: f64, z: f64) -> Self {
+        Vec3 { x, y, z }
+    }
+
+    pub fn dot(self, rhs: Vec3) -> f64 {
+        self.x * rhs.x + self.y * rhs.y + self.z * rhs.z
+    }
+
+    pub fn cross(self, rhs: Vec3) -> Vec3 {
+        Vec3 {
+            x: self.y * rhs.z - self.z * rhs.y,
+            y: self.z * rhs.x - self.x * rhs.z,
+            z: self.x * rhs.y - self.y * rhs.x,
+        }
+    }
+
+    pub fn length(self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    ///Gets a vector in the same direction with length 1
+    ///Returns the zero vector unchanged
+    pub fn normalised(self) -> Vec3 {
+        let length = self.length();
+        if length == 0.0 {
+            return self;
+        }
+        self * (1.0 / length)
+    }
+
+    ///Multiplies each component by the matching component of rhs
+    pub fn scale(self, rhs: Vec3) -> Vec3 {
+        Vec3::new(self.x * rhs.x, self.y * rhs.y, self.z * rhs.z)
+    }
+
+    ///Linearly interpolates between self (t = 0) and rhs (t = 1)
+    pub fn lerp(self, rhs: Vec3, t: f64) -> Vec3 {
+        self + (rhs - self) * t
+    }
+
+    pub fn to_tuple(self) -> (f64, f64, f64) {
+        (self.x, self.y, self.z)
+    }
+}
+
+impl From<[f64; 3]> for Vec3 {
+    fn from(v: [f64; 3]) -> Self {
+        Vec3::new(v[0], v[1], v[2])
+    }
+}
+
+impl From<Vec3> for [f64; 3] {
+    fn from(v: Vec3) -> Self {
+        [v.x, v.y, v.z]
+    }
+}
+
+impl Add for Vec3 {
+    type Output = Vec3;
+    fn add(self, rhs: Vec3) -> Vec3 {
+        Vec3::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z)
+    }
+}
+
+impl AddAssign for Vec3 {
+    fn add_assign(&mut self, rhs: Vec3) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for Vec3 {
+    type Output = Vec3;
+    fn sub(self, rhs: Vec3) -> Vec3 {
+        Vec3::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z)
+    }
+}
+
+impl SubAssign for Vec3 {
+    fn sub_assign(&mut self, rhs: Vec3) {
+        *self = *self - rhs;
+    }
+}
+
+impl Mul<f64> for Vec3 {
+    type Output = Vec3;
+    fn mul(self, rhs: f64) -> Vec3 {
+        Vec3::new(self.x * rhs, self.y * rhs, self.z * rhs)
+    }
+}
+
+impl Neg for Vec3 {
+    type Output = Vec3;
+    fn neg(self) -> Vec3 {
+        Vec3::new(-self.x, -self.y, -self.z)
+    }
+}
+
+impl From<(f64, f64, f64)> for Vec3 {
+    fn from(t: (f64, f64, f64)) -> Self {
+        Vec3::new(t.0, t.1, t.2)
+    }
+}
+
+///A quaternion representing a rotation in 3d space
+///Saved in scene files as [x, y, z, w]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(from = "[f64; 4]", into = "[f64; 4]")]
+pub struct Quat {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub w: f64,
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Quat::IDENTITY
+    }
+}
+
+impl Quat {
+    ///The rotation that does nothing
+    pub const IDENTITY: Quat = Quat { x: 0.0, y: 0.0, z: 0.0, w: 1.0 };
+
+    pub const fn new(x: f64, y: f64, z: f64, w: f64) -> Self {
+        Quat { x, y, z, w }
+    }
+
+    ///Creates a rotation of angle radians anticlockwise around axis
+    pub fn from_axis_angle(axis: Vec3, angle: f64) -> Self {
+        let axis = axis.normalised();
+        let (s, c) = (angle / 2.0).sin_cos();
+        Quat::new(axis.x * s, axis.y * s, axis.z * s, c)
+    }
+
+    pub fn dot(self, rhs: Quat) -> f64 {
+        self.x * rhs.x + self.y * rhs.y + self.z * rhs.z + self.w * rhs.w
+    }
+
+    pub fn length(self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalised(self) -> Quat {
+        let length = self.length();
+        if length == 0.0 {
+            return Quat::IDENTITY;
+        }
+        Quat::new(self.x / length, self.y / length, self.z / length, self.w / length)
+    }
+
+    ///Gets the rotation in the opposite direction
+    pub fn inverse(self) -> Quat {
+        let length_squared = self.dot(self);
+        Quat::new(-self.x / length_squared, -self.y / length_squared, -self.z / length_squared, self.w / length_squared)
+    }
+
+    ///Rotates a vector by this rotation
+    pub fn rotate(self, v: Vec3) -> Vec3 {
+        let u = Vec3::new(self.x, self.y, self.z);
+        let t = u.cross(v) * 2.0;
+        v + t * self.w + u.cross(t)
+    }
+
+    ///Creates a rotation from euler angles in radians
+    ///Matches the rotation made by Transform::from_euler
+    pub fn from_euler(x: f64, y: f64, z: f64) -> Self {
+        Quat::from_axis_angle(Vec3::Z, z) * Quat::from_axis_angle(Vec3::Y, -y) * Quat::from_axis_angle(Vec3::X, -x)
+    }
+
+    ///Gets the rotation part of a matrix
+    ///The top left 3x3 of the matrix should be a rotation with no scale
+    pub fn from_mat4(m: &Mat4) -> Self {
+        //m.rows[i][j] is element (j, i) of the usual column vector rotation matrix
+        let r = |i: usize, j: usize| m.rows[j][i];
+        let trace = r(0, 0) + r(1, 1) + r(2, 2);
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quat::new((r(2, 1) - r(1, 2)) / s, (r(0, 2) - r(2, 0)) / s, (r(1, 0) - r(0, 1)) / s, 0.25 * s)
+        }
+        else if r(0, 0) > r(1, 1) && r(0, 0) > r(2, 2) {
+            let s = (1.0 + r(0, 0) - r(1, 1) - r(2, 2)).sqrt() * 2.0;
+            Quat::new(0.25 * s, (r(0, 1) + r(1, 0)) / s, (r(0, 2) + r(2, 0)) / s, (r(2, 1) - r(1, 2)) / s)
+        }
+        else if r(1, 1) > r(2, 2) {
+            let s = (1.0 + r(1, 1) - r(0, 0) - r(2, 2)).sqrt() * 2.0;
+            Quat::new((r(0, 1) + r(1, 0)) / s, 0.25 * s, (r(1, 2) + r(2, 1)) / s, (r(0, 2) - r(2, 0)) / s)
+        }
+        else {
+            let s = (1.0 + r(2, 2) - r(0, 0) - r(1, 1)).sqrt() * 2.0;
+            Quat::new((r(0, 2) + r(2, 0)) / s, (r(1, 2) + r(2, 1)) / s, 0.25 * s, (r(1, 0) - r(0, 1)) / s)
+        };
+        q.normalised()
+    }
+
+    ///Creates a rotation that points +z along forward, with +y as close to up as possible
+    ///Returns the identity if forward is zero or parallel to up
+    pub fn look_rotation(forward: Vec3, up: Vec3) -> Self {
+        let forward = forward.normalised();
+        let right = up.cross(forward).normalised();
+        if forward == Vec3::ZERO || right == Vec3::ZERO {
+            return Quat::IDENTITY;
+        }
+        let up = forward.cross(right);
+        Quat::from_mat4(&Mat4::from_rows([
+            [right.x, right.y, right.z, 0.0],
+            [up.x, up.y, up.z, 0.0],
+            [forward.x, forward.y, forward.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]))
+    }
+
+    ///Spherically interpolates between self (t = 0) and rhs (t = 1), taking the shortest path
+    pub fn slerp(self, rhs: Quat, t: f64) -> Quat {
+        let mut rhs = rhs;
+        let mut cos = self.dot(rhs);
+        if cos < 0.0 {
+            rhs = Quat::new(-rhs.x, -rhs.y, -rhs.z, -rhs.w);
+            cos = -cos;
+        }
+
+        //Very close rotations would divide by almost 0, so lerp instead
+        let (a, b) = if cos > 0.9995 {
+            (1.0 - t, t)
+        }
+        else {
+            let angle = cos.acos();
+            let sin = angle.sin();
+            (((1.0 - t) * angle).sin() / sin, (t * angle).sin() / sin)
+        };
+        Quat::new(
+            self.x * a + rhs.x * b,
+            self.y * a + rhs.y * b,
+            self.z * a + rhs.z * b,
+            self.w * a + rhs.w * b,
+        ).normalised()
+    }
+}
+
+impl From<[f64; 4]> for Quat {
+    fn from(q: [f64; 4]) -> Self {
+        Quat::new(q[0], q[1], q[2], q[3])
+    }
+}
+
+impl From<Quat> for [f64; 4] {
+    fn from(q: Quat) -> Self {
+        [q.x, q.y, q.z, q.w]
+    }
+}
+
+impl Mul for Quat {
+    type Output = Quat;
+    ///Combines two rotations, so that the result rotates by rhs then by self
+    fn mul(self, rhs: Quat) -> Quat {
+        Quat::new(
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+        )
+    }
+}
+
+///A 4x4 matrix stored on the stack
+///
+///Uses the row vector convention, so points are transformed as `[x, y, z, 1] * M`,
+///translation is in the last row, and `A * B` applies A then B.
+///Aligned so that rows can be loaded straight into simd registers, which lets the
+///compiler vectorise multiplication.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[repr(C, align(32))]
+pub struct Mat4 {
+    pub rows: [[f64; 4]; 4],
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Mat4::IDENTITY
+    }
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4 { rows: [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]};
+
+    pub const fn from_rows(rows: [[f64; 4]; 4]) -> Self {
+        Mat4 { rows }
+    }
+
+    pub fn from_translation(v: Vec3) -> Self {
+        Mat4::from_rows([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [v.x, v.y, v.z, 1.0],
+        ])
+    }
+
+    pub fn from_scale(v: Vec3) -> Self {
+        Mat4::from_rows([
+            [v.x, 0.0, 0.0, 0.0],
+            [0.0, v.y, 0.0, 0.0],
+            [0.0, 0.0, v.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn from_rotation(q: Quat) -> Self {
+        let q = q.normalised();
+        let (x, y, z, w) = (q.x, q.y, q.z, q.w);
+        Mat4::from_rows([
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y + w * z), 2.0 * (x * z - w * y), 0.0],
+            [2.0 * (x * y - w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z + w * x), 0.0],
+            [2.0 * (x * z + w * y), 2.0 * (y * z - w * x), 1.0 - 2.0 * (x * x + y * y), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let r = &self.rows;
+        Mat4::from_rows([
+            [r[0][0], r[1][0], r[2][0], r[3][0]],
+            [r[0][1], r[1][1], r[2][1], r[3][1]],
+            [r[0][2], r[1][2], r[2][2], r[3][2]],
+            [r[0][3], r[1][3], r[2][3], r[3][3]],
+        ])
+    }
+
+    ///Gets the inverse of the matrix
+    ///Returns None if the matrix is singular, such as a scale of 0
+    pub fn inverse(&self) -> Option<Mat4> {
+        let m = &self.rows;
+
+        //2x2 determinants of the top two rows and bottom two rows
+        let s0 = m[0][0] * m[1][1] - m[1][0] * m[0][1];
+        let s1 = m[0][0] * m[1][2] - m[1][0] * m[0][2];
+        let s2 = m[0][0] * m[1][3] - m[1][0] * m[0][3];
+        let s3 = m[0][1] * m[1][2] - m[1][1] * m[0][2];
+        let s4 = m[0][1] * m[1][3] - m[1][1] * m[0][3];
+        let s5 = m[0][2] * m[1][3] - m[1][2] * m[0][3];
+
+        let c5 = m[2][2] * m[3][3] - m[3][2] * m[2][3];
+        let c4 = m[2][1] * m[3][3] - m[3][1] * m[2][3];
+        let c3 = m[2][1] * m[3][2] - m[3][1] * m[2][2];
+        let c2 = m[2][0] * m[3][3] - m[3][0] * m[2][3];
+        let c1 = m[2][0] * m[3][2] - m[3][0] * m[2][2];
+        let c0 = m[2][0] * m[3][1] - m[3][0] * m[2][1];
+
+        let det = s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0;
+        if det == 0.0 || !det.is_finite() {
+            return None;
+        }
+        let d = 1.0 / det;
+
+        Some(Mat4::from_rows([
+            [
+                ( m[1][1] * c5 - m[1][2] * c4 + m[1][3] * c3) * d,
+                (-m[0][1] * c5 + m[0][2] * c4 - m[0][3] * c3) * d,
+                ( m[3][1] * s5 - m[3][2] * s4 + m[3][3] * s3) * d,
+                (-m[2][1] * s5 + m[2][2] * s4 - m[2][3] * s3) * d,
+            ],
+            [
+                (-m[1][0] * c5 + m[1][2] * c2 - m[1][3] * c1) * d,
+                ( m[0][0] * c5 - m[0][2] * c2 + m[0][3] * c1) * d,
+                (-m[3][0] * s5 + m[3][2] * s2 - m[3][3] * s1) * d,
+                ( m[2][0] * s5 - m[2][2] * s2 + m[2][3] * s1) * d,
+            ],
+            [
+                ( m[1][0] * c4 - m[1][1] * c2 + m[1][3] * c0) * d,
+                (-m[0][0] * c4 + m[0][1] * c2 - m[0][3] * c0) * d,
+                ( m[3][0] * s4 - m[3][1] * s2 + m[3][3] * s0) * d,
+                (-m[2][0] * s4 + m[2][1] * s2 - m[2][3] * s0) * d,
+            ],
+            [
+                (-m[1][0] * c3 + m[1][1] * c1 - m[1][2] * c0) * d,
+                ( m[0][0] * c3 - m[0][1] * c1 + m[0][2] * c0) * d,
+                (-m[3][0] * s3 + m[3][1] * s1 - m[3][2] * s0) * d,
+                ( m[2][0] * s3 - m[2][1] * s1 + m[2][2] * s0) * d,
+            ],
+        ]))
+    }
+
+    ///Transforms a point, applying translation
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        let r = &self.rows;
+        Vec3::new(
+            p.x * r[0][0] + p.y * r[1][0] + p.z * r[2][0] + r[3][0],
+            p.x * r[0][1] + p.y * r[1][1] + p.z * r[2][1] + r[3][1],
+            p.x * r[0][2] + p.y * r[1][2] + p.z * r[2][2] + r[3][2],
+        )
+    }
+
+    ///Transforms a point without dividing by w, for projection matrices
+    ///Returns [x, y, z, w]
+    pub fn transform_point4(&self, p: Vec3) -> [f64; 4] {
+        let r = &self.rows;
+        [0, 1, 2, 3].map(|j| p.x * r[0][j] + p.y * r[1][j] + p.z * r[2][j] + r[3][j])
+    }
+
+    ///Transforms a direction, ignoring translation
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let r = &self.rows;
+        Vec3::new(
+            v.x * r[0][0] + v.y * r[1][0] + v.z * r[2][0],
+            v.x * r[0][1] + v.y * r[1][1] + v.z * r[2][1],
+            v.x * r[0][2] + v.y * r[1][2] + v.z * r[2][2],
+        )
+    }
+
+    ///Gets the translation part of the matrix
+    pub fn get_translation(&self) -> Vec3 {
+        Vec3::new(self.rows[3][0], self.rows[3][1], self.rows[3][2])
+    }
+
+    ///Converts to the format used for shader uniforms
+    pub fn to_f32_array(&self) -> [[f32; 4]; 4] {
+        self.rows.map(|row| row.map(|x| x as f32))
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+    fn mul(self, rhs: Mat4) -> Mat4 {
+        let mut out = [[0.0; 4]; 4];
+        for (out_row, row) in out.iter_mut().zip(&self.rows) {
+            //Each output row is a sum of rhs's rows weighted by this row, which vectorises well
+            for (weight, rhs_row) in row.iter().zip(&rhs.rows) {
+                for (x, r) in out_row.iter_mut().zip(rhs_row) {
+                    *x += weight * r;
+                }
+            }
+        }
+        Mat4::from_rows(out)
+    }
+}
+
+impl MulAssign for Mat4 {
+    fn mul_assign(&mut self, rhs: Mat4) {
+        *self = *self * rhs;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 1e-9;
+
+    fn assert_mat4_near(a: &Mat4, b: &Mat4) {
+        for (row_a, row_b) in a.rows.iter().zip(b.rows.iter()) {
+            for (x, y) in row_a.iter().zip(row_b.iter()) {
+                assert!((x - y).abs() < EPSILON, "{:?} != {:?}", a, b);
+            }
+        }
+    }
+
+    fn assert_vec3_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < EPSILON, "{:?} != {:?}", a, b);
+    }
+
+    ///Checks two quaternions make the same rotation, where q and -q are the same
+    fn assert_same_rotation(a: Quat, b: Quat) {
+        assert!(a.dot(b).abs() > 1.0 - EPSILON, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn vec3_ops() {
+        let a = Vec3::new(1.0, 2.0, 3.0);
+        let b = Vec3::new(-2.0, 0.5, 4.0);
+        assert_eq!(a + b, Vec3::new(-1.0, 2.5, 7.0));
+        assert_eq!(a - b, Vec3::new(3.0, 1.5, -1.0));
+        assert_eq!(-a * 2.0, Vec3::new(-2.0, -4.0, -6.0));
+        assert_eq!(a.dot(b), 11.0);
+        assert_eq!(Vec3::X.cross(Vec3::Y), Vec3::Z);
+        assert_eq!(a.cross(b).dot(a), 0.0);
+        assert_eq!(a.scale(b), Vec3::new(-2.0, 1.0, 12.0));
+        assert_eq!(a.lerp(b, 0.5), Vec3::new(-0.5, 1.25, 3.5));
+        assert!((Vec3::new(3.0, 0.0, 4.0).normalised().length() - 1.0).abs() < EPSILON);
+        assert_eq!(Vec3::ZERO.normalised(), Vec3::ZERO);
+    }
+
+    #[test]
+    fn inverse_undoes_the_matrix() {
+        let m = Mat4::from_scale(Vec3::new(2.0, 0.5, 3.0))
+            * Mat4::from_rotation(Quat::from_euler(0.3, -1.2, 2.0))
+            * Mat4::from_translation(Vec3::new(4.0, -5.0, 6.0));
+        let inverse = m.inverse().unwrap();
+        assert_mat4_near(&(m * inverse), &Mat4::IDENTITY);
+        assert_mat4_near(&(inverse * m), &Mat4::IDENTITY);
+        assert_eq!(Mat4::from_scale(Vec3::new(1.0, 0.0, 1.0)).inverse(), None);
+    }
+
+    #[test]
+    fn quaternion_matrix_round_trip() {
+        for q in [Quat::IDENTITY, Quat::from_euler(0.3, -1.2, 2.0), Quat::from_axis_angle(Vec3::new(1.0, 1.0, 0.0), 3.0), Quat::from_axis_angle(Vec3::Y, std::f64::consts::PI)] {
+            assert_same_rotation(Quat::from_mat4(&Mat4::from_rotation(q)), q);
+
+            //The matrix and quaternion rotate vectors the same way
+            let v = Vec3::new(1.0, -2.0, 0.5);
+            assert_vec3_near(Mat4::from_rotation(q).transform_vector(v), q.rotate(v));
+        }
+        assert_vec3_near(Quat::from_axis_angle(Vec3::Z, std::f64::consts::FRAC_PI_2).rotate(Vec3::X), Vec3::Y);
+    }
+
+    #[test]
+    fn slerp_endpoints_and_midpoint() {
+        let a = Quat::from_axis_angle(Vec3::Y, 0.2);
+        let b = Quat::from_axis_angle(Vec3::Y, 1.4);
+        assert_same_rotation(a.slerp(b, 0.0), a);
+        assert_same_rotation(a.slerp(b, 1.0), b);
+        assert_same_rotation(a.slerp(b, 0.5), Quat::from_axis_angle(Vec3::Y, 0.8));
+
+        //Takes the shortest path when the quaternions are in opposite hemispheres
+        let negative_b = Quat::new(-b.x, -b.y, -b.z, -b.w);
+        assert_same_rotation(a.slerp(negative_b, 0.5), Quat::from_axis_angle(Vec3::Y, 0.8));
+    }
+
+    #[test]
+    fn from_euler_matches_euler_matrices() {
+        let (x, y, z): (f64, f64, f64) = (0.4, -0.9, 2.3);
+        //The x, then y, then z rotation matrices that Transform::from_euler multiplied before quaternions
+        let rotate_x = Mat4::from_rows([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, x.cos(), -x.sin(), 0.0],
+            [0.0, x.sin(), x.cos(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        let rotate_y = Mat4::from_rows([
+            [y.cos(), 0.0, y.sin(), 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [-y.sin(), 0.0, y.cos(), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        let rotate_z = Mat4::from_rows([
+            [z.cos(), z.sin(), 0.0, 0.0],
+            [-z.sin(), z.cos(), 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        assert_mat4_near(&Mat4::from_rotation(Quat::from_euler(x, y, z)), &(rotate_x * rotate_y * rotate_z));
+    }
+}
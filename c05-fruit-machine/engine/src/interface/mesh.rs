@@ -1,9 +1,10 @@
-use std::fs::File;
 use std::io::BufReader;
 
 use obj::load_obj;
 use glium::implement_vertex;
 
+use crate::{Assets, Handle, Aabb, BoundingSphere, Vec3, Instance, MeshShape};
+
 
 #[derive(Debug, Copy, Clone)]
 pub struct Vertex {
@@ -24,11 +25,27 @@ pub struct Mesh {
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u32>,
     pub shader: Box<dyn crate::shaders::Shader>,
+    ///Whether the mesh is drawn
+    pub visible: bool,
+    ///Meshes with a lower sort order are drawn first, for layering 2D meshes
+    ///Opaque meshes are always drawn before transparent ones
+    pub sort_order: i32,
+    ///Copies of the mesh to draw in one draw call, each relative to the mesh's transform
+    ///None draws the mesh once, while an empty list draws nothing
+    pub instances: Option<Vec<Instance>>,
+    ///The asset the mesh's data came from, if any
+    ///Used to update the mesh when the asset is reloaded
+    pub(crate) source: Option<Handle<MeshData>>,
+    ///The built in shape the mesh was created as, if any, for saving in scene files
+    pub(crate) shape: Option<MeshShape>,
+    ///Bounds of the vertices in the mesh's own space, used for culling
+    bounds: Aabb,
+    sphere: BoundingSphere,
 }
 
 impl Mesh {
     pub fn cube(shader: Box<dyn crate::shaders::Shader>) -> Self {
-        Mesh {
+        let mut mesh = Mesh {
             //TODO: normals and uvs
             vertices: vec![
                 Vertex{position: (-1.0, -1.0, -1.0), normal:(0.0, 0.0, 0.0), uv: (0.0, 0.0)},
@@ -54,12 +71,21 @@ impl Mesh {
                 4, 5, 7,
                 4, 7, 6,
             ],
-            shader:shader
-         }
+            shader,
+            source: None,
+            shape: Some(MeshShape::Cube),
+            visible: true,
+            sort_order: 0,
+            instances: None,
+            bounds: Default::default(),
+            sphere: Default::default(),
+        };
+        mesh.recalculate_bounds();
+        mesh
     }
 
     pub fn plane(double_sided: bool, shader: Box<dyn crate::shaders::Shader>) -> Self {
-        Mesh {
+        let mut mesh = Mesh {
             //TODO: normals
             vertices: vec![
                 Vertex {position: (-1.0, 1.0, 0.0), normal: (0.0, 0.0, 0.0), uv: (0.0, 1.0)},
@@ -79,81 +105,152 @@ impl Mesh {
                     2, 1, 3
                 ]
             },
-            shader:shader
-        }
+            shader,
+            source: None,
+            shape: Some(MeshShape::Plane { double_sided }),
+            visible: true,
+            sort_order: 0,
+            instances: None,
+            bounds: Default::default(),
+            sphere: Default::default(),
+        };
+        mesh.recalculate_bounds();
+        mesh
     }
 
-    pub fn from_obj(path: &str, shader: Box<dyn crate::shaders::Shader>) -> Result<Self, ObjLoadError> {
-        let file = match File::open(path) {
-            Ok(o) => o,
-            Err(e) => return Err(ObjLoadError::Io(e))
+    ///Creates a mesh from vertex and index data
+    pub fn from_data(data: &MeshData, shader: Box<dyn crate::shaders::Shader>) -> Self {
+        let mut mesh = Mesh {
+            vertices: data.vertices.clone(),
+            indices: data.indices.clone(),
+            shader,
+            source: None,
+            shape: None,
+            visible: true,
+            sort_order: 0,
+            instances: None,
+            bounds: Default::default(),
+            sphere: Default::default(),
         };
+        mesh.recalculate_bounds();
+        mesh
+    }
+
+    ///Creates a mesh from a loaded asset
+    ///The mesh is updated if the asset is reloaded
+    ///Returns None if the asset has not finished loading
+    pub fn from_asset(assets: &Assets, handle: Handle<MeshData>, shader: Box<dyn crate::shaders::Shader>) -> Option<Self> {
+        let mut mesh = Mesh::from_data(assets.get(handle)?, shader);
+        mesh.source = Some(handle);
+        Some(mesh)
+    }
+
+    pub fn from_obj(path: &str, shader: Box<dyn crate::shaders::Shader>) -> Result<Self, ObjLoadError> {
+        Ok(Mesh::from_data(&MeshData::from_obj(path)?, shader))
+    }
+
+    ///Creates a mesh from the contents of an obj file
+    pub fn from_obj_bytes(bytes: &[u8], shader: Box<dyn crate::shaders::Shader>) -> Result<Self, ObjLoadError> {
+        Ok(Mesh::from_data(&MeshData::from_obj_bytes(bytes)?, shader))
+    }
+
+    ///Draws the mesh once for each instance instead of once
+    pub fn with_instances(mut self, instances: Vec<Instance>) -> Self {
+        self.instances = Some(instances);
+        self
+    }
+
+    ///Gets the box containing every vertex, in the mesh's own space
+    ///Includes every instance if the mesh is instanced
+    pub fn get_bounds(&self) -> Aabb {
+        match &self.instances {
+            Some(instances) => instances.iter()
+                .map(|instance| self.bounds.transformed(&instance.transform.to_matrix()))
+                .reduce(|a, b| a.union(&b))
+                .unwrap_or_default(),
+            None => self.bounds
+        }
+    }
+
+    ///Gets the sphere containing every vertex, in the mesh's own space
+    ///Ignores instances
+    pub fn get_bounding_sphere(&self) -> BoundingSphere {
+        self.sphere
+    }
 
-        let reader = BufReader::new(file);
+    ///Updates the bounds to fit the vertices
+    ///Must be called after changing vertices, or the mesh may be culled while it is visible
+    pub fn recalculate_bounds(&mut self) {
+        let points = self.vertices.iter().map(|v| Vec3::new(v.position.0 as f64, v.position.1 as f64, v.position.2 as f64));
+        self.bounds = Aabb::from_points(points.clone()).unwrap_or_default();
+        self.sphere = BoundingSphere::from_points(points).unwrap_or_default();
+    }
+}
+
+///The vertex and index data of a mesh, without a shader
+///Can be shared between multiple meshes with different shaders
+#[derive(Debug, Clone)]
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    pub fn from_obj(path: &str) -> Result<Self, ObjLoadError> {
+        match std::fs::read(path) {
+            Ok(bytes) => MeshData::from_obj_bytes(&bytes),
+            Err(e) => Err(ObjLoadError::Io(e))
+        }
+    }
+
+    ///Loads mesh data from the contents of an obj file
+    pub fn from_obj_bytes(bytes: &[u8]) -> Result<Self, ObjLoadError> {
+        let reader = BufReader::new(bytes);
 
         // Try with position, normal and uv
-        match load_obj::<obj::TexturedVertex, BufReader<File>, u32>(reader) {
-            Ok(object) => return Ok(Mesh{
+        match load_obj::<obj::TexturedVertex, BufReader<&[u8]>, u32>(reader) {
+            Ok(object) => return Ok(MeshData{
                 vertices: object.vertices.iter().map(|vert| Vertex {
                     position:(vert.position[0], vert.position[1], vert.position[2]),
                     normal: (vert.normal[0], vert.normal[1], vert.normal[2]),
                     uv: (vert.texture[0], vert.texture[1])
                 }).collect(),
-                indices: object.indices.iter().map(|x|u32::from(x.to_owned())).collect(),
-                shader: shader
+                indices: object.indices
             }),
-            Err(e) => match e {
-                obj::ObjError::Io(ioe) => return Err(ObjLoadError::Io(ioe)),
-                _ => ()
-            }
+            Err(obj::ObjError::Io(ioe)) => return Err(ObjLoadError::Io(ioe)),
+            Err(_) => ()
         };
 
-        let file = match File::open(path) {
-            Ok(o) => o,
-            Err(e) => return Err(ObjLoadError::Io(e))
-        };
-
-        let reader = BufReader::new(file);
+        let reader = BufReader::new(bytes);
 
         // Try with position and normal only
-        match load_obj::<obj::Vertex, BufReader<File>, u32>(reader) {
-            Ok(object) => return Ok(Mesh{
+        match load_obj::<obj::Vertex, BufReader<&[u8]>, u32>(reader) {
+            Ok(object) => return Ok(MeshData{
                 vertices: object.vertices.iter().map(|vert| Vertex {
                     position:(vert.position[0], vert.position[1], vert.position[2]),
                     normal: (vert.normal[0], vert.normal[1], vert.normal[2]),
                     uv: (0.0, 0.0)
                 }).collect(),
-                indices: object.indices.iter().map(|x|u32::from(x.to_owned())).collect(),
-                shader: shader
+                indices: object.indices
             }),
-            Err(e) => match e {
-                obj::ObjError::Io(ioe) => return Err(ObjLoadError::Io(ioe)),
-                _ => ()
-            }
-        };
-
-        let file = match File::open(path) {
-            Ok(o) => o,
-            Err(e) => return Err(ObjLoadError::Io(e))
+            Err(obj::ObjError::Io(ioe)) => return Err(ObjLoadError::Io(ioe)),
+            Err(_) => ()
         };
 
-        let reader = BufReader::new(file);
+        let reader = BufReader::new(bytes);
 
         // Try with position only
-        match load_obj::<obj::Position, BufReader<File>, u32>(reader) {
-            Ok(object) => return Ok(Mesh{
+        match load_obj::<obj::Position, BufReader<&[u8]>, u32>(reader) {
+            Ok(object) => return Ok(MeshData{
                 vertices: object.vertices.iter().map(|vert| Vertex {
                     position:(vert.position[0], vert.position[1], vert.position[2]),
                     normal: (0.0, 0.0, 0.0),
                     uv: (0.0, 0.0)
                 }).collect(),
-                indices: object.indices.iter().map(|x|u32::from(x.to_owned())).collect(),
-                shader: shader
+                indices: object.indices
             }),
-            Err(e) => match e {
-                obj::ObjError::Io(ioe) => return Err(ObjLoadError::Io(ioe)),
-                _ => ()
-            }
+            Err(obj::ObjError::Io(ioe)) => return Err(ObjLoadError::Io(ioe)),
+            Err(_) => ()
         };
         
         println!("No data");
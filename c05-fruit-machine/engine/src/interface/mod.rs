@@ -1,16 +1,47 @@
 pub mod gameobject;
+pub mod ecs;
 pub mod scene;
 pub mod window;
+pub mod monitor;
 pub mod camera;
+pub mod viewport;
+pub mod math;
 pub mod transform;
+pub mod bounds;
 pub mod mesh;
+pub mod instance;
+pub mod particles;
 pub mod frame;
+pub mod assets;
+pub mod pack;
+pub mod audio;
+pub mod postprocess;
+pub mod debug;
+pub mod scenefile;
+pub mod scenestack;
 pub mod shaders;
+///Headless gl contexts for tests, created with EGL's surfaceless platform so no window or display server is needed
+#[cfg(test)]
+pub(crate) mod headless;
 
 pub use gameobject::*;
+pub use ecs::*;
 pub use scene::*;
 pub use window::*;
+pub use monitor::*;
 pub use camera::*;
+pub use viewport::*;
+pub use math::*;
 pub use transform::*;
+pub use bounds::*;
 pub use mesh::*;
-pub use frame::*;
\ No newline at end of file
+pub use instance::*;
+pub use particles::*;
+pub use frame::*;
+pub use assets::*;
+pub use pack::*;
+pub use audio::*;
+pub use postprocess::*;
+pub use debug::*;
+pub use scenefile::*;
+pub use scenestack::*;
\ No newline at end of file
@@ -0,0 +1,197 @@
+use glium::glutin;
+use glium::glutin::monitor::MonitorHandle;
+
+///A screen connected to the computer
+#[derive(Debug, Clone, PartialEq)]
+pub struct Monitor {
+    pub name: Option<String>,
+    ///Size of the monitor in physical pixels
+    pub resolution: (u32, u32),
+    ///Position of the top left of the monitor on the desktop, in physical pixels
+    pub position: (i32, i32),
+    ///Number of physical pixels per logical pixel
+    pub scale_factor: f64,
+    ///Refresh rate of the current video mode in millihertz, if known
+    pub refresh_rate: Option<u32>,
+    pub(crate) handle: MonitorHandle,
+}
+
+impl Monitor {
+    ///Gets every video mode the monitor supports, for exclusive fullscreen
+    pub fn get_video_modes(&self) -> Vec<VideoMode> {
+        self.handle.video_modes().map(VideoMode::from).collect()
+    }
+}
+
+impl From<MonitorHandle> for Monitor {
+    fn from(handle: MonitorHandle) -> Self {
+        let size = handle.size();
+        let position = handle.position();
+        Monitor {
+            name: handle.name(),
+            resolution: (size.width, size.height),
+            position: (position.x, position.y),
+            scale_factor: handle.scale_factor(),
+            refresh_rate: handle.refresh_rate_millihertz(),
+            handle,
+        }
+    }
+}
+
+///A resolution, colour depth and refresh rate that a monitor can be switched to in exclusive fullscreen
+#[derive(Debug, Clone, PartialEq)]
+pub struct VideoMode {
+    pub resolution: (u32, u32),
+    pub bit_depth: u16,
+    ///Refresh rate in millihertz
+    pub refresh_rate: u32,
+    pub(crate) handle: glutin::monitor::VideoMode,
+}
+
+impl VideoMode {
+    pub fn get_monitor(&self) -> Monitor {
+        Monitor::from(self.handle.monitor())
+    }
+}
+
+impl From<glutin::monitor::VideoMode> for VideoMode {
+    fn from(handle: glutin::monitor::VideoMode) -> Self {
+        let size = handle.size();
+        VideoMode {
+            resolution: (size.width, size.height),
+            bit_depth: handle.bit_depth(),
+            refresh_rate: handle.refresh_rate_millihertz(),
+            handle,
+        }
+    }
+}
+
+///How a window covers a monitor
+#[derive(Debug, Clone, PartialEq)]
+pub enum FullscreenMode {
+    ///Covers a monitor with a borderless window, without changing the monitor's video mode
+    ///None uses the monitor the window is currently on
+    Borderless(Option<Monitor>),
+    ///Takes over a monitor and switches it to a video mode
+    Exclusive(VideoMode),
+}
+
+impl FullscreenMode {
+    pub(crate) fn to_glutin(&self) -> glutin::window::Fullscreen {
+        match self {
+            FullscreenMode::Borderless(monitor) => glutin::window::Fullscreen::Borderless(monitor.as_ref().map(|m| m.handle.clone())),
+            FullscreenMode::Exclusive(mode) => glutin::window::Fullscreen::Exclusive(mode.handle.clone()),
+        }
+    }
+
+    pub(crate) fn from_glutin(fullscreen: glutin::window::Fullscreen) -> Self {
+        match fullscreen {
+            glutin::window::Fullscreen::Borderless(monitor) => FullscreenMode::Borderless(monitor.map(Monitor::from)),
+            glutin::window::Fullscreen::Exclusive(mode) => FullscreenMode::Exclusive(VideoMode::from(mode)),
+        }
+    }
+}
+
+///Whether the window waits for the monitor to refresh before showing each frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VSync {
+    ///Shows frames as soon as they are drawn, which can tear
+    #[default]
+    Off,
+    ///Waits for the monitor, so frames never tear
+    On,
+    ///Waits for the monitor unless the frame is late, so slow frames tear instead of halving the frame rate
+    ///Uses a swap interval of -1, which needs GLX_EXT_swap_control_tear on X11 or WGL_EXT_swap_control_tear on Windows
+    ///Other platforms, including Wayland and macOS, can't swap late frames early, so fall back to On
+    Adaptive,
+}
+
+impl VSync {
+    pub(crate) fn is_enabled(self) -> bool {
+        self != VSync::Off
+    }
+
+    ///Sets up a display created with vsync enabled for this mode
+    ///Returns false if the mode couldn't be used, in which case the display is left with vsync on
+    pub(crate) fn apply(self, display: &glium::Display) -> bool {
+        match self {
+            VSync::Adaptive => swap_interval::set_adaptive(display),
+            _ => true
+        }
+    }
+}
+
+///Sets the swap interval of a display's context to -1 for adaptive vsync,
+///as glutin can only turn vsync on or off
+mod swap_interval {
+    #[cfg(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"))]
+    pub(super) fn set_adaptive(display: &glium::Display) -> bool {
+        use std::ffi::{c_char, c_int, c_ulong, c_void, CStr};
+        use glium::glutin::platform::ContextTraitExt;
+        use glium::glutin::platform::unix::{RawHandle, WindowExtUnix};
+
+        type QueryExtensionsString = unsafe extern "C" fn(*mut c_void, c_int) -> *const c_char;
+        type SwapIntervalEXT = unsafe extern "C" fn(*mut c_void, c_ulong, c_int);
+
+        let gl_window = display.gl_window();
+        //EGL contexts, such as on Wayland, clamp the interval to their minimum so can't use -1
+        if !matches!(unsafe { gl_window.raw_handle() }, RawHandle::Glx(_)) {
+            return false;
+        }
+        let window = gl_window.window();
+        let (Some(xlib_display), Some(xlib_window), Some(screen)) = (window.xlib_display(), window.xlib_window(), window.xlib_screen_id()) else {
+            return false;
+        };
+
+        let query_extensions = gl_window.get_proc_address("glXQueryExtensionsString");
+        let swap_interval = gl_window.get_proc_address("glXSwapIntervalEXT");
+        if query_extensions.is_null() || swap_interval.is_null() {
+            return false;
+        }
+        unsafe {
+            let query_extensions: QueryExtensionsString = std::mem::transmute(query_extensions);
+            let extensions = query_extensions(xlib_display, screen);
+            if extensions.is_null() || !CStr::from_ptr(extensions).to_string_lossy().split(' ').any(|e| e == "GLX_EXT_swap_control_tear") {
+                return false;
+            }
+            let swap_interval: SwapIntervalEXT = std::mem::transmute(swap_interval);
+            swap_interval(xlib_display, xlib_window, -1);
+        }
+        true
+    }
+
+    #[cfg(target_os = "windows")]
+    pub(super) fn set_adaptive(display: &glium::Display) -> bool {
+        use std::ffi::{c_char, c_int, CStr};
+        use glium::glutin::platform::ContextTraitExt;
+        use glium::glutin::platform::windows::RawHandle;
+
+        type GetExtensionsStringEXT = unsafe extern "system" fn() -> *const c_char;
+        type SwapIntervalEXT = unsafe extern "system" fn(c_int) -> c_int;
+
+        let gl_window = display.gl_window();
+        if !matches!(unsafe { gl_window.raw_handle() }, RawHandle::Wgl(_)) {
+            return false;
+        }
+
+        let get_extensions = gl_window.get_proc_address("wglGetExtensionsStringEXT");
+        let swap_interval = gl_window.get_proc_address("wglSwapIntervalEXT");
+        if get_extensions.is_null() || swap_interval.is_null() {
+            return false;
+        }
+        unsafe {
+            let get_extensions: GetExtensionsStringEXT = std::mem::transmute(get_extensions);
+            let extensions = get_extensions();
+            if extensions.is_null() || !CStr::from_ptr(extensions).to_string_lossy().split(' ').any(|e| e == "WGL_EXT_swap_control_tear") {
+                return false;
+            }
+            let swap_interval: SwapIntervalEXT = std::mem::transmute(swap_interval);
+            swap_interval(-1) != 0
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "dragonfly", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd", target_os = "windows")))]
+    pub(super) fn set_adaptive(_display: &glium::Display) -> bool {
+        false
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+///Bytes at the start of every pack file
+const PACK_MAGIC: &[u8; 4] = b"PAK1";
+
+#[derive(Debug)]
+pub enum PackLoadError {
+    Io(std::io::Error),
+    InvalidFile
+}
+
+///A collection of named files stored in one buffer
+///
+///The format is the magic bytes "PAK1", then a little endian u32 count of files,
+///then for each file a u32 name length, the utf-8 name, a u64 data length, and the data.
+///Names are paths relative to the asset root, separated with '/'.
+///
+///Packs can be embedded in the executable with include_bytes! and mounted on Assets,
+///so that the game does not need any files next to it to run.
+#[derive(Debug, Clone, Default)]
+pub struct AssetPack {
+    files: HashMap<String, Arc<[u8]>>,
+}
+
+impl AssetPack {
+    ///Creates an empty pack
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    ///Reads a pack from the bytes of a pack file
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PackLoadError> {
+        let mut reader = PackReader { bytes, position: 0 };
+
+        if reader.take(4)? != PACK_MAGIC {
+            return Err(PackLoadError::InvalidFile);
+        }
+
+        let count = u32::from_le_bytes(reader.take(4)?.try_into().unwrap());
+        let mut pack = AssetPack::new();
+        for _ in 0..count {
+            let name_len = u32::from_le_bytes(reader.take(4)?.try_into().unwrap()) as usize;
+            let name = match std::str::from_utf8(reader.take(name_len)?) {
+                Ok(n) => n.to_string(),
+                Err(_) => return Err(PackLoadError::InvalidFile)
+            };
+            let data_len = match usize::try_from(u64::from_le_bytes(reader.take(8)?.try_into().unwrap())) {
+                Ok(l) => l,
+                Err(_) => return Err(PackLoadError::InvalidFile)
+            };
+            pack.insert(&name, reader.take(data_len)?);
+        }
+
+        Ok(pack)
+    }
+
+    ///Reads a pack file
+    pub fn open(path: &str) -> Result<Self, PackLoadError> {
+        match fs::read(path) {
+            Ok(bytes) => AssetPack::from_bytes(&bytes),
+            Err(e) => Err(PackLoadError::Io(e))
+        }
+    }
+
+    ///Creates a pack containing every file in a directory and its subdirectories
+    pub fn from_dir(root: &str) -> Result<Self, PackLoadError> {
+        let mut pack = AssetPack::new();
+        pack.add_dir(Path::new(root), "")?;
+        Ok(pack)
+    }
+
+    fn add_dir(&mut self, dir: &Path, prefix: &str) -> Result<(), PackLoadError> {
+        let entries = match fs::read_dir(dir) {
+            Ok(e) => e,
+            Err(e) => return Err(PackLoadError::Io(e))
+        };
+        for entry in entries {
+            let entry = match entry {
+                Ok(e) => e,
+                Err(e) => return Err(PackLoadError::Io(e))
+            };
+            let name = prefix.to_string() + &entry.file_name().to_string_lossy();
+            let path = entry.path();
+            if path.is_dir() {
+                self.add_dir(&path, &(name + "/"))?;
+            }
+            else {
+                match fs::read(&path) {
+                    Ok(bytes) => self.insert(&name, &bytes),
+                    Err(e) => return Err(PackLoadError::Io(e))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    ///Adds a file to the pack, replacing any file with the same name
+    pub fn insert(&mut self, name: &str, data: &[u8]) {
+        self.files.insert(normalise_name(name), Arc::from(data));
+    }
+
+    ///Gets the contents of a file in the pack
+    pub fn get(&self, name: &str) -> Option<&[u8]> {
+        self.files.get(&normalise_name(name)).map(|d| &d[..])
+    }
+
+    ///Gets a shared reference to the contents of a file, to avoid copying it
+    pub(crate) fn get_shared(&self, name: &str) -> Option<Arc<[u8]>> {
+        self.files.get(&normalise_name(name)).cloned()
+    }
+
+    ///Gets the names of every file in the pack
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.files.keys()
+    }
+
+    ///Writes the pack into the pack file format
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = PACK_MAGIC.to_vec();
+        bytes.extend_from_slice(&(self.files.len() as u32).to_le_bytes());
+
+        //Sort names so that the same pack always produces the same file
+        let mut names: Vec<&String> = self.files.keys().collect();
+        names.sort();
+        for name in names {
+            let data = &self.files[name];
+            bytes.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(data);
+        }
+        bytes
+    }
+
+    ///Writes the pack to a file
+    pub fn save(&self, path: &str) -> Result<(), std::io::Error> {
+        fs::write(path, self.to_bytes())
+    }
+}
+
+///Makes names use '/' separators with no leading "./" so that lookups match however the path was written
+fn normalise_name(name: &str) -> String {
+    let name = name.replace('\\', "/");
+    name.trim_start_matches("./").to_string()
+}
+
+///Helper to read sections of a pack file with bounds checking
+struct PackReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> PackReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], PackLoadError> {
+        if self.bytes.len() - self.position < len {
+            return Err(PackLoadError::InvalidFile);
+        }
+        let slice = &self.bytes[self.position..self.position + len];
+        self.position += len;
+        Ok(slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example() -> AssetPack {
+        let mut pack = AssetPack::new();
+        pack.insert("icon.webp", &[1, 2, 3]);
+        pack.insert("sounds\\win.wav", b"RIFF");
+        pack.insert("./empty.txt", &[]);
+        pack
+    }
+
+    #[test]
+    fn round_trip() {
+        let bytes = example().to_bytes();
+        assert!(bytes.starts_with(PACK_MAGIC));
+        let pack = AssetPack::from_bytes(&bytes).unwrap();
+
+        let mut names: Vec<&String> = pack.names().collect();
+        names.sort();
+        assert_eq!(names, ["empty.txt", "icon.webp", "sounds/win.wav"]);
+        assert_eq!(pack.get("icon.webp"), Some(&[1, 2, 3][..]));
+        assert_eq!(pack.get("sounds/win.wav"), Some(&b"RIFF"[..]));
+        assert_eq!(pack.get("empty.txt"), Some(&[][..]));
+        assert_eq!(pack.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn truncated_files_are_invalid() {
+        let bytes = example().to_bytes();
+        for len in 0..bytes.len() {
+            assert!(matches!(AssetPack::from_bytes(&bytes[..len]), Err(PackLoadError::InvalidFile)), "accepted {} bytes", len);
+        }
+    }
+
+    #[test]
+    fn bad_magic_is_invalid() {
+        let mut bytes = example().to_bytes();
+        bytes[3] = b'2';
+        assert!(matches!(AssetPack::from_bytes(&bytes), Err(PackLoadError::InvalidFile)));
+    }
+
+    #[test]
+    fn oversized_lengths_are_invalid() {
+        let mut bytes = PACK_MAGIC.to_vec();
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.push(b'a');
+        bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(AssetPack::from_bytes(&bytes), Err(PackLoadError::InvalidFile)));
+    }
+}
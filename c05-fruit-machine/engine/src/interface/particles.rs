@@ -0,0 +1,275 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{Instance, Quat, Transform, Vec3};
+
+///How a particle emitter spawns and moves its particles
+///Ranges are given as (start, end) for values that change over a particle's life, or (min, max) for random values
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParticleSettings {
+    ///Particles spawned per second while the emitter is emitting
+    pub spawn_rate: f64,
+    ///Most particles alive at once, no more are spawned until some have died
+    pub max_particles: usize,
+    ///Shortest and longest time in seconds that a particle lives
+    pub lifetime: (f64, f64),
+    ///Velocity of new particles, in the mesh's space
+    pub velocity: Vec3,
+    ///Largest random change added to the velocity of new particles along each axis
+    pub velocity_spread: Vec3,
+    ///Acceleration applied to every particle
+    pub gravity: Vec3,
+    ///Scale of particles at the start and end of their life
+    pub size: (f64, f64),
+    ///Colour of particles at the start and end of their life, multiplied with the shader's colour
+    pub colour: ([f32; 4], [f32; 4]),
+    ///Number of columns and rows of frames in the texture, (1, 1) uses the whole texture
+    pub atlas: (u32, u32),
+    ///Whether particles play through the atlas frames over their life, instead of each showing a random frame
+    pub animate_frames: bool,
+}
+
+impl Default for ParticleSettings {
+    fn default() -> Self {
+        ParticleSettings {
+            spawn_rate: 10.0,
+            max_particles: 1000,
+            lifetime: (1.0, 1.0),
+            velocity: Vec3::new(0.0, 1.0, 0.0),
+            velocity_spread: Vec3::ZERO,
+            gravity: Vec3::ZERO,
+            size: (1.0, 1.0),
+            colour: ([1.0, 1.0, 1.0, 1.0], [1.0, 1.0, 1.0, 1.0]),
+            atlas: (1, 1),
+            animate_frames: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Particle {
+    position: Vec3,
+    velocity: Vec3,
+    age: f64,
+    lifetime: f64,
+    ///Atlas frame shown when frames aren't animated
+    frame: u32,
+}
+
+///Spawns and simulates particles, which are drawn as instances of a mesh
+///Add to a GameObject with GameObject::add_emitter, which updates it every frame
+///Particles are simulated in the space of the mesh, so they move with the GameObject
+#[derive(Debug, Clone)]
+pub struct ParticleEmitter {
+    pub settings: ParticleSettings,
+    ///Whether particles are spawned at the spawn rate, bursts spawn particles either way
+    pub emitting: bool,
+
+    particles: Vec<Particle>,
+    ///Part of a particle left over from the last update, so low spawn rates still spawn
+    spawn_remainder: f64,
+    rng: u64,
+    ///Index of the GameObject mesh that draws the particles
+    pub(crate) mesh: Option<usize>,
+}
+
+impl ParticleEmitter {
+    pub fn new(settings: ParticleSettings) -> Self {
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+        ParticleEmitter {
+            settings,
+            emitting: true,
+            particles: Vec::new(),
+            spawn_remainder: 0.0,
+            rng: seed | 1,
+            mesh: None,
+        }
+    }
+
+    ///Sets the seed used for random values, so that the emitter behaves the same every time
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = seed | 1;
+        self
+    }
+
+    ///Spawns a number of particles at once, up to max_particles
+    pub fn burst(&mut self, count: usize) {
+        for _ in 0..count {
+            self.spawn();
+        }
+    }
+
+    ///Removes every particle
+    pub fn clear(&mut self) {
+        self.particles.clear();
+        self.spawn_remainder = 0.0;
+    }
+
+    pub fn get_particle_count(&self) -> usize {
+        self.particles.len()
+    }
+
+    ///Moves the particles forward by delta seconds, removing dead ones and spawning new ones
+    pub fn update(&mut self, delta: f64) {
+        let gravity = self.settings.gravity;
+        for particle in self.particles.iter_mut() {
+            particle.age += delta;
+            particle.velocity += gravity * delta;
+            particle.position += particle.velocity * delta;
+        }
+        self.particles.retain(|p| p.age < p.lifetime);
+
+        if self.emitting {
+            self.spawn_remainder += self.settings.spawn_rate * delta;
+            while self.spawn_remainder >= 1.0 {
+                self.spawn_remainder -= 1.0;
+                self.spawn();
+            }
+        }
+    }
+
+    ///Gets the instances that draw the particles
+    pub fn get_instances(&self) -> Vec<Instance> {
+        let settings = &self.settings;
+        let (columns, rows) = (settings.atlas.0.max(1), settings.atlas.1.max(1));
+        let frames = columns * rows;
+        let uv_scale = [1.0 / columns as f32, 1.0 / rows as f32];
+
+        self.particles.iter().map(|particle| {
+            let t = (particle.age / particle.lifetime).clamp(0.0, 1.0);
+            let size = settings.size.0 + (settings.size.1 - settings.size.0) * t;
+            let colour = [0, 1, 2, 3].map(|i| settings.colour.0[i] + (settings.colour.1[i] - settings.colour.0[i]) * t as f32);
+
+            let frame = match settings.animate_frames {
+                true => ((t * frames as f64) as u32).min(frames - 1),
+                false => particle.frame % frames
+            };
+            //Frames go left to right from the top row, but uvs start at the bottom
+            let uv_offset = [
+                (frame % columns) as f32 * uv_scale[0],
+                (rows - 1 - frame / columns) as f32 * uv_scale[1],
+            ];
+
+            Instance {
+                transform: Transform::new(particle.position, Quat::IDENTITY, Vec3::new(size, size, size)),
+                colour,
+                uv_offset,
+                uv_scale,
+            }
+        }).collect()
+    }
+
+    fn spawn(&mut self) {
+        if self.particles.len() >= self.settings.max_particles {
+            return;
+        }
+
+        let (min_life, max_life) = self.settings.lifetime;
+        let lifetime = min_life + (max_life - min_life) * self.random();
+        let spread = self.settings.velocity_spread;
+        let velocity = self.settings.velocity + Vec3::new(
+            spread.x * (self.random() * 2.0 - 1.0),
+            spread.y * (self.random() * 2.0 - 1.0),
+            spread.z * (self.random() * 2.0 - 1.0),
+        );
+        let frames = self.settings.atlas.0.max(1) * self.settings.atlas.1.max(1);
+        let frame = (self.random() * frames as f64) as u32;
+
+        self.particles.push(Particle {
+            position: Vec3::ZERO,
+            velocity,
+            age: 0.0,
+            lifetime,
+            frame,
+        });
+    }
+
+    ///Gets a random number from 0 to 1 with xorshift, so the engine doesn't need a random number crate
+    fn random(&mut self) -> f64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn emitter(settings: ParticleSettings) -> ParticleEmitter {
+        ParticleEmitter::new(settings).with_seed(12345)
+    }
+
+    #[test]
+    fn seeded_emitters_match() {
+        let settings = ParticleSettings { velocity_spread: Vec3::ONE, lifetime: (0.5, 2.0), atlas: (4, 4), ..Default::default() };
+        let mut a = emitter(settings.clone());
+        let mut b = emitter(settings);
+        for _ in 0..30 {
+            a.update(0.1);
+            b.update(0.1);
+        }
+        assert!(a.get_particle_count() > 0);
+        assert_eq!(format!("{:?}", a.get_instances()), format!("{:?}", b.get_instances()));
+    }
+
+    #[test]
+    fn bursts_are_capped_at_max_particles() {
+        let mut emitter = emitter(ParticleSettings { max_particles: 5, ..Default::default() });
+        emitter.burst(3);
+        assert_eq!(emitter.get_particle_count(), 3);
+        emitter.burst(10);
+        assert_eq!(emitter.get_particle_count(), 5);
+    }
+
+    #[test]
+    fn particles_die_at_their_lifetime() {
+        let mut emitter = emitter(ParticleSettings { lifetime: (1.0, 1.0), ..Default::default() });
+        emitter.emitting = false;
+        emitter.burst(3);
+        emitter.update(0.5);
+        assert_eq!(emitter.get_particle_count(), 3);
+        emitter.update(0.5);
+        assert_eq!(emitter.get_particle_count(), 0);
+    }
+
+    #[test]
+    fn low_spawn_rates_accumulate() {
+        let mut emitter = emitter(ParticleSettings { spawn_rate: 0.5, lifetime: (100.0, 100.0), ..Default::default() });
+        for _ in 0..7 {
+            emitter.update(0.25);
+        }
+        assert_eq!(emitter.get_particle_count(), 0);
+        emitter.update(0.25);
+        assert_eq!(emitter.get_particle_count(), 1);
+        for _ in 0..8 {
+            emitter.update(0.25);
+        }
+        assert_eq!(emitter.get_particle_count(), 2);
+    }
+
+    #[test]
+    fn animated_frames_move_through_the_atlas() {
+        let mut emitter = emitter(ParticleSettings {
+            lifetime: (1.0, 1.0),
+            atlas: (2, 2),
+            animate_frames: true,
+            size: (1.0, 3.0),
+            ..Default::default()
+        });
+        emitter.emitting = false;
+        emitter.burst(1);
+
+        //Frames go left to right from the top row, and uvs start at the bottom left
+        let mut age = 0.0;
+        for (next_age, uv_offset) in [(0.1, [0.0, 0.5]), (0.3, [0.5, 0.5]), (0.6, [0.0, 0.0]), (0.9, [0.5, 0.0])] {
+            emitter.update(next_age - age);
+            age = next_age;
+            let instances = emitter.get_instances();
+            assert_eq!(instances[0].uv_offset, uv_offset);
+            assert_eq!(instances[0].uv_scale, [0.5, 0.5]);
+        }
+        let scale = emitter.get_instances()[0].transform.get_scale();
+        assert!((scale.x - 2.8).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,282 @@
+use std::fmt::Debug;
+use std::rc::Rc;
+
+use glium::framebuffer::{DepthRenderBuffer, SimpleFrameBuffer};
+use glium::texture::{DepthFormat, SrgbTexture2d};
+use glium::uniforms::UniformValue;
+use glium::{Display, IndexBuffer, Program, Surface, VertexBuffer};
+
+use crate::shaders::{self, RenderError, shader_priv::{AssetCreationError, ShaderUniforms, UniformType}};
+
+pub(crate) const POST_VERTEX_SHADER: &str = r#"
+    #version 140
+
+    in vec2 position;
+
+    out vec2 v_uv;
+
+    void main() {
+        v_uv = position * 0.5 + 0.5;
+        gl_Position = vec4(position, 0.0, 1.0);
+    }
+"#;
+
+const BLOOM_FRAGMENT_SHADER: &str = r#"
+    #version 140
+
+    in vec2 v_uv;
+
+    out vec4 colour_out;
+
+    uniform sampler2D tex;
+    uniform vec2 resolution;
+    uniform float threshold;
+    uniform float intensity;
+    uniform float radius;
+
+    vec3 bright(vec2 uv) {
+        return max(texture(tex, uv).rgb - threshold, 0.0);
+    }
+
+    void main() {
+        vec3 glow = vec3(0.0);
+        float total = 0.0;
+        for (int x = -4; x <= 4; x++) {
+            for (int y = -4; y <= 4; y++) {
+                float weight = exp(-float(x * x + y * y) / 8.0);
+                glow += bright(v_uv + vec2(x, y) * radius / resolution) * weight;
+                total += weight;
+            }
+        }
+        colour_out = vec4(texture(tex, v_uv).rgb + glow / total * intensity, 1.0);
+    }
+"#;
+
+const VIGNETTE_FRAGMENT_SHADER: &str = r#"
+    #version 140
+
+    in vec2 v_uv;
+
+    out vec4 colour_out;
+
+    uniform sampler2D tex;
+    uniform float strength;
+    uniform float radius;
+
+    void main() {
+        float edge = smoothstep(radius, 0.75, distance(v_uv, vec2(0.5)));
+        colour_out = vec4(texture(tex, v_uv).rgb * (1.0 - strength * edge), 1.0);
+    }
+"#;
+
+const COLOUR_GRADE_FRAGMENT_SHADER: &str = r#"
+    #version 140
+
+    in vec2 v_uv;
+
+    out vec4 colour_out;
+
+    uniform sampler2D tex;
+    uniform float brightness;
+    uniform float contrast;
+    uniform float saturation;
+    uniform vec3 tint;
+
+    void main() {
+        vec3 colour = texture(tex, v_uv).rgb * brightness;
+        colour = (colour - 0.5) * contrast + 0.5;
+        float luma = dot(colour, vec3(0.2126, 0.7152, 0.0722));
+        colour = mix(vec3(luma), colour, saturation) * tint;
+        colour_out = vec4(clamp(colour, 0.0, 1.0), 1.0);
+    }
+"#;
+
+const CRT_FRAGMENT_SHADER: &str = r#"
+    #version 140
+
+    in vec2 v_uv;
+
+    out vec4 colour_out;
+
+    uniform sampler2D tex;
+    uniform vec2 resolution;
+    uniform float curvature;
+    uniform float scanline_strength;
+
+    void main() {
+        //Bend the image outwards like the glass of a crt
+        vec2 centred = v_uv * 2.0 - 1.0;
+        centred += centred * centred.yx * centred.yx * curvature;
+        vec2 uv = centred * 0.5 + 0.5;
+        if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) {
+            colour_out = vec4(0.0, 0.0, 0.0, 1.0);
+            return;
+        }
+
+        float scanline = 1.0 - scanline_strength * (0.5 + 0.5 * sin(uv.y * resolution.y * 3.14159));
+        colour_out = vec4(texture(tex, uv).rgb * scanline, 1.0);
+    }
+"#;
+
+///An effect applied to the whole frame after the scene has been drawn
+///Effects are added to a window with Window::add_post_effect, and are applied in order
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PostEffect {
+    ///Makes bright areas glow
+    ///threshold is the brightness from 0 to 1 that starts glowing, and radius is the size of the glow in pixels
+    Bloom { threshold: f32, intensity: f32, radius: f32 },
+    ///Darkens the edges of the frame
+    ///radius is the distance from the centre where darkening starts, where the edge of the frame is 0.5
+    Vignette { strength: f32, radius: f32 },
+    ///Adjusts the colours of the frame, where 1.0 for everything makes no change
+    ColourGrade { brightness: f32, contrast: f32, saturation: f32, tint: [f32; 3] },
+    ///Makes the frame look like an old crt screen, with a curved image and scanlines
+    Crt { curvature: f32, scanline_strength: f32 },
+}
+
+impl PostEffect {
+    ///Number of different effects, for storing one program for each
+    const COUNT: usize = 4;
+
+    fn index(&self) -> usize {
+        match self {
+            PostEffect::Bloom { .. } => 0,
+            PostEffect::Vignette { .. } => 1,
+            PostEffect::ColourGrade { .. } => 2,
+            PostEffect::Crt { .. } => 3,
+        }
+    }
+
+    fn get_fragment_shader(&self) -> &'static str {
+        match self {
+            PostEffect::Bloom { .. } => BLOOM_FRAGMENT_SHADER,
+            PostEffect::Vignette { .. } => VIGNETTE_FRAGMENT_SHADER,
+            PostEffect::ColourGrade { .. } => COLOUR_GRADE_FRAGMENT_SHADER,
+            PostEffect::Crt { .. } => CRT_FRAGMENT_SHADER,
+        }
+    }
+
+    fn get_uniforms<'a>(&self, texture: Rc<SrgbTexture2d>, resolution: (u32, u32)) -> ShaderUniforms<'a> {
+        let float = |name: &str, value: f32| (name.to_string(), UniformType::Immediate(UniformValue::Float(value)));
+
+        let mut uniforms = vec![
+            ("tex".to_string(), UniformType::Texture(texture)),
+            ("resolution".to_string(), UniformType::Immediate(UniformValue::Vec2([resolution.0 as f32, resolution.1 as f32]))),
+        ];
+        match *self {
+            PostEffect::Bloom { threshold, intensity, radius } => uniforms.extend([
+                float("threshold", threshold),
+                float("intensity", intensity),
+                float("radius", radius),
+            ]),
+            PostEffect::Vignette { strength, radius } => uniforms.extend([
+                float("strength", strength),
+                float("radius", radius),
+            ]),
+            PostEffect::ColourGrade { brightness, contrast, saturation, tint } => uniforms.extend([
+                float("brightness", brightness),
+                float("contrast", contrast),
+                float("saturation", saturation),
+                ("tint".to_string(), UniformType::Immediate(UniformValue::Vec3(tint))),
+            ]),
+            PostEffect::Crt { curvature, scanline_strength } => uniforms.extend([
+                float("curvature", curvature),
+                float("scanline_strength", scanline_strength),
+            ]),
+        }
+        ShaderUniforms(uniforms)
+    }
+}
+
+///Draws the scene into a texture then applies each effect in turn, with the last drawing to the window
+#[derive(Default)]
+pub(crate) struct PostProcessing {
+    pub(crate) effects: Vec<PostEffect>,
+
+    programs: [Option<Program>; PostEffect::COUNT],
+    vertices: Option<VertexBuffer<shaders::ClearVertex>>,
+    indices: Option<IndexBuffer<u32>>,
+    ///Two textures that effects read from and write to in turn
+    buffers: Vec<Rc<SrgbTexture2d>>,
+    depth: Option<DepthRenderBuffer>,
+}
+
+impl Debug for PostProcessing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PostProcessing").field("effects", &self.effects).finish()
+    }
+}
+
+impl PostProcessing {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    ///Drops any gpu assets so that they are recreated on the next render
+    pub(crate) fn invalidate_assets(&mut self) {
+        self.programs = Default::default();
+        self.vertices = None;
+        self.indices = None;
+        self.buffers.clear();
+        self.depth = None;
+    }
+
+    ///Makes sure the buffers are the same size as the frame
+    fn create_assets(&mut self, display: &Display, (width, height): (u32, u32)) -> Result<(), AssetCreationError> {
+        if self.vertices.is_none() {
+            self.vertices = Some(VertexBuffer::new(display, &shaders::CLEAR_VERTICES)?);
+            self.indices = Some(IndexBuffer::new(display, glium::index::PrimitiveType::TrianglesList, &shaders::CLEAR_INDICES)?);
+        }
+
+        if self.buffers.first().map(|b| b.dimensions()) != Some((width, height)) {
+            self.buffers = (0..2).map(|_| SrgbTexture2d::empty(display, width, height).map(Rc::new)).collect::<Result<_, _>>()?;
+            self.depth = Some(DepthRenderBuffer::new(display, DepthFormat::I24, width, height)?);
+        }
+
+        for effect in self.effects.iter() {
+            let program = &mut self.programs[effect.index()];
+            if program.is_none() {
+                *program = Some(Program::from_source(display, POST_VERTEX_SHADER, effect.get_fragment_shader(), None)?);
+            }
+        }
+        Ok(())
+    }
+
+    ///Calls draw_scene to draw into a texture, then applies the effects and draws the result to frame
+    ///Nothing is drawn to a surface with no pixels, such as a minimised window
+    pub(crate) fn render<S: Surface, F: FnOnce(&mut SimpleFrameBuffer) -> Result<(), RenderError>>(&mut self, frame: &mut S, display: &Display, draw_scene: F) -> Result<(), RenderError> {
+        let dimensions = frame.get_dimensions();
+        if dimensions.0 == 0 || dimensions.1 == 0 {
+            return Ok(());
+        }
+        self.create_assets(display, dimensions)?;
+
+        //Always set once create_assets has succeeded
+        let (Some(vertices), Some(indices), Some(depth)) = (&self.vertices, &self.indices, &self.depth) else {
+            return Ok(());
+        };
+
+        {
+            let mut surface = SimpleFrameBuffer::with_depth_buffer(display, &*self.buffers[0], depth)?;
+            surface.clear_color_and_depth((0.0, 0.0, 0.0, 1.0), 1.0);
+            draw_scene(&mut surface)?;
+        }
+
+        let last = self.effects.len() - 1;
+        for (i, effect) in self.effects.iter().enumerate() {
+            let Some(program) = &self.programs[effect.index()] else {
+                continue;
+            };
+            let uniforms = effect.get_uniforms(self.buffers[i % 2].clone(), dimensions);
+
+            if i == last {
+                frame.draw(vertices, indices, program, &uniforms, &Default::default())?;
+            }
+            else {
+                let mut surface = SimpleFrameBuffer::new(display, &*self.buffers[(i + 1) % 2])?;
+                surface.draw(vertices, indices, program, &uniforms, &Default::default())?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -2,22 +2,40 @@ use std::fmt::Debug;
 use std::mem::take;
 use std::mem::replace;
 
-use glium::Frame;
+use glium::Surface;
 
 use crate::Renderable;
 use crate::Camera;
+use crate::RenderStats;
 use crate::Transform;
 use crate::Window;
 use crate::GameObject;
+use crate::Handle;
+use crate::MeshData;
+use crate::Frame;
+use crate::{World, Entity, System};
+use crate::Viewport;
+use crate::shaders::RenderError;
 
+///Callbacks for a scene, which are called in this order over the scene's life:
+///on_load when the scene is created, on_insert when it is added to a window's scene stack,
+///on_tick every frame it isn't paused, on_remove when it leaves the stack after any transition out has finished,
+///then on_unload when it is dropped, followed by on_destroy on each of its objects
 pub trait SceneCallback: Debug {
     fn on_load(&mut self, _scene: &mut Scene){}
     fn on_unload(&mut self, _scene: &mut Scene){}
 
+    ///Called before the scene is first ticked or drawn, so that it can add objects that need the window's assets
+    ///When a scene replaces another, the new scene is inserted before the old one is removed
     fn on_insert(&mut self, _scene: &mut Scene, _window: &mut Window){}
+    ///Called when the scene leaves the window, including when the window closes
+    ///Scenes are removed from the top of the stack down
     fn on_remove(&mut self, _scene: &mut Scene, _window: &mut Window){}
 
     fn on_tick(&mut self, _scene: &mut Scene, _frame: &crate::Frame) {}
+
+    ///Called when the window the scene is in changes size, after frame.display has been updated
+    fn on_resize(&mut self, _scene: &mut Scene, _frame: &crate::Frame) {}
 }
 
 #[derive(Debug)]
@@ -28,24 +46,39 @@ impl SceneCallback for SceneCallbackDefault {}
 pub struct Scene {
     callbacks: Option<Box<dyn SceneCallback>>,
     pub main_camera: Renderable,
+    ///Cameras that draw into textures, rendered before the main camera
+    offscreen_cameras: Vec<Camera>,
+    ///Paused scenes are drawn but not ticked
+    paused: bool,
+    ///Hidden scenes are ticked but not drawn
+    visible: bool,
 
-    pub(crate) objects: Vec<GameObject>,
+    ///Holds the scene's GameObjects as components of entities, along with any other components
+    world: World,
+    systems: Systems,
+}
+
+///Error from Scene::add_offscreen_camera when the camera has no target to draw into, holding the camera that wasn't added
+#[derive(Debug)]
+pub struct NoCameraTarget(pub Box<Camera>);
+
+///Systems run by a scene every frame, in the order they were added
+#[derive(Default)]
+struct Systems(Vec<Box<dyn System>>);
+
+impl Debug for Systems {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} systems", self.0.len())
+    }
 }
 
 impl Scene {
+    ///Removes the scene from a window, then drops it
     pub(crate) fn remove (mut self, window: &mut Window) {
         if let Some(mut callbacks) = take(&mut self.callbacks) {
             callbacks.on_remove(&mut self, window);
             self.callbacks = Some(callbacks);
         }
-
-        //TODO: free resources, call unload callback on gameobjects
-        for object in &mut self.objects {
-            if let Some(mut callbacks) = take(&mut object.callbacks) {
-                callbacks.on_destroy(object);
-                object.callbacks = Some(callbacks);
-            }
-        }
     }
 
     ///Insert the scene into a window
@@ -60,53 +93,203 @@ impl Scene {
     pub fn new(mut callbacks: Box<dyn SceneCallback>, camera: Renderable) -> Self {
         let mut scene = Scene {
             callbacks: None,
-            objects: Vec::new(),
+            world: World::new(),
+            systems: Default::default(),
             main_camera: camera,
+            offscreen_cameras: Vec::new(),
+            paused: false,
+            visible: true,
         };
         callbacks.on_load(&mut scene);
         scene.callbacks = Some(callbacks);
         scene
     }
 
-    pub(crate) fn render(&mut self, frame: &mut Frame, window: &Window) {
+    ///Stops the scene and its objects from ticking while it stays drawn, such as under a pause menu
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    ///Stops the scene from being drawn while it keeps ticking, such as when a scene above covers it
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    ///Adds a camera that draws into a texture before the main camera draws
+    ///Returns the camera back in the error if it has no target set with Camera::set_target
+    pub fn add_offscreen_camera(&mut self, camera: Camera) -> Result<usize, NoCameraTarget> {
+        if camera.get_target().is_none() {
+            return Err(NoCameraTarget(Box::new(camera)));
+        }
+        self.offscreen_cameras.push(camera);
+        Ok(self.offscreen_cameras.len() - 1)
+    }
+
+    pub fn get_offscreen_camera(&mut self, index: usize) -> Option<&mut Camera> {
+        self.offscreen_cameras.get_mut(index)
+    }
+
+    ///Removes an offscreen camera, so it stops drawing into its texture
+    pub fn remove_offscreen_camera(&mut self, index: usize) -> Option<Camera> {
+        if index < self.offscreen_cameras.len() {
+            Some(self.offscreen_cameras.remove(index))
+        }
+        else {
+            None
+        }
+    }
+
+    pub(crate) fn render<S: Surface>(&mut self, frame: &mut S, window: &Window) -> Result<(), RenderError> {
+        let mut offscreen_cameras = take(&mut self.offscreen_cameras);
+        let result = offscreen_cameras.iter_mut().try_for_each(|camera| camera.render(frame, self, window, Viewport::FULL));
+        self.offscreen_cameras = offscreen_cameras;
+        result?;
+
+        let viewport = window.scaling_policy.get_viewport(frame.get_dimensions());
         let mut main_camera = replace(&mut self.main_camera, Renderable::Camera(Camera::new(Transform::origin(), 3.0)));
-        main_camera.render(frame, self, window, -1.0, 1.0, -1.0, 1.0);
+        let result = main_camera.render(frame, self, window, viewport);
         self.main_camera = main_camera;
+        result
     }
 
-    pub(crate) fn tick(&mut self, window: &Window) {
+    ///Gets the number of meshes drawn and culled by the scene's cameras in the last frame
+    pub fn get_render_stats(&self) -> RenderStats {
+        self.main_camera.get_render_stats()
+    }
 
-        if let Some(mut callbacks) = take(&mut self.callbacks) {
-            callbacks.on_tick(self, &window.frame);
-            self.callbacks = Some(callbacks);
+    ///Drops all gpu assets owned by the scene so that they are recreated on the next render
+    ///Used after the gl context has been lost
+    pub(crate) fn invalidate_assets(&mut self) {
+        self.main_camera.invalidate_assets();
+        for camera in self.offscreen_cameras.iter_mut() {
+            camera.invalidate_assets();
+        }
+        for object in self.objects_mut() {
+            for mesh in &mut object.meshes {
+                mesh.1.shader.invalidate_assets();
+            }
         }
+    }
 
-        for mut object in &mut self.objects {
-            if let Some(mut callbacks) = take(&mut object.callbacks) {
-                callbacks.on_tick(object, &window.frame);
-                object.callbacks = Some(callbacks);
+    ///Replaces the data of every mesh created from a reloaded asset
+    pub(crate) fn reload_mesh(&mut self, handle: Handle<MeshData>, data: &MeshData) {
+        for object in self.objects_mut() {
+            for mesh in &mut object.meshes {
+                if mesh.1.source == Some(handle) {
+                    mesh.1.vertices = data.vertices.clone();
+                    mesh.1.indices = data.indices.clone();
+                    mesh.1.recalculate_bounds();
+                }
             }
         }
     }
 
-    pub fn add_object(&mut self, mut object: GameObject) {
-        if let Some(mut callbacks) = take(&mut object.callbacks) {
-            callbacks.on_load(&mut object, self);
-            object.callbacks = Some(callbacks);
+    ///Tells the scene's callbacks that the window has changed size
+    pub(crate) fn resize(&mut self, frame: &Frame) {
+        if let Some(mut callbacks) = take(&mut self.callbacks) {
+            callbacks.on_resize(self, frame);
+            self.callbacks = Some(callbacks);
         }
-        self.objects.push(object);
+    }
+
+    ///Runs one frame of the scene: its on_tick, then its systems, then its objects' components and emitters
+    ///Windows tick their scenes every frame, but this can also be called without a window by using Frame::headless
+    pub fn tick(&mut self, frame: &Frame) {
+
+        if let Some(mut callbacks) = take(&mut self.callbacks) {
+            callbacks.on_tick(self, frame);
+            self.callbacks = Some(callbacks);
+        }
+
+        for system in self.systems.0.iter_mut() {
+            system.run(&mut self.world, frame);
+        }
+
+        for object in self.objects_mut() {
+            object.for_each_component(|component, object| component.on_tick(object, frame));
+            object.update_emitters(frame.time.delta_time.as_secs_f64());
+        }
+    }
+
+    ///Adds an object to the scene, calling its components' on_load
+    ///Returns the entity the object is stored on in the scene's World, which other components can be added to
+    pub fn add_object(&mut self, mut object: GameObject) -> Entity {
+        let start = object.meshes.len();
+        object.for_each_component(|component, object| component.on_load(object, self));
+        object.callback_meshes = start..object.meshes.len();
+
+        let entity = self.world.spawn();
+        self.world.storage_mut::<GameObject>().insert(entity, object);
+        entity
+    }
+
+    pub fn get_object(&self, entity: Entity) -> Option<&GameObject> {
+        self.world.get::<GameObject>(entity)
+    }
+
+    pub fn get_object_mut(&mut self, entity: Entity) -> Option<&mut GameObject> {
+        self.world.get_mut::<GameObject>(entity)
+    }
+
+    ///Removes an object from the scene, calling its components' on_unload
+    ///The object's entity is despawned, dropping any other components on it
+    ///The other objects keep their order
+    pub fn remove_object(&mut self, entity: Entity) -> Option<GameObject> {
+        let mut object = self.world.remove_ordered::<GameObject>(entity)?;
+        object.for_each_component(|component, object| component.on_unload(object, self));
+        self.world.despawn(entity);
+        Some(object)
     }
 
     pub fn alter_object_by_name(&mut self, name: String, closure: Box<dyn FnOnce(&mut GameObject)>) -> Option<()> {
-        for mut object in &mut self.objects {
+        for object in self.objects_mut() {
             if object.name == name {
-                closure(&mut object);
+                closure(object);
                 return Some(());
             }
         }
         None
     }
 
+    ///Gets the scene's objects in the order they were added, which is the order they are ticked
+    ///Removing a GameObject through the World rather than remove_object moves the last object into its place
+    pub(crate) fn objects(&self) -> &[GameObject] {
+        self.world.storage::<GameObject>().map_or(&[], |s| s.components())
+    }
+
+    pub(crate) fn objects_mut(&mut self) -> &mut [GameObject] {
+        self.world.storage_mut::<GameObject>().components_mut()
+    }
+
+    ///Gets the entity of each object returned by objects()
+    pub(crate) fn object_entities(&self) -> &[Entity] {
+        self.world.storage::<GameObject>().map_or(&[], |s| s.entities())
+    }
+
+    ///Gets the entities and components in the scene, including an entity for each GameObject
+    pub fn get_world(&self) -> &World {
+        &self.world
+    }
+
+    ///Gets the scene's World mutably, to add entities and components that systems work on
+    pub fn get_world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    ///Adds a system that runs over the scene's World every frame, after the scene's on_tick
+    ///Closures taking the World and the Frame can be used as systems
+    pub fn add_system<S: System + 'static>(&mut self, system: S) {
+        self.systems.0.push(Box::new(system));
+    }
+
 }
 
 impl Drop for Scene {
@@ -116,11 +299,8 @@ impl Drop for Scene {
             self.callbacks = Some(callbacks);
         }
 
-        for object in &mut self.objects {
-            if let Some(mut callbacks) = take(&mut object.callbacks) {
-                callbacks.on_destroy(object);
-                object.callbacks = Some(callbacks);
-            }
+        for object in self.objects_mut() {
+            object.for_each_component(|component, object| component.on_destroy(object));
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,853 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Debug;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Assets, Camera, ClearMode, GameObject, GameObjectCallback, Handle, MeshData, Mesh, Renderable, Scene, SceneCallback, Transform, Window};
+use crate::shaders::{BlendMode, Shader, Texture, TextureOnly2D, TextureOnly3D, Unshaded2D, Unshaded3D};
+
+///Represents an error that can occur while loading a scene file
+#[derive(Debug)]
+pub enum SceneLoadError {
+    Io(std::io::Error),
+    ///The file isn't valid, or doesn't match the layout of a scene
+    Parse(serde_json::Error),
+    ///An object uses a callback type that hasn't been registered
+    UnknownCallback(String),
+    ///An object uses a prefab that isn't in the scene file or the registry
+    UnknownPrefab(String),
+    ///A prefab contains an instance of itself, directly or through its children
+    RecursivePrefab(String),
+    ///A callback factory couldn't use an object's parameters
+    InvalidParameters { type_name: String, message: String },
+    ///A texture or mesh failed to load
+    Asset(String),
+}
+
+///A scene's main camera in a scene file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraDescription {
+    #[serde(default)]
+    pub transform: Transform,
+    pub fov: f32,
+    ///Colour the camera fills its view with before drawing, or None to draw over what is already there
+    #[serde(default)]
+    pub clear_colour: Option<[f32; 4]>,
+}
+
+///The shape of a mesh in a scene file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MeshShape {
+    Cube,
+    Plane { double_sided: bool },
+    ///An obj file, loaded through the window's assets
+    Obj(String),
+}
+
+///A shader and its settings in a scene file
+///Textures are asset paths, loaded through the window's assets
+///Blend modes can be left out to use the shader's default
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "shader")]
+pub enum MaterialDescription {
+    Unshaded2D {
+        colour: [f32; 4],
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        blend_mode: Option<BlendMode>,
+    },
+    Unshaded3D {
+        colour: [f32; 4],
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        blend_mode: Option<BlendMode>,
+    },
+    TextureOnly2D {
+        texture: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        blend_mode: Option<BlendMode>,
+    },
+    TextureOnly3D {
+        texture: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        blend_mode: Option<BlendMode>,
+    },
+}
+
+///A mesh of a GameObject in a scene file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MeshDescription {
+    ///Transform of the mesh relative to its GameObject
+    #[serde(default)]
+    pub transform: Transform,
+    pub shape: MeshShape,
+    pub material: MaterialDescription,
+    #[serde(default = "default_visible")]
+    pub visible: bool,
+    #[serde(default)]
+    pub sort_order: i32,
+}
+
+///A GameObject's callback in a scene file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CallbackDescription {
+    ///Name the callback's factory was registered with in a CallbackRegistry
+    #[serde(rename = "type")]
+    pub type_name: String,
+    ///Settings passed to the factory, such as which wheel of a fruit machine the object is
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub parameters: serde_json::Value,
+}
+
+impl CallbackDescription {
+    pub fn new(type_name: &str, parameters: serde_json::Value) -> Self {
+        CallbackDescription { type_name: type_name.to_string(), parameters }
+    }
+}
+
+///A GameObject in a scene file
+///
+///An object can be an instance of a prefab, which it adds to:
+///- its transform is applied after the prefab's
+///- its meshes are added after the prefab's
+///- its callback replaces the prefab's
+///- its parameters replace the matching parameters of the callback
+///- its components are added after the prefab's
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObjectDescription {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefab: Option<String>,
+    #[serde(default)]
+    pub transform: Transform,
+    #[serde(default = "default_visible")]
+    pub visible: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub meshes: Vec<MeshDescription>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub callback: Option<CallbackDescription>,
+    ///More behaviours, added after the callback
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub components: Vec<CallbackDescription>,
+    ///Replaces the matching parameters of the callback, such as one from the prefab
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub parameters: serde_json::Value,
+}
+
+impl ObjectDescription {
+    pub fn new(name: &str, transform: Transform) -> Self {
+        ObjectDescription {
+            name: name.to_string(),
+            prefab: None,
+            transform,
+            visible: true,
+            meshes: Vec::new(),
+            callback: None,
+            components: Vec::new(),
+            parameters: serde_json::Value::Null,
+        }
+    }
+
+    ///Creates an instance of a prefab
+    pub fn instance(prefab: &str, name: &str, transform: Transform) -> Self {
+        ObjectDescription { prefab: Some(prefab.to_string()), ..ObjectDescription::new(name, transform) }
+    }
+
+    pub fn with_mesh(mut self, mesh: MeshDescription) -> Self {
+        self.meshes.push(mesh);
+        self
+    }
+
+    pub fn with_callback(mut self, callback: CallbackDescription) -> Self {
+        self.callback = Some(callback);
+        self
+    }
+
+    pub fn with_component(mut self, component: CallbackDescription) -> Self {
+        self.components.push(component);
+        self
+    }
+
+    pub fn with_parameters(mut self, parameters: serde_json::Value) -> Self {
+        self.parameters = parameters;
+        self
+    }
+
+    ///Adds the object to a scene, along with the children of its prefab
+    ///Textures and meshes are loaded through the window's assets, blocking until they are ready
+    pub fn add_to(&self, scene: &mut Scene, registry: &CallbackRegistry, window: &mut Window) -> Result<(), SceneLoadError> {
+        SceneDescription { camera: None, prefabs: BTreeMap::new(), objects: vec![self.clone()] }.add_to(scene, registry, window)
+    }
+}
+
+///A reusable template for GameObjects, instantiated by objects in a scene file or in code
+///Children are created as separate GameObjects named "instance/child", with their transforms applied before the instance's
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Prefab {
+    #[serde(default)]
+    pub transform: Transform,
+    #[serde(default = "default_visible")]
+    pub visible: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub meshes: Vec<MeshDescription>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub callback: Option<CallbackDescription>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub components: Vec<CallbackDescription>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<ObjectDescription>,
+}
+
+impl Default for Prefab {
+    fn default() -> Self {
+        Prefab {
+            transform: Transform::origin(),
+            visible: true,
+            meshes: Vec::new(),
+            callback: None,
+            components: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+}
+
+impl Prefab {
+    pub fn new() -> Self {
+        Prefab::default()
+    }
+
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    pub fn with_mesh(mut self, mesh: MeshDescription) -> Self {
+        self.meshes.push(mesh);
+        self
+    }
+
+    pub fn with_callback(mut self, callback: CallbackDescription) -> Self {
+        self.callback = Some(callback);
+        self
+    }
+
+    pub fn with_component(mut self, component: CallbackDescription) -> Self {
+        self.components.push(component);
+        self
+    }
+
+    pub fn with_child(mut self, child: ObjectDescription) -> Self {
+        self.children.push(child);
+        self
+    }
+}
+
+///The contents of a scene file, describing a camera and the objects in a scene
+///
+///Scene files are json. Objects list their meshes and the registered types of their callback and components,
+///and anything left out takes the same default as when building the object in code.
+///Particle emitters, instances and meshes made from raw vertex data aren't saved.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct SceneDescription {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub camera: Option<CameraDescription>,
+    ///Prefabs that objects in the file can be instances of, which take priority over prefabs in the registry
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub prefabs: BTreeMap<String, Prefab>,
+    #[serde(default)]
+    pub objects: Vec<ObjectDescription>,
+}
+
+fn default_visible() -> bool {
+    true
+}
+
+///Creates a GameObject's callback from the parameters in a scene file
+pub type CallbackFactory = Box<dyn Fn(&serde_json::Value) -> Result<Box<dyn GameObjectCallback>, String>>;
+
+///Maps type names used in scene files to the factories that create their callbacks, along with prefabs defined in code
+///Factories are closures, so they can capture shared state and loaded assets
+#[derive(Default)]
+pub struct CallbackRegistry {
+    factories: HashMap<String, CallbackFactory>,
+    prefabs: HashMap<String, Prefab>,
+}
+
+impl Debug for CallbackRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CallbackRegistry")
+            .field("factories", &self.factories.keys())
+            .field("prefabs", &self.prefabs.keys())
+            .finish()
+    }
+}
+
+impl CallbackRegistry {
+    pub fn new() -> Self {
+        CallbackRegistry::default()
+    }
+
+    ///Registers a factory, replacing any factory already registered with the same name
+    ///The factory returns an error message if it can't use the parameters
+    pub fn register<F>(&mut self, type_name: &str, factory: F)
+    where F: Fn(&serde_json::Value) -> Result<Box<dyn GameObjectCallback>, String> + 'static {
+        self.factories.insert(type_name.to_string(), Box::new(factory));
+    }
+
+    pub fn contains(&self, type_name: &str) -> bool {
+        self.factories.contains_key(type_name)
+    }
+
+    ///Registers a prefab that objects can be instances of, replacing any prefab already registered with the same name
+    pub fn register_prefab(&mut self, name: &str, prefab: Prefab) {
+        self.prefabs.insert(name.to_string(), prefab);
+    }
+
+    pub fn get_prefab(&self, name: &str) -> Option<&Prefab> {
+        self.prefabs.get(name)
+    }
+
+    ///Creates a callback with its registered factory
+    pub fn create(&self, description: &CallbackDescription) -> Result<Box<dyn GameObjectCallback>, SceneLoadError> {
+        let factory = match self.factories.get(&description.type_name) {
+            Some(f) => f,
+            None => return Err(SceneLoadError::UnknownCallback(description.type_name.clone()))
+        };
+        factory(&description.parameters).map_err(|message| SceneLoadError::InvalidParameters {
+            type_name: description.type_name.clone(),
+            message,
+        })
+    }
+}
+
+impl SceneDescription {
+    pub fn from_json(text: &str) -> Result<Self, SceneLoadError> {
+        serde_json::from_str(text).map_err(SceneLoadError::Parse)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    ///Reads a scene file through the window's assets, so it can come from a mounted pack
+    pub fn open(path: &str, window: &Window) -> Result<Self, SceneLoadError> {
+        let bytes = window.assets().read(path).map_err(SceneLoadError::Io)?;
+        let text = String::from_utf8_lossy(&bytes);
+        SceneDescription::from_json(&text)
+    }
+
+    ///Creates a scene with the described camera and objects
+    ///A scene without a camera gets Camera::new(Transform::origin(), 3.0)
+    pub fn build(&self, callbacks: Box<dyn SceneCallback>, registry: &CallbackRegistry, window: &mut Window) -> Result<Scene, SceneLoadError> {
+        let camera = match &self.camera {
+            Some(c) => c.to_camera(),
+            None => Camera::new(Transform::origin(), 3.0)
+        };
+        let mut scene = Scene::new(callbacks, Renderable::Camera(camera));
+        self.add_to(&mut scene, registry, window)?;
+        Ok(scene)
+    }
+
+    ///Adds the described objects to an existing scene, ignoring the camera
+    ///Textures and meshes are loaded through the window's assets, blocking until they are ready
+    pub fn add_to(&self, scene: &mut Scene, registry: &CallbackRegistry, window: &mut Window) -> Result<(), SceneLoadError> {
+        let (textures, meshes) = self.asset_paths(registry);
+        for path in textures {
+            window.assets_mut().load_texture(&path);
+        }
+        for path in meshes {
+            window.assets_mut().load_mesh(&path);
+        }
+        window.wait_for_assets();
+
+        for object in self.create_objects(registry, window.assets())? {
+            scene.add_object(object);
+        }
+        Ok(())
+    }
+
+    ///Creates the described objects, with every texture and mesh they use already loaded in assets
+    ///Instances of prefabs are followed by their prefab's children
+    pub(crate) fn create_objects(&self, registry: &CallbackRegistry, assets: &Assets) -> Result<Vec<GameObject>, SceneLoadError> {
+        let builder = ObjectBuilder { prefabs: &self.prefabs, registry, assets };
+        let mut objects = Vec::new();
+        for object in &self.objects {
+            builder.create(object, None, &mut Vec::new(), &mut objects)?;
+        }
+        Ok(objects)
+    }
+
+    ///Gets the paths of the textures and obj files used by the objects and every prefab they could use
+    fn asset_paths(&self, registry: &CallbackRegistry) -> (Vec<String>, Vec<String>) {
+        let prefabs = self.prefabs.values().chain(registry.prefabs.values());
+        let meshes = self.objects.iter().chain(prefabs.clone().flat_map(|p| &p.children)).flat_map(|o| &o.meshes)
+            .chain(prefabs.flat_map(|p| &p.meshes));
+
+        let (mut textures, mut objs) = (Vec::new(), Vec::new());
+        for mesh in meshes {
+            if let MaterialDescription::TextureOnly2D { texture, .. } | MaterialDescription::TextureOnly3D { texture, .. } = &mesh.material {
+                textures.push(texture.clone());
+            }
+            if let MeshShape::Obj(path) = &mesh.shape {
+                objs.push(path.clone());
+            }
+        }
+        (textures, objs)
+    }
+}
+
+impl CameraDescription {
+    fn to_camera(&self) -> Camera {
+        let mut camera = Camera::new(self.transform, self.fov);
+        camera.set_clear_colour(self.clear_colour);
+        camera
+    }
+}
+
+///Creates GameObjects from descriptions, looking up prefabs in the scene file then the registry
+struct ObjectBuilder<'a> {
+    prefabs: &'a BTreeMap<String, Prefab>,
+    registry: &'a CallbackRegistry,
+    assets: &'a Assets,
+}
+
+impl ObjectBuilder<'_> {
+    ///Creates an object and the children of its prefab
+    ///parent is the name and transform of the instance whose prefab the object is a child of
+    ///path is the prefabs currently being created, to catch prefabs that contain themselves
+    fn create(&self, description: &ObjectDescription, parent: Option<(&str, Transform)>, path: &mut Vec<String>, out: &mut Vec<GameObject>) -> Result<(), SceneLoadError> {
+        let default_prefab = Prefab::default();
+        let prefab = match &description.prefab {
+            Some(name) => {
+                if path.contains(name) {
+                    return Err(SceneLoadError::RecursivePrefab(name.clone()));
+                }
+                self.prefabs.get(name).or_else(|| self.registry.get_prefab(name)).ok_or_else(|| SceneLoadError::UnknownPrefab(name.clone()))?
+            },
+            None => &default_prefab
+        };
+
+        //Combining with the origin would add rounding errors to objects that aren't instances
+        let mut transform = match description.prefab {
+            Some(_) => prefab.transform * description.transform,
+            None => description.transform
+        };
+        let mut name = description.name.clone();
+        if let Some((parent_name, parent_transform)) = parent {
+            transform = transform * parent_transform;
+            name = format!("{}/{}", parent_name, name);
+        }
+
+        let callback = match description.callback.as_ref().or(prefab.callback.as_ref()) {
+            Some(c) => {
+                let mut c = c.clone();
+                merge_parameters(&mut c.parameters, &description.parameters);
+                Some(self.registry.create(&c)?)
+            },
+            None => None
+        };
+
+        let mut object = GameObject::new(callback, name.clone(), transform);
+        for component in prefab.components.iter().chain(&description.components) {
+            object.add_component(self.registry.create(component)?);
+        }
+        object.visible = prefab.visible && description.visible;
+        for mesh in prefab.meshes.iter().chain(&description.meshes) {
+            object.meshes.push((mesh.transform, mesh.create(self.assets)?));
+        }
+        out.push(object);
+
+        if let Some(prefab_name) = &description.prefab {
+            path.push(prefab_name.clone());
+            for child in &prefab.children {
+                self.create(child, Some((&name, transform)), path, out)?;
+            }
+            path.pop();
+        }
+        Ok(())
+    }
+}
+
+///Replaces parameters with overrides, key by key when both are maps
+fn merge_parameters(parameters: &mut serde_json::Value, overrides: &serde_json::Value) {
+    match (parameters, overrides) {
+        (_, serde_json::Value::Null) => (),
+        (serde_json::Value::Object(base), serde_json::Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                base.insert(key.clone(), value.clone());
+            }
+        },
+        (base, overrides) => *base = overrides.clone(),
+    }
+}
+
+impl MeshDescription {
+    fn create(&self, assets: &Assets) -> Result<Mesh, SceneLoadError> {
+        let shader = self.material.create(assets)?;
+        let mut mesh = match &self.shape {
+            MeshShape::Cube => Mesh::cube(shader),
+            MeshShape::Plane { double_sided } => Mesh::plane(*double_sided, shader),
+            MeshShape::Obj(path) => {
+                let handle: Handle<MeshData> = assets.find(path).ok_or_else(|| SceneLoadError::Asset(path.clone()))?;
+                Mesh::from_asset(assets, handle, shader).ok_or_else(|| SceneLoadError::Asset(path.clone()))?
+            }
+        };
+        mesh.visible = self.visible;
+        mesh.sort_order = self.sort_order;
+        Ok(mesh)
+    }
+}
+
+impl MaterialDescription {
+    fn create(&self, assets: &Assets) -> Result<Box<dyn Shader>, SceneLoadError> {
+        let texture = |path: &String| -> Result<Texture, SceneLoadError> {
+            assets.find(path).and_then(|h| assets.get(h)).cloned().ok_or_else(|| SceneLoadError::Asset(path.clone()))
+        };
+        Ok(match self {
+            MaterialDescription::Unshaded2D { colour, blend_mode } => {
+                let mut shader = Unshaded2D::new(*colour);
+                if let Some(b) = blend_mode { shader.set_blend_mode(*b); }
+                Box::new(shader)
+            },
+            MaterialDescription::Unshaded3D { colour, blend_mode } => {
+                let mut shader = Unshaded3D::new(*colour);
+                if let Some(b) = blend_mode { shader.set_blend_mode(*b); }
+                Box::new(shader)
+            },
+            MaterialDescription::TextureOnly2D { texture: path, blend_mode } => {
+                let mut shader = TextureOnly2D::new(texture(path)?);
+                if let Some(b) = blend_mode { shader.set_blend_mode(*b); }
+                Box::new(shader)
+            },
+            MaterialDescription::TextureOnly3D { texture: path, blend_mode } => {
+                let mut shader = TextureOnly3D::new(texture(path)?);
+                if let Some(b) = blend_mode { shader.set_blend_mode(*b); }
+                Box::new(shader)
+            },
+        })
+    }
+}
+
+impl Scene {
+    ///Describes the scene's camera and objects so they can be saved to a scene file
+    ///Objects created from prefabs are saved as separate objects, without their prefabs
+    ///Meshes that can't be saved are left out, along with meshes added by callbacks' on_load
+    ///and meshes drawing particle emitters. The camera is left out if the main camera is a SplitView.
+    pub fn describe(&self, assets: &Assets) -> SceneDescription {
+        let camera = match &self.main_camera {
+            Renderable::Camera(c) => Some(CameraDescription {
+                transform: c.transform,
+                fov: c.fov,
+                clear_colour: match c.get_clear_mode() {
+                    ClearMode::Colour(colour) => Some(*colour),
+                    _ => None
+                },
+            }),
+            Renderable::SplitView(_) => None
+        };
+        SceneDescription {
+            camera,
+            prefabs: BTreeMap::new(),
+            objects: self.objects().iter().map(|o| o.describe(assets)).collect(),
+        }
+    }
+}
+
+impl GameObject {
+    ///Describes the object for saving in a scene file, as Scene::describe does
+    pub fn describe(&self, assets: &Assets) -> ObjectDescription {
+        let emitter_meshes: Vec<usize> = self.emitters.iter().filter_map(|e| e.mesh).collect();
+        let meshes = self.meshes.iter().enumerate()
+            .filter(|(i, _)| !self.callback_meshes.contains(i) && !emitter_meshes.contains(i))
+            .filter_map(|(_, (transform, mesh))| Some(MeshDescription {
+                transform: *transform,
+                shape: match (&mesh.shape, mesh.source) {
+                    (_, Some(handle)) => MeshShape::Obj(assets.get_name(handle)?),
+                    (Some(shape), None) => shape.clone(),
+                    (None, None) => return None
+                },
+                material: mesh.shader.describe(assets)?,
+                visible: mesh.visible,
+                sort_order: mesh.sort_order,
+            }))
+            .collect();
+
+        //The first component that can be saved becomes the callback
+        let mut components = self.components.iter().flatten().filter_map(|c| c.describe());
+        ObjectDescription {
+            name: self.name.clone(),
+            prefab: None,
+            transform: self.transform,
+            visible: self.visible,
+            meshes,
+            callback: components.next(),
+            components: components.collect(),
+            parameters: serde_json::Value::Null,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{Quat, SceneCallbackDefault, Vec3};
+
+    ///A callback that saves the speed it was created with
+    #[derive(Debug)]
+    struct Spinner {
+        speed: f64,
+    }
+
+    impl GameObjectCallback for Spinner {
+        fn on_load(&mut self, object: &mut GameObject, _scene: &mut Scene) {
+            object.meshes.push((Transform::origin(), Mesh::cube(Box::new(Unshaded3D::new([1.0; 4])))));
+        }
+
+        fn describe(&self) -> Option<CallbackDescription> {
+            Some(CallbackDescription::new("Spinner", serde_json::json!({ "speed": self.speed })))
+        }
+    }
+
+    ///A component that copies the speed of the object's Spinner when it loads
+    #[derive(Debug)]
+    struct Follower {
+        speed: Option<f64>,
+    }
+
+    impl GameObjectCallback for Follower {
+        fn on_load(&mut self, object: &mut GameObject, _scene: &mut Scene) {
+            self.speed = object.get_component::<Spinner>().map(|s| s.speed);
+        }
+
+        fn describe(&self) -> Option<CallbackDescription> {
+            Some(CallbackDescription::new("Follower", serde_json::Value::Null))
+        }
+    }
+
+    fn registry() -> CallbackRegistry {
+        let mut registry = CallbackRegistry::new();
+        registry.register("Spinner", |parameters| {
+            let speed = parameters["speed"].as_f64().ok_or("speed should be a number")?;
+            Ok(Box::new(Spinner { speed }))
+        });
+        registry.register("Follower", |_| Ok(Box::new(Follower { speed: None })));
+        registry
+    }
+
+    fn description() -> SceneDescription {
+        SceneDescription {
+            camera: Some(CameraDescription {
+                transform: Transform::from_pos(0.0, 1.0, -5.0),
+                fov: 3.0,
+                clear_colour: Some([1.0, 1.0, 1.0, 0.0]),
+            }),
+            prefabs: BTreeMap::new(),
+            objects: vec![
+                ObjectDescription {
+                    name: "Box".to_string(),
+                    prefab: None,
+                    parameters: serde_json::Value::Null,
+                    transform: Transform::new(Vec3::new(1.0, 2.0, 3.0), Quat::from_euler(0.0, 0.5, 0.0), Vec3::new(2.0, 1.0, 1.0)),
+                    visible: true,
+                    meshes: vec![
+                        MeshDescription {
+                            transform: Transform::from_pos(0.0, 0.5, 0.0),
+                            shape: MeshShape::Cube,
+                            material: MaterialDescription::Unshaded3D { colour: [1.0, 0.0, 0.0, 1.0], blend_mode: None },
+                            visible: true,
+                            sort_order: 0,
+                        },
+                        MeshDescription {
+                            transform: Transform::origin(),
+                            shape: MeshShape::Plane { double_sided: true },
+                            material: MaterialDescription::Unshaded2D { colour: [0.0, 0.0, 0.0, 0.5], blend_mode: Some(BlendMode::Alpha) },
+                            visible: false,
+                            sort_order: 2,
+                        },
+                    ],
+                    callback: None,
+                    components: Vec::new(),
+                },
+                ObjectDescription::new("Spinner", Transform::from_pos(-1.0, 0.0, 0.0))
+                    .with_callback(CallbackDescription::new("Spinner", serde_json::json!({ "speed": 2.5 }))),
+            ],
+        }
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let description = description();
+        let text = description.to_json();
+        assert_eq!(SceneDescription::from_json(&text).unwrap(), description);
+    }
+
+    #[test]
+    fn scene_round_trip() {
+        let description = description();
+        let assets = Assets::new(PathBuf::new());
+        let mut scene = Scene::new(Box::new(SceneCallbackDefault {}), Renderable::Camera(description.camera.as_ref().unwrap().to_camera()));
+        for object in description.create_objects(&registry(), &assets).unwrap() {
+            scene.add_object(object);
+        }
+
+        //The spinner's cube comes from on_load, so isn't saved
+        assert_eq!(scene.objects()[1].meshes.len(), 1);
+        assert_eq!(scene.describe(&assets), description);
+    }
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let text = r#"{
+            "objects": [
+                { "name": "Empty" },
+                {
+                    "name": "Moved",
+                    "transform": { "position": [1, 2, 3] },
+                    "meshes": [{ "shape": "Cube", "material": { "shader": "Unshaded2D", "colour": [1, 1, 1, 1] } }]
+                }
+            ]
+        }"#;
+        let description = SceneDescription::from_json(text).unwrap();
+        assert_eq!(description.camera, None);
+        assert_eq!(description.objects[0].transform, Transform::origin());
+        assert!(description.objects[0].visible);
+        assert!(description.objects[0].meshes.is_empty());
+
+        assert_eq!(description.objects[1].transform, Transform::from_pos(1.0, 2.0, 3.0));
+        let mesh = &description.objects[1].meshes[0];
+        assert!(mesh.visible);
+        assert_eq!(mesh.material, MaterialDescription::Unshaded2D { colour: [1.0; 4], blend_mode: None });
+    }
+
+    #[test]
+    fn errors() {
+        let assets = Assets::new(PathBuf::new());
+        let mut description = description();
+        description.objects[1].callback = Some(CallbackDescription::new("Unknown", serde_json::Value::Null));
+        assert!(matches!(description.create_objects(&registry(), &assets), Err(SceneLoadError::UnknownCallback(t)) if t == "Unknown"));
+
+        description.objects[1].callback = Some(CallbackDescription::new("Spinner", serde_json::json!({ "speed": "fast" })));
+        assert!(matches!(description.create_objects(&registry(), &assets), Err(SceneLoadError::InvalidParameters { .. })));
+
+        description.objects[1].callback = None;
+        description.objects[1].meshes.push(MeshDescription {
+            transform: Transform::origin(),
+            shape: MeshShape::Plane { double_sided: false },
+            material: MaterialDescription::TextureOnly2D { texture: "missing.png".to_string(), blend_mode: None },
+            visible: true,
+            sort_order: 0,
+        });
+        assert!(matches!(description.create_objects(&registry(), &assets), Err(SceneLoadError::Asset(p)) if p == "missing.png"));
+
+        assert!(matches!(SceneDescription::from_json("{ \"objects\": 3 }"), Err(SceneLoadError::Parse(_))));
+    }
+
+    fn marker() -> Prefab {
+        Prefab::new()
+            .with_transform(Transform::from_scale(0.5, 0.5, 0.5))
+            .with_mesh(MeshDescription {
+                transform: Transform::origin(),
+                shape: MeshShape::Plane { double_sided: false },
+                material: MaterialDescription::Unshaded2D { colour: [1.0; 4], blend_mode: None },
+                visible: true,
+                sort_order: 0,
+            })
+            .with_callback(CallbackDescription::new("Spinner", serde_json::json!({ "speed": 1.0 })))
+            .with_child(ObjectDescription::new("Light", Transform::from_pos(0.0, 1.0, 0.0)))
+    }
+
+    #[test]
+    fn prefab_instances_with_overrides() {
+        let assets = Assets::new(PathBuf::new());
+        let mut description = SceneDescription::default();
+        description.prefabs.insert("Marker".to_string(), marker());
+        description.objects.push(ObjectDescription::instance("Marker", "A", Transform::from_pos(2.0, 0.0, 0.0))
+            .with_parameters(serde_json::json!({ "speed": 3.0 })));
+        description.objects.push(ObjectDescription::instance("Marker", "B", Transform::from_pos(-2.0, 0.0, 0.0))
+            .with_mesh(marker().meshes[0].clone()));
+
+        let objects = description.create_objects(&registry(), &assets).unwrap();
+        let names: Vec<_> = objects.iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(names, ["A", "A/Light", "B", "B/Light"]);
+
+        //The prefab's transform comes first, then the instance's, and children go before both
+        assert_eq!(objects[0].transform, Transform::new(Vec3::new(2.0, 0.0, 0.0), Quat::IDENTITY, Vec3::new(0.5, 0.5, 0.5)));
+        assert_eq!(objects[1].transform.get_position(), Vec3::new(2.0, 0.5, 0.0));
+
+        assert_eq!(objects[0].meshes.len(), 1);
+        assert_eq!(objects[2].meshes.len(), 2);
+        assert!(objects[1].meshes.is_empty());
+
+        let speed = |o: &GameObject| o.describe(&assets).callback.unwrap().parameters["speed"].as_f64().unwrap();
+        assert_eq!(speed(&objects[0]), 3.0);
+        assert_eq!(speed(&objects[2]), 1.0);
+    }
+
+    #[test]
+    fn prefabs_from_registry_and_file() {
+        let assets = Assets::new(PathBuf::new());
+        let mut registry = registry();
+        registry.register_prefab("Marker", marker());
+
+        let text = r#"{
+            "objects": [
+                { "name": "Registered", "prefab": "Marker" },
+                { "name": "Plain", "prefab": "Plain" }
+            ]
+        }"#;
+        let description = SceneDescription::from_json(text).unwrap();
+        assert!(matches!(description.create_objects(&registry, &assets), Err(SceneLoadError::UnknownPrefab(p)) if p == "Plain"));
+
+        //Prefabs in the file are used over prefabs in the registry
+        let text = r#"{
+            "prefabs": {
+                "Plain": { "transform": { "position": [0, 0, 1] } },
+                "Marker": { "visible": false }
+            },
+            "objects": [
+                { "name": "Overridden", "prefab": "Marker" },
+                { "name": "Plain", "prefab": "Plain", "transform": { "position": [1, 0, 0] } }
+            ]
+        }"#;
+        let objects = SceneDescription::from_json(text).unwrap().create_objects(&registry, &assets).unwrap();
+        assert_eq!(objects.len(), 2);
+        assert!(!objects[0].visible);
+        assert!(objects[0].meshes.is_empty());
+        assert_eq!(objects[1].transform.get_position(), Vec3::new(1.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn recursive_prefab() {
+        let assets = Assets::new(PathBuf::new());
+        let mut description = SceneDescription::default();
+        description.prefabs.insert("Outer".to_string(), Prefab::new().with_child(ObjectDescription::instance("Inner", "Inner", Transform::origin())));
+        description.prefabs.insert("Inner".to_string(), Prefab::new().with_child(ObjectDescription::instance("Outer", "Outer", Transform::origin())));
+        description.objects.push(ObjectDescription::instance("Outer", "Loop", Transform::origin()));
+        assert!(matches!(description.create_objects(&registry(), &assets), Err(SceneLoadError::RecursivePrefab(p)) if p == "Outer"));
+    }
+
+    #[test]
+    fn components() {
+        let assets = Assets::new(PathBuf::new());
+        let mut description = SceneDescription::default();
+        description.prefabs.insert("Marker".to_string(), marker().with_component(CallbackDescription::new("Follower", serde_json::Value::Null)));
+        description.objects.push(ObjectDescription::instance("Marker", "A", Transform::origin())
+            .with_parameters(serde_json::json!({ "speed": 3.0 }))
+            .with_component(CallbackDescription::new("Spinner", serde_json::json!({ "speed": 4.0 }))));
+
+        let mut objects = description.create_objects(&registry(), &assets).unwrap();
+        let mut scene = Scene::new(Box::new(SceneCallbackDefault {}), Renderable::Camera(Camera::new(Transform::origin(), 3.0)));
+        scene.add_object(objects.remove(0));
+        let object = &scene.objects()[0];
+        assert_eq!(object.get_component_count(), 3);
+
+        //The first Spinner is found, and Follower could look it up while loading
+        assert_eq!(object.get_component::<Spinner>().unwrap().speed, 3.0);
+        assert_eq!(object.get_component::<Follower>().unwrap().speed, Some(3.0));
+
+        let saved = object.describe(&assets);
+        assert_eq!(saved.callback.unwrap().type_name, "Spinner");
+        let types: Vec<_> = saved.components.iter().map(|c| c.type_name.as_str()).collect();
+        assert_eq!(types, ["Follower", "Spinner"]);
+    }
+}
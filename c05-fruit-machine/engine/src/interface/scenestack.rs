@@ -0,0 +1,499 @@
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::mem::take;
+
+use glium::framebuffer::{DepthRenderBuffer, SimpleFrameBuffer};
+use glium::texture::{DepthFormat, SrgbTexture2d};
+use glium::{uniform, Display, IndexBuffer, Program, Surface, VertexBuffer};
+
+use crate::{Scene, Window};
+use crate::shaders;
+use crate::shaders::RenderError;
+use crate::shaders::shader_priv::AssetCreationError;
+use crate::postprocess::POST_VERTEX_SHADER;
+
+const TRANSITION_FRAGMENT_SHADER: &str = r#"
+    #version 140
+
+    in vec2 v_uv;
+
+    out vec4 colour_out;
+
+    uniform sampler2D old_view;
+    uniform sampler2D new_view;
+    uniform vec3 colour;
+    uniform vec3 weights;
+
+    void main() {
+        vec3 blended = texture(old_view, v_uv).rgb * weights.x + colour * weights.y + texture(new_view, v_uv).rgb * weights.z;
+        colour_out = vec4(blended, 1.0);
+    }
+"#;
+
+///How the window changes from the scenes it was showing to the scenes it will show
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Transition {
+    ///Changes straight away
+    #[default]
+    Cut,
+    ///Fades the old scenes out to a colour over the first half of the duration, then fades the new scenes in
+    Fade { duration: f64, colour: [f32; 3] },
+    ///Blends from the old scenes to the new ones
+    Crossfade { duration: f64 },
+}
+
+impl Transition {
+    ///Fades through black over a duration in seconds
+    pub fn fade(duration: f64) -> Self {
+        Transition::Fade { duration, colour: [0.0, 0.0, 0.0] }
+    }
+
+    ///Gets how long the transition takes in seconds
+    pub fn get_duration(self) -> f64 {
+        match self {
+            Transition::Cut => 0.0,
+            Transition::Fade { duration, .. } | Transition::Crossfade { duration } => duration.max(0.0),
+        }
+    }
+
+    ///Gets how much of the old scenes, the fade colour and the new scenes to show, at a progress from 0 to 1
+    fn get_weights(self, progress: f64) -> [f32; 3] {
+        let progress = progress.clamp(0.0, 1.0) as f32;
+        match self {
+            Transition::Cut => [0.0, 0.0, 1.0],
+            Transition::Fade { .. } if progress < 0.5 => [1.0 - progress * 2.0, progress * 2.0, 0.0],
+            Transition::Fade { .. } => [0.0, 2.0 - progress * 2.0, progress * 2.0 - 1.0],
+            Transition::Crossfade { .. } => [1.0 - progress, 0.0, progress],
+        }
+    }
+
+    fn get_colour(self) -> [f32; 3] {
+        match self {
+            Transition::Fade { colour, .. } => colour,
+            _ => [0.0, 0.0, 0.0]
+        }
+    }
+}
+
+///A change to the scene stack, waiting for the current transition to finish
+#[derive(Debug)]
+pub(crate) enum SceneChange {
+    Push(Scene, Transition),
+    Pop(Transition),
+    Replace(Scene, Transition),
+}
+
+///Where a scene is in a transition
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EntryState {
+    ///Only drawn in the new view, until the transition finishes
+    Entering,
+    Active,
+    ///Only drawn in the old view, and removed when the transition finishes
+    Leaving,
+}
+
+#[derive(Debug)]
+struct StackEntry {
+    scene: Scene,
+    state: EntryState,
+}
+
+///Owns a SceneStack and inserts and removes scenes as they enter and leave it
+///Windows are the only hosts outside of tests
+pub(crate) trait SceneHost {
+    fn get_scenes(&mut self) -> &mut SceneStack;
+    ///Called before a scene enters the stack
+    fn insert_scene(&mut self, scene: &mut Scene);
+    ///Called once a scene has left the stack, after any transition out has finished
+    fn remove_scene(&mut self, scene: Scene);
+}
+
+///The scenes a window is running, drawn from the bottom up, with the top scene receiving the debug tools
+#[derive(Debug, Default)]
+pub(crate) struct SceneStack {
+    entries: Vec<StackEntry>,
+    changes: VecDeque<SceneChange>,
+    ///The transition being played and how far through it is in seconds
+    transition: Option<(Transition, f64)>,
+    renderer: TransitionRenderer,
+}
+
+impl SceneStack {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    ///Whether a transition is playing or changes are waiting to be made
+    pub(crate) fn is_changing(&self) -> bool {
+        self.transition.is_some() || !self.changes.is_empty()
+    }
+
+    ///Gets the highest scene that isn't leaving the stack
+    pub(crate) fn get_top(&self) -> Option<&Scene> {
+        self.entries.iter().rev().find(|e| e.state != EntryState::Leaving).map(|e| &e.scene)
+    }
+
+    pub(crate) fn get_top_mut(&mut self) -> Option<&mut Scene> {
+        self.entries.iter_mut().rev().find(|e| e.state != EntryState::Leaving).map(|e| &mut e.scene)
+    }
+
+    pub(crate) fn scenes_mut(&mut self) -> impl Iterator<Item = &mut Scene> {
+        self.entries.iter_mut().map(|e| &mut e.scene)
+    }
+
+    ///Queues a change to be made once the current transition has finished
+    pub(crate) fn queue(&mut self, change: SceneChange) {
+        self.changes.push_back(change);
+    }
+
+    ///Takes the next change to make, or None while a transition is playing
+    pub(crate) fn next_change(&mut self) -> Option<SceneChange> {
+        match self.transition {
+            Some(_) => None,
+            None => self.changes.pop_front()
+        }
+    }
+
+    ///Plays the host's current transition and makes waiting scene changes, inserting and removing scenes as they enter and leave
+    ///A replacing scene is inserted before the scene it replaces is removed
+    pub(crate) fn update<H: SceneHost>(host: &mut H, delta_time: f64) {
+        host.get_scenes().advance(delta_time);
+        loop {
+            for scene in host.get_scenes().finish_transition() {
+                host.remove_scene(scene);
+            }
+
+            match host.get_scenes().next_change() {
+                Some(SceneChange::Push(mut scene, transition)) => {
+                    host.insert_scene(&mut scene);
+                    host.get_scenes().push(scene, transition);
+                },
+                Some(SceneChange::Pop(transition)) => {
+                    host.get_scenes().pop(transition);
+                },
+                Some(SceneChange::Replace(mut scene, transition)) => {
+                    host.insert_scene(&mut scene);
+                    host.get_scenes().pop(transition);
+                    host.get_scenes().push(scene, transition);
+                },
+                None => break
+            }
+        }
+    }
+
+    ///Adds an inserted scene to the top of the stack
+    pub(crate) fn push(&mut self, scene: Scene, transition: Transition) {
+        self.entries.push(StackEntry { scene, state: EntryState::Entering });
+        self.transition = Some((transition, 0.0));
+    }
+
+    ///Starts removing the top scene, returning false if there is nothing to remove
+    pub(crate) fn pop(&mut self, transition: Transition) -> bool {
+        match self.entries.last_mut() {
+            Some(entry) => {
+                entry.state = EntryState::Leaving;
+                self.transition = Some((transition, 0.0));
+                true
+            },
+            None => false
+        }
+    }
+
+    ///Moves the transition on by a number of seconds
+    pub(crate) fn advance(&mut self, delta_time: f64) {
+        if let Some((_, elapsed)) = &mut self.transition {
+            *elapsed += delta_time;
+        }
+    }
+
+    ///Ends the transition if it has run for its whole duration, returning the scenes that left the stack top first
+    pub(crate) fn finish_transition(&mut self) -> Vec<Scene> {
+        match self.transition {
+            Some((transition, elapsed)) if elapsed >= transition.get_duration() => (),
+            _ => return Vec::new()
+        }
+        self.transition = None;
+
+        let (leaving, staying): (Vec<StackEntry>, Vec<StackEntry>) = take(&mut self.entries).into_iter().partition(|e| e.state == EntryState::Leaving);
+        self.entries = staying.into_iter().map(|e| StackEntry { state: EntryState::Active, ..e }).collect();
+        leaving.into_iter().rev().map(|e| e.scene).collect()
+    }
+
+    ///Takes every scene out of the stack top first and forgets any waiting changes
+    pub(crate) fn clear(&mut self) -> Vec<Scene> {
+        self.changes.clear();
+        self.transition = None;
+        take(&mut self.entries).into_iter().rev().map(|e| e.scene).collect()
+    }
+
+    ///Ticks every scene that isn't paused, from the bottom up
+    pub(crate) fn tick(&mut self, window: &Window) {
+        for entry in self.entries.iter_mut() {
+            if !entry.scene.is_paused() {
+                entry.scene.tick(&window.frame);
+            }
+        }
+    }
+
+    ///Draws the visible scenes, blending between the old and new scenes while a transition plays
+    pub(crate) fn render<S: Surface>(&mut self, frame: &mut S, window: &Window) -> Result<(), RenderError> {
+        let entries = &mut self.entries;
+        match self.transition {
+            Some((transition, elapsed)) if transition.get_duration() > 0.0 => {
+                let weights = transition.get_weights(elapsed / transition.get_duration());
+                self.renderer.render(frame, &window.display, weights, transition.get_colour(), |surface, new_view| {
+                    let hidden = if new_view {EntryState::Leaving} else {EntryState::Entering};
+                    render_entries(entries, surface, window, hidden)
+                })
+            },
+            //Nothing is leaving or entering outside of a transition
+            _ => render_entries(entries, frame, window, EntryState::Leaving)
+        }
+    }
+
+    ///Drops all gpu assets so that they are recreated on the next render
+    pub(crate) fn invalidate_assets(&mut self) {
+        for scene in self.scenes_mut() {
+            scene.invalidate_assets();
+        }
+        self.renderer.invalidate_assets();
+    }
+}
+
+///Draws the visible scenes from the bottom up, skipping those in the hidden state
+fn render_entries<S: Surface>(entries: &mut [StackEntry], frame: &mut S, window: &Window, hidden: EntryState) -> Result<(), RenderError> {
+    for entry in entries.iter_mut() {
+        if entry.state != hidden && entry.scene.is_visible() {
+            entry.scene.render(frame, window)?;
+        }
+    }
+    Ok(())
+}
+
+///Draws the old and new scenes into textures and blends them together
+#[derive(Default)]
+struct TransitionRenderer {
+    program: Option<Program>,
+    vertices: Option<VertexBuffer<shaders::ClearVertex>>,
+    indices: Option<IndexBuffer<u32>>,
+    ///Textures for the old and new scenes
+    views: Vec<SrgbTexture2d>,
+    depth: Option<DepthRenderBuffer>,
+}
+
+impl Debug for TransitionRenderer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TransitionRenderer").finish()
+    }
+}
+
+impl TransitionRenderer {
+    fn invalidate_assets(&mut self) {
+        self.program = None;
+        self.vertices = None;
+        self.indices = None;
+        self.views.clear();
+        self.depth = None;
+    }
+
+    ///Makes sure the textures are the same size as the frame
+    fn create_assets(&mut self, display: &Display, (width, height): (u32, u32)) -> Result<(), AssetCreationError> {
+        if self.program.is_none() {
+            self.program = Some(Program::from_source(display, POST_VERTEX_SHADER, TRANSITION_FRAGMENT_SHADER, None)?);
+            self.vertices = Some(VertexBuffer::new(display, &shaders::CLEAR_VERTICES)?);
+            self.indices = Some(IndexBuffer::new(display, glium::index::PrimitiveType::TrianglesList, &shaders::CLEAR_INDICES)?);
+        }
+
+        if self.views.first().map(|v| v.dimensions()) != Some((width, height)) {
+            self.views = (0..2).map(|_| SrgbTexture2d::empty(display, width, height)).collect::<Result<_, _>>()?;
+            self.depth = Some(DepthRenderBuffer::new(display, DepthFormat::I24, width, height)?);
+        }
+        Ok(())
+    }
+
+    ///Calls draw_view with false for the old view and true for the new one, skipping views that aren't shown,
+    ///then draws them to frame mixed by weights
+    ///Nothing is drawn to a surface with no pixels, such as a minimised window
+    fn render<S: Surface, F: FnMut(&mut SimpleFrameBuffer, bool) -> Result<(), RenderError>>(&mut self, frame: &mut S, display: &Display, weights: [f32; 3], colour: [f32; 3], mut draw_view: F) -> Result<(), RenderError> {
+        let dimensions = frame.get_dimensions();
+        if dimensions.0 == 0 || dimensions.1 == 0 {
+            return Ok(());
+        }
+        self.create_assets(display, dimensions)?;
+        //Always set once create_assets has succeeded
+        let (Some(program), Some(vertices), Some(indices), Some(depth)) = (&self.program, &self.vertices, &self.indices, &self.depth) else {
+            return Ok(());
+        };
+
+        for (i, view) in self.views.iter().enumerate() {
+            let mut surface = SimpleFrameBuffer::with_depth_buffer(display, view, depth)?;
+            surface.clear_color_and_depth((0.0, 0.0, 0.0, 1.0), 1.0);
+            //The old view has the first weight and the new view the last
+            if weights[i * 2] > 0.0 {
+                draw_view(&mut surface, i == 1)?;
+            }
+        }
+
+        let uniforms = uniform! {
+            old_view: &self.views[0],
+            new_view: &self.views[1],
+            colour: colour,
+            weights: weights,
+        };
+        frame.draw(vertices, indices, program, &uniforms, &Default::default())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::{SceneCallback, Renderable, Camera, Transform, GameObject};
+
+    type Log = Rc<RefCell<Vec<String>>>;
+
+    ///Records when its scene is loaded and unloaded, and names the scene with an object so the host can log it
+    #[derive(Debug)]
+    struct Logged {
+        name: &'static str,
+        log: Log,
+    }
+
+    impl SceneCallback for Logged {
+        fn on_load(&mut self, scene: &mut Scene) {
+            self.log.borrow_mut().push(format!("load {}", self.name));
+            scene.add_object(GameObject::new(None, self.name.to_string(), Transform::origin()));
+        }
+
+        fn on_unload(&mut self, _scene: &mut Scene) {
+            self.log.borrow_mut().push(format!("unload {}", self.name));
+        }
+    }
+
+    ///Stands in for a window, logging scenes as they are inserted and removed
+    #[derive(Default)]
+    struct Host {
+        scenes: SceneStack,
+        log: Log,
+    }
+
+    impl SceneHost for Host {
+        fn get_scenes(&mut self) -> &mut SceneStack {
+            &mut self.scenes
+        }
+
+        fn insert_scene(&mut self, scene: &mut Scene) {
+            self.log.borrow_mut().push(format!("insert {}", scene.objects()[0].name));
+        }
+
+        fn remove_scene(&mut self, scene: Scene) {
+            self.log.borrow_mut().push(format!("remove {}", scene.objects()[0].name));
+        }
+    }
+
+    impl Host {
+        fn scene(&self, name: &'static str) -> Scene {
+            Scene::new(Box::new(Logged { name, log: self.log.clone() }), Renderable::Camera(Camera::new(Transform::origin(), 3.0)))
+        }
+
+        fn top(&self) -> Option<&str> {
+            self.scenes.get_top().map(|s| s.objects()[0].name.as_str())
+        }
+
+        fn take_log(&self) -> Vec<String> {
+            take(&mut *self.log.borrow_mut())
+        }
+    }
+
+    #[test]
+    fn push_and_pop_callback_order() {
+        let mut host = Host::default();
+        let a = host.scene("a");
+        host.scenes.queue(SceneChange::Push(a, Transition::Cut));
+        let b = host.scene("b");
+        host.scenes.queue(SceneChange::Push(b, Transition::Cut));
+        SceneStack::update(&mut host, 0.0);
+        assert_eq!(host.take_log(), ["load a", "load b", "insert a", "insert b"]);
+        assert_eq!(host.top(), Some("b"));
+
+        host.scenes.queue(SceneChange::Pop(Transition::Cut));
+        SceneStack::update(&mut host, 0.0);
+        assert_eq!(host.take_log(), ["remove b", "unload b"]);
+        assert_eq!(host.top(), Some("a"));
+    }
+
+    #[test]
+    fn replace_inserts_before_removing() {
+        let mut host = Host::default();
+        let a = host.scene("a");
+        host.scenes.queue(SceneChange::Push(a, Transition::Cut));
+        SceneStack::update(&mut host, 0.0);
+        host.take_log();
+
+        let b = host.scene("b");
+        host.scenes.queue(SceneChange::Replace(b, Transition::Cut));
+        SceneStack::update(&mut host, 0.0);
+        assert_eq!(host.take_log(), ["load b", "insert b", "remove a", "unload a"]);
+        assert_eq!(host.top(), Some("b"));
+        assert_eq!(host.scenes.clear().len(), 1);
+    }
+
+    #[test]
+    fn cut_finishes_immediately() {
+        let mut host = Host::default();
+        let a = host.scene("a");
+        host.scenes.queue(SceneChange::Push(a, Transition::Cut));
+        assert!(host.scenes.is_changing());
+        SceneStack::update(&mut host, 0.0);
+        assert!(!host.scenes.is_changing());
+    }
+
+    #[test]
+    fn leaving_scenes_are_removed_after_the_transition() {
+        let mut host = Host::default();
+        let a = host.scene("a");
+        host.scenes.queue(SceneChange::Push(a, Transition::Cut));
+        let b = host.scene("b");
+        host.scenes.queue(SceneChange::Push(b, Transition::fade(1.0)));
+        SceneStack::update(&mut host, 0.0);
+        host.take_log();
+
+        //Changes wait for the transition playing to finish
+        host.scenes.queue(SceneChange::Pop(Transition::Crossfade { duration: 1.0 }));
+        SceneStack::update(&mut host, 0.6);
+        assert_eq!(host.top(), Some("b"));
+        SceneStack::update(&mut host, 0.6);
+        assert_eq!(host.top(), Some("a"));
+        assert!(host.take_log().is_empty());
+
+        //b is drawn while it crossfades out, then removed
+        SceneStack::update(&mut host, 0.9);
+        assert!(host.scenes.is_changing());
+        assert!(host.take_log().is_empty());
+        SceneStack::update(&mut host, 0.2);
+        assert!(!host.scenes.is_changing());
+        assert_eq!(host.take_log(), ["remove b", "unload b"]);
+    }
+
+    #[test]
+    fn transition_weights() {
+        let fade = Transition::fade(2.0);
+        assert_eq!(fade.get_weights(0.0), [1.0, 0.0, 0.0]);
+        assert_eq!(fade.get_weights(0.25), [0.5, 0.5, 0.0]);
+        assert_eq!(fade.get_weights(0.5), [0.0, 1.0, 0.0]);
+        assert_eq!(fade.get_weights(1.0), [0.0, 0.0, 1.0]);
+
+        let crossfade = Transition::Crossfade { duration: 2.0 };
+        assert_eq!(crossfade.get_weights(0.0), [1.0, 0.0, 0.0]);
+        assert_eq!(crossfade.get_weights(0.5), [0.5, 0.0, 0.5]);
+        assert_eq!(crossfade.get_weights(1.0), [0.0, 0.0, 1.0]);
+
+        assert_eq!(Transition::Cut.get_weights(0.0), [0.0, 0.0, 1.0]);
+        assert_eq!(crossfade.get_weights(1.5), [0.0, 0.0, 1.0]);
+        assert_eq!(Transition::Crossfade { duration: -1.0 }.get_duration(), 0.0);
+    }
+}
@@ -8,22 +8,47 @@ pub(crate) const CLEAR_VERTEX_SHADER: &str = "
 
     in vec2 position;
 
+    out vec2 v_position;
+
     void main() {
+        v_position = position;
         gl_Position = vec4(positions[gl_VertexID]);
         //gl_Position = vec4(position, 0.0, 1.0);
     }
 ";
 
-pub(crate) const CLEAR_FRAGMENT_SHADER: &str = "
+pub(crate) const GRADIENT_FRAGMENT_SHADER: &str = "
     //FRAGMENT_SHADER
     #version 140
 
+    in vec2 v_position;
+
+    out vec4 color;
+
+    uniform vec4 top_colour;
+    uniform vec4 bottom_colour;
+
+    void main() {
+        color = mix(bottom_colour, top_colour, v_position.y * 0.5 + 0.5);
+    }
+";
+
+pub(crate) const SKYBOX_FRAGMENT_SHADER: &str = "
+    //FRAGMENT_SHADER
+    #version 140
+
+    in vec2 v_position;
+
     out vec4 color;
 
-    uniform vec4 clear_colour;
+    uniform samplerCube skybox;
+    //How far the view spreads out along x and y for each unit forward
+    uniform vec2 view_scale;
+    uniform mat4 camera_rotation;
 
     void main() {
-        color = clear_colour;
+        vec3 direction = vec3(v_position * view_scale, 1.0);
+        color = texture(skybox, mat3(camera_rotation) * direction);
     }
 ";
 
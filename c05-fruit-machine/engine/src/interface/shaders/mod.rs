@@ -9,7 +9,7 @@ mod texture_only_3D;
 #[allow(non_snake_case)]
 mod texture_only_2D;
 
-use std::{rc::Rc};
+use std::{rc::{Rc, Weak}, cell::RefCell, sync::Arc};
 
 pub use unshaded_3D::Unshaded3D;
 pub use unshaded_2D::Unshaded2D;
@@ -18,22 +18,23 @@ pub use texture_only_2D::TextureOnly2D;
 
 pub(crate) use clear::*;
 
+use glium::backend::Facade;
+use serde::{Deserialize, Serialize};
+
 use crate::Window;
 
-use self::shader_priv::{TexturePriv, TextureLoadError};
+use self::shader_priv::{TexturePriv, TextureLoadError, TextureSource, AssetCreationError};
 
 pub(crate) mod shader_priv {
-    use std::{fmt::Debug};
+    use std::{fmt::Debug, rc::Rc, cell::RefCell, sync::Arc};
     use glium::{Program, uniforms::{Uniforms, UniformValue}, Display, ProgramCreationError};
 
     use crate::Transform;
 
-    use super::Texture;
-
     #[derive(Clone)]
     pub enum UniformType<'a> {
         Immediate(UniformValue<'a>),
-        Texture(Texture),
+        Texture(Rc<glium::texture::SrgbTexture2d>),
     }
 
     #[derive(Clone)]
@@ -47,7 +48,7 @@ pub(crate) mod shader_priv {
                 match pair {
                     (s, UniformType::Immediate(u)) => (st, uv) = (s, *u),
                     (s, UniformType::Texture(t)) => {
-                        (st, uv) = (s, UniformValue::SrgbTexture2d(&t.0.0, Default::default()))
+                        (st, uv) = (s, UniformValue::SrgbTexture2d(t, Default::default()))
                     }
 
                 }
@@ -67,42 +68,252 @@ pub(crate) mod shader_priv {
     #[derive(Debug)]
     pub enum AssetCreationError {
         Program(ProgramCreationError),
-        Texture(TextureLoadError)
+        Texture(TextureLoadError),
+        VertexBuffer(glium::vertex::BufferCreationError),
+        IndexBuffer(glium::index::BufferCreationError),
+        ///A texture to draw into couldn't be created
+        RenderTarget(glium::texture::TextureCreationError),
+        DepthBuffer(glium::framebuffer::RenderBufferCreationError),
+    }
+
+    impl From<ProgramCreationError> for AssetCreationError {
+        fn from(e: ProgramCreationError) -> Self {
+            AssetCreationError::Program(e)
+        }
+    }
+
+    impl From<glium::vertex::BufferCreationError> for AssetCreationError {
+        fn from(e: glium::vertex::BufferCreationError) -> Self {
+            AssetCreationError::VertexBuffer(e)
+        }
+    }
+
+    impl From<glium::index::BufferCreationError> for AssetCreationError {
+        fn from(e: glium::index::BufferCreationError) -> Self {
+            AssetCreationError::IndexBuffer(e)
+        }
+    }
+
+    impl From<glium::texture::TextureCreationError> for AssetCreationError {
+        fn from(e: glium::texture::TextureCreationError) -> Self {
+            AssetCreationError::RenderTarget(e)
+        }
+    }
+
+    impl From<glium::framebuffer::RenderBufferCreationError> for AssetCreationError {
+        fn from(e: glium::framebuffer::RenderBufferCreationError) -> Self {
+            AssetCreationError::DepthBuffer(e)
+        }
+    }
+
+    ///Where a texture's image data was loaded from
+    ///Kept so that the texture can be uploaded again if the gl context is lost
+    #[derive(Debug, Clone)]
+    pub enum TextureSource {
+        Path(String),
+        Bytes(Arc<[u8]>),
+        ///A blank texture of the given size, such as a render target
+        Empty(u32, u32),
     }
 
     #[derive(Debug)]
-    pub struct TexturePriv (pub glium::texture::SrgbTexture2d);
+    pub struct TexturePriv {
+        pub source: TextureSource,
+        pub texture: RefCell<Rc<glium::texture::SrgbTexture2d>>,
+    }
 
     pub trait ShaderPriv: Debug {
         fn get_vertex_shader(&self) -> String;
         fn get_fragment_shader(&self) -> String;
         fn get_program(& self) -> &Option<Program>;
         fn create_assets(&mut self, display: &Display) -> Result<(), AssetCreationError>;
+        ///Drops any gpu assets so that they are recreated by the next call to create_assets
+        fn invalidate_assets(&mut self);
+        #[allow(clippy::too_many_arguments)]
         fn get_uniforms(&self, camera_mat: &Transform, mesh_mat: &Transform, fov: f32, aspect_ratio: f32, zfar: f32, znear: f32, obj_mat: &crate::Transform, out: &mut ShaderUniforms);
+        ///Gets the matrix the shader uses to project from the camera's space onto the screen
+        fn get_projection(&self, fov: f32, aspect_ratio: f32, zfar: f32, znear: f32) -> [[f32; 4]; 4];
+        fn get_blend_mode(&self) -> super::BlendMode;
+        ///Gets the shader's settings for saving in a scene file
+        ///None if the shader can't be saved, such as when its texture wasn't loaded from an asset
+        fn describe(&self, _assets: &crate::Assets) -> Option<crate::MaterialDescription> {
+            None
+        }
     }
 }
 pub trait Shader: shader_priv::ShaderPriv {}
 
+///How a shader's output is combined with what has already been drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum BlendMode {
+    ///Replaces what is behind it, ignoring alpha
+    #[default]
+    Opaque,
+    ///Mixes with what is behind it by alpha
+    ///Drawn after opaque meshes, from back to front
+    Alpha,
+    ///Adds to what is behind it, scaled by alpha
+    ///Drawn after opaque meshes, from back to front
+    Additive,
+}
+
+impl BlendMode {
+    ///Whether meshes with this mode can be seen through, so need to be drawn after opaque meshes
+    pub fn is_transparent(self) -> bool {
+        self != BlendMode::Opaque
+    }
+
+    pub(crate) fn to_blend(self) -> glium::Blend {
+        use glium::{Blend, BlendingFunction, LinearBlendingFactor};
+        match self {
+            BlendMode::Opaque => Blend::default(),
+            BlendMode::Alpha => Blend::alpha_blending(),
+            BlendMode::Additive => Blend {
+                color: BlendingFunction::Addition {
+                    source: LinearBlendingFactor::SourceAlpha,
+                    destination: LinearBlendingFactor::One,
+                },
+                alpha: BlendingFunction::Addition {
+                    source: LinearBlendingFactor::Zero,
+                    destination: LinearBlendingFactor::One,
+                },
+                constant_value: (0.0, 0.0, 0.0, 0.0),
+            },
+        }
+    }
+}
+
+///An error while drawing a frame, which stops the rest of the frame being drawn
+#[derive(Debug)]
+pub enum RenderError {
+    ///A gpu asset needed for drawing couldn't be created
+    Assets(AssetCreationError),
+    ///A texture couldn't be drawn into
+    Framebuffer(glium::framebuffer::ValidationError),
+    Draw(glium::DrawError),
+}
+
+impl From<AssetCreationError> for RenderError {
+    fn from(e: AssetCreationError) -> Self {
+        RenderError::Assets(e)
+    }
+}
+
+impl From<glium::framebuffer::ValidationError> for RenderError {
+    fn from(e: glium::framebuffer::ValidationError) -> Self {
+        RenderError::Framebuffer(e)
+    }
+}
+
+impl From<glium::DrawError> for RenderError {
+    fn from(e: glium::DrawError) -> Self {
+        RenderError::Draw(e)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Texture(Rc<TexturePriv>);
 
 impl Texture {
     pub fn new(path: String, window: &Window) -> Result<Self, TextureLoadError> {
-        let texture_image = match image::open(path) {
+        let source = TextureSource::Path(path);
+        let image = Texture::decode(&source)?;
+        Texture::from_image(source, image, &window.display, &window.textures)
+    }
+
+    ///Creates a texture from the contents of an image file, such as one embedded with include_bytes!
+    pub fn from_bytes(bytes: &[u8], window: &Window) -> Result<Self, TextureLoadError> {
+        let source = TextureSource::Bytes(Arc::from(bytes));
+        let image = Texture::decode(&source)?;
+        Texture::from_image(source, image, &window.display, &window.textures)
+    }
+
+    ///Creates a blank texture that a camera can render into with Camera::set_target
+    pub fn new_render_target(width: u32, height: u32, window: &Window) -> Result<Self, TextureLoadError> {
+        let source = TextureSource::Empty(width, height);
+        let image = Texture::decode(&source)?;
+        Texture::from_image(source, image, &window.display, &window.textures)
+    }
+
+    ///Gets the width and height of the texture in pixels
+    pub fn get_dimensions(&self) -> (u32, u32) {
+        self.get().dimensions()
+    }
+
+    ///Creates a texture from already decoded image data, registering it so that it can be
+    ///reuploaded if the context is lost
+    pub(crate) fn from_image<F: Facade + ?Sized>(source: TextureSource, image: image::RgbaImage, display: &F, registry: &RefCell<Vec<Weak<TexturePriv>>>) -> Result<Self, TextureLoadError> {
+        let texture = Texture(Rc::new(TexturePriv {
+            texture: RefCell::new(Rc::new(Texture::upload(image, display)?)),
+            source,
+        }));
+
+        registry.borrow_mut().push(Rc::downgrade(&texture.0));
+
+        Ok(texture)
+    }
+
+    ///Whether two textures are clones of each other
+    pub(crate) fn ptr_eq(&self, other: &Texture) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+
+    ///Gets the texture's gpu data
+    pub(crate) fn get(&self) -> Rc<glium::texture::SrgbTexture2d> {
+        self.0.texture.borrow().clone()
+    }
+
+    ///Uploads the texture's image data to the gpu again
+    ///If the data can't be loaded, a blank texture of the same size is uploaded instead,
+    ///so that the texture no longer uses the old context even when reloading fails
+    pub(crate) fn reload<F: Facade + ?Sized>(texture: &TexturePriv, display: &F) -> Result<(), TextureLoadError> {
+        let result = Texture::decode(&texture.source).and_then(|image| Texture::upload(image, display));
+        let uploaded = match result {
+            Ok(uploaded) => uploaded,
+            Err(e) => {
+                let (width, height) = texture.texture.borrow().dimensions();
+                if let Ok(blank) = Texture::upload(image::RgbaImage::new(width, height), display) {
+                    *texture.texture.borrow_mut() = Rc::new(blank);
+                }
+                return Err(e);
+            }
+        };
+        *texture.texture.borrow_mut() = Rc::new(uploaded);
+        Ok(())
+    }
+
+    ///Replaces the texture's gpu data with new image data
+    ///Every clone of the texture will use the new data
+    pub(crate) fn replace_image<F: Facade + ?Sized>(&self, image: image::RgbaImage, display: &F) -> Result<(), TextureLoadError> {
+        *self.0.texture.borrow_mut() = Rc::new(Texture::upload(image, display)?);
+        Ok(())
+    }
+
+    ///Loads a texture's image data from its source
+    ///Does not touch the gpu, so can be called from any thread
+    pub(crate) fn decode(source: &TextureSource) -> Result<image::RgbaImage, TextureLoadError> {
+        let image = match source {
+            TextureSource::Path(path) => image::open(path),
+            TextureSource::Bytes(bytes) => image::load_from_memory(bytes),
+            TextureSource::Empty(width, height) => return Ok(image::RgbaImage::new(*width, *height)),
+        };
+        Ok(match image {
             Ok(i) => i,
             Err(e) => match e {
                 image::ImageError::IoError(e) => return Err(TextureLoadError::Io(e)),
                 _ => return Err(TextureLoadError::ImageError)
             }
-        }.to_rgba8();
-        let image_dimensions = texture_image.dimensions();
-        let image = glium::texture::RawImage2d::from_raw_rgba_reversed(&texture_image.into_raw(), image_dimensions);
+        }.to_rgba8())
+    }
 
-        let texture = match glium::texture::SrgbTexture2d::new(&window.display, image) {
-            Ok(t) => t,
-            Err(_) => return Err(TextureLoadError::ImageError)
-        };
+    ///Uploads decoded image data to the gpu
+    fn upload<F: Facade + ?Sized>(image: image::RgbaImage, display: &F) -> Result<glium::texture::SrgbTexture2d, TextureLoadError> {
+        let image_dimensions = image.dimensions();
+        let image = glium::texture::RawImage2d::from_raw_rgba_reversed(&image.into_raw(), image_dimensions);
 
-        Ok(Texture(Rc::new(TexturePriv(texture))))
+        match glium::texture::SrgbTexture2d::new(display, image) {
+            Ok(t) => Ok(t),
+            Err(_) => Err(TextureLoadError::ImageError)
+        }
     }
 }
\ No newline at end of file
@@ -1,8 +1,8 @@
 use glium::{uniforms::UniformValue, Program};
 
-use crate::Transform;
+use crate::{Transform, MaterialDescription};
 
-use super::{shader_priv::{ShaderUniforms, ShaderPriv, UniformType, AssetCreationError}, Shader, Texture};
+use super::{shader_priv::{ShaderUniforms, ShaderPriv, UniformType, AssetCreationError}, Shader, Texture, BlendMode};
 
 pub(crate) const VERTEX_SHADER_TEXTURE_ONLY_2D_SRC: &str = r#"
     #version 140
@@ -11,7 +11,12 @@ pub(crate) const VERTEX_SHADER_TEXTURE_ONLY_2D_SRC: &str = r#"
     in vec3 normal;
     in vec2 uv;
 
+    in mat4 instance_matrix;
+    in vec4 instance_colour;
+    in vec4 instance_uv;
+
     out vec2 v_uv;
+    out vec4 v_colour;
 
     uniform mat4 camera_matrix;
     uniform mat4 perspective_matrix;
@@ -19,8 +24,9 @@ pub(crate) const VERTEX_SHADER_TEXTURE_ONLY_2D_SRC: &str = r#"
     uniform mat4 mesh_matrix;
 
     void main() {
-        v_uv = uv;
-        gl_Position = perspective_matrix * inverse(camera_matrix) * object_matrix * mesh_matrix * vec4(position, 1.0);
+        v_uv = uv * instance_uv.zw + instance_uv.xy;
+        v_colour = instance_colour;
+        gl_Position = perspective_matrix * inverse(camera_matrix) * object_matrix * mesh_matrix * instance_matrix * vec4(position, 1.0);
     }
 "#;
 
@@ -28,13 +34,14 @@ pub(crate) const FRAGMENT_SHADER_TEXTURE_ONLY_2D_SRC: &str = r#"
     #version 140
 
     in vec2 v_uv;
+    in vec4 v_colour;
 
     out vec4 colour_out;
 
     uniform sampler2D tex;
 
     void main() {
-        colour_out = texture(tex, v_uv);
+        colour_out = texture(tex, v_uv) * v_colour;
     }
 "#;
 
@@ -43,35 +50,26 @@ struct TextureOnly2DPriv {
     fragment_text: String,
     program: Option<Program>,
     texture: Texture,
+    blend_mode: BlendMode,
 }
 
-impl<'a> TextureOnly2DPriv {
+impl TextureOnly2DPriv {
     fn create_program(&mut self, display: &glium::Display) -> Result<&Program, glium::ProgramCreationError> {
         self.program = Some(glium::Program::from_source(display, &self.vertex_text, &self.fragment_text, None)?);
         Ok(self.program.as_ref().unwrap())
     }
 }
 
-impl<'a> std::fmt::Debug for TextureOnly2DPriv {
+impl std::fmt::Debug for TextureOnly2DPriv {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "program generated: {}", self.program.is_some()).unwrap();
         Ok(())
     }
 }
 
-impl<'a> ShaderPriv for TextureOnly2DPriv {
-    fn get_uniforms(&self, camera_mat: &Transform, mesh_mat: &Transform, _fov: f32, aspect_ratio: f32, zfar: f32, _znear: f32, obj_mat: &crate::Transform, out: &mut ShaderUniforms) {
-
-        let persp_mat = {
-            //let f = 1.0 / ((3.141592 / fov) / 2.0).tan();
-        
-            [
-                [ zfar * (aspect_ratio).min(1.0) ,                 0.0                   ,       0.0       ,   0.0   ],
-                [                0.0             , zfar *  (1.0 / aspect_ratio).min(1.0) ,       0.0       ,   0.0   ],
-                [                0.0             ,                 0.0                   ,       1.0       ,   0.0   ],
-                [                0.0             ,                 0.0                   ,       0.0       ,   zfar  ],
-            ]
-        };
+impl ShaderPriv for TextureOnly2DPriv {
+    fn get_uniforms(&self, camera_mat: &Transform, mesh_mat: &Transform, fov: f32, aspect_ratio: f32, zfar: f32, znear: f32, obj_mat: &crate::Transform, out: &mut ShaderUniforms) {
+        let persp_mat = self.get_projection(fov, aspect_ratio, zfar, znear);
 
         *out = ShaderUniforms (
             vec![
@@ -79,12 +77,22 @@ impl<'a> ShaderPriv for TextureOnly2DPriv {
                 ("perspective_matrix".to_string(), UniformType::Immediate(UniformValue::Mat4(persp_mat))),
                 ("object_matrix".to_string(), UniformType::Immediate(UniformValue::Mat4(obj_mat.to_array()))),
                 ("mesh_matrix".to_string(), UniformType::Immediate(UniformValue::Mat4(mesh_mat.to_array()))),
-                ("tex".to_string(), UniformType::Texture(self.texture.clone())),
+                ("tex".to_string(), UniformType::Texture(self.texture.get())),
             ]
         )
 
     }
     
+    fn get_projection(&self, _fov: f32, aspect_ratio: f32, zfar: f32, _znear: f32) -> [[f32; 4]; 4] {
+        //let f = 1.0 / ((3.141592 / fov) / 2.0).tan();
+
+        [
+            [ zfar * (aspect_ratio).min(1.0) ,                 0.0                   ,       0.0       ,   0.0   ],
+            [                0.0             , zfar *  (1.0 / aspect_ratio).min(1.0) ,       0.0       ,   0.0   ],
+            [                0.0             ,                 0.0                   ,       1.0       ,   0.0   ],
+            [                0.0             ,                 0.0                   ,       0.0       ,   zfar  ],
+        ]
+    }
     fn get_vertex_shader(&self) -> String {
         self.vertex_text.clone()
     }
@@ -103,6 +111,12 @@ impl<'a> ShaderPriv for TextureOnly2DPriv {
         }
         Ok(())
     }
+    fn invalidate_assets(&mut self) {
+        self.program = None;
+    }
+    fn get_blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
 }
 
 #[derive(Debug)]
@@ -115,16 +129,30 @@ impl TextureOnly2D {
             fragment_text: FRAGMENT_SHADER_TEXTURE_ONLY_2D_SRC.to_string(),
             program: None,
             
-            texture: texture,
+            texture,
+            blend_mode: BlendMode::Opaque,
         })
     }
+
+    ///Sets how the shader's output is combined with what has already been drawn
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.set_blend_mode(blend_mode);
+        self
+    }
+
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.0.blend_mode = blend_mode;
+    }
 }
 
 
-impl<'a> ShaderPriv for TextureOnly2D {
+impl ShaderPriv for TextureOnly2D {
     fn get_uniforms(&self, camera_mat: &Transform, mesh_mat: &Transform, fov: f32, aspect_ratio: f32, zfar: f32, znear: f32, obj_mat: &crate::Transform, out: &mut ShaderUniforms) {
         self.0.get_uniforms(camera_mat, mesh_mat, fov, aspect_ratio, zfar, znear, obj_mat, out)
     }
+    fn get_projection(&self, fov: f32, aspect_ratio: f32, zfar: f32, znear: f32) -> [[f32; 4]; 4] {
+        self.0.get_projection(fov, aspect_ratio, zfar, znear)
+    }
     fn get_fragment_shader(&self) -> String {
         self.0.get_fragment_shader()
     }
@@ -137,5 +165,17 @@ impl<'a> ShaderPriv for TextureOnly2D {
     fn create_assets(&mut self, display: &glium::Display) -> Result<(), AssetCreationError> {
         self.0.create_assets(display)
     }
+    fn invalidate_assets(&mut self) {
+        self.0.invalidate_assets()
+    }
+    fn get_blend_mode(&self) -> BlendMode {
+        self.0.get_blend_mode()
+    }
+    fn describe(&self, assets: &crate::Assets) -> Option<MaterialDescription> {
+        Some(MaterialDescription::TextureOnly2D {
+            texture: assets.find_texture_name(&self.0.texture)?,
+            blend_mode: Some(self.0.blend_mode).filter(|b| *b != BlendMode::Opaque),
+        })
+    }
 }
-impl<'a> Shader for TextureOnly2D {}
\ No newline at end of file
+impl Shader for TextureOnly2D {}
\ No newline at end of file
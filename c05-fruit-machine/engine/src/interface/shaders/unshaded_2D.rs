@@ -1,8 +1,8 @@
 use glium::{uniforms::UniformValue, Program};
 
-use crate::Transform;
+use crate::{Transform, MaterialDescription};
 
-use super::{shader_priv::{ShaderUniforms, ShaderPriv, UniformType, AssetCreationError}, Shader};
+use super::{shader_priv::{ShaderUniforms, ShaderPriv, UniformType, AssetCreationError}, Shader, BlendMode};
 
 pub(crate) const VERTEX_SHADER_UNSHADED_2D_SRC: &str = r#"
     #version 140
@@ -10,24 +10,32 @@ pub(crate) const VERTEX_SHADER_UNSHADED_2D_SRC: &str = r#"
     in vec3 position;
     in vec3 normal;
 
+    in mat4 instance_matrix;
+    in vec4 instance_colour;
+
+    out vec4 v_colour;
+
     uniform mat4 camera_matrix;
     uniform mat4 perspective_matrix;
     uniform mat4 object_matrix;
     uniform mat4 mesh_matrix;
 
     void main() {
-        gl_Position = perspective_matrix * inverse(camera_matrix) * object_matrix * mesh_matrix * vec4(position, 1.0);
+        v_colour = instance_colour;
+        gl_Position = perspective_matrix * inverse(camera_matrix) * object_matrix * mesh_matrix * instance_matrix * vec4(position, 1.0);
     }
 "#;
 
 pub(crate) const FRAGMENT_SHADER_UNSHADED_2D_SRC: &str = r#"
     #version 140
 
+    in vec4 v_colour;
+
     out vec4 colour_out;
     uniform vec4 colour_in;
 
     void main() {
-        colour_out = vec4(colour_in);
+        colour_out = colour_in * v_colour;
     }
 "#;
 
@@ -37,6 +45,7 @@ struct Unshaded2DPriv {
     fragment_text: String,
     program: Option<Program>,
     colour: [f32; 4],
+    blend_mode: BlendMode,
 }
 
 impl Unshaded2DPriv {
@@ -47,16 +56,8 @@ impl Unshaded2DPriv {
 }
 
 impl ShaderPriv for Unshaded2DPriv {
-    fn get_uniforms(&self, camera_mat: &Transform, mesh_mat: &Transform, _fov: f32, aspect_ratio: f32, zfar: f32, _znear: f32, obj_mat: &crate::Transform, out: &mut ShaderUniforms) {
-        let persp_mat = {
-        
-            [
-                [ zfar * (aspect_ratio).min(1.0) ,                 0.0                   ,       0.0       ,   0.0   ],
-                [                0.0             , zfar *  (1.0 / aspect_ratio).min(1.0) ,       0.0       ,   0.0   ],
-                [                0.0             ,                 0.0                   ,       1.0       ,   0.0   ],
-                [                0.0             ,                 0.0                   ,       0.0       ,   zfar  ],
-            ]
-        };
+    fn get_uniforms(&self, camera_mat: &Transform, mesh_mat: &Transform, fov: f32, aspect_ratio: f32, zfar: f32, znear: f32, obj_mat: &crate::Transform, out: &mut ShaderUniforms) {
+        let persp_mat = self.get_projection(fov, aspect_ratio, zfar, znear);
 
         *out = ShaderUniforms (
             vec![
@@ -68,6 +69,14 @@ impl ShaderPriv for Unshaded2DPriv {
             ]
         )
     }
+    fn get_projection(&self, _fov: f32, aspect_ratio: f32, zfar: f32, _znear: f32) -> [[f32; 4]; 4] {
+        [
+            [ zfar * (aspect_ratio).min(1.0) ,                 0.0                   ,       0.0       ,   0.0   ],
+            [                0.0             , zfar *  (1.0 / aspect_ratio).min(1.0) ,       0.0       ,   0.0   ],
+            [                0.0             ,                 0.0                   ,       1.0       ,   0.0   ],
+            [                0.0             ,                 0.0                   ,       0.0       ,   zfar  ],
+        ]
+    }
     fn get_vertex_shader(&self) -> String {
         self.vertex_text.clone()
     }
@@ -87,6 +96,12 @@ impl ShaderPriv for Unshaded2DPriv {
         }
         Ok(())
     }
+    fn invalidate_assets(&mut self) {
+        self.program = None;
+    }
+    fn get_blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
 }
 
 #[derive(Debug)]
@@ -100,11 +115,22 @@ impl Unshaded2D {
                 fragment_text: FRAGMENT_SHADER_UNSHADED_2D_SRC.to_string(),
                 program: None,
                 
-                colour: colour
+                colour,
+                blend_mode: BlendMode::Opaque,
             }  
         )
         
     }
+
+    ///Sets how the shader's output is combined with what has already been drawn
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.set_blend_mode(blend_mode);
+        self
+    }
+
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.0.blend_mode = blend_mode;
+    }
 }
 
 
@@ -112,6 +138,9 @@ impl ShaderPriv for Unshaded2D {
     fn get_uniforms(&self, camera_mat: &Transform, mesh_mat: &Transform, fov: f32, aspect_ratio: f32, zfar: f32, znear: f32, obj_mat: &crate::Transform, out: &mut ShaderUniforms) {
         self.0.get_uniforms(camera_mat, mesh_mat, fov, aspect_ratio, zfar, znear, obj_mat, out)
     }
+    fn get_projection(&self, fov: f32, aspect_ratio: f32, zfar: f32, znear: f32) -> [[f32; 4]; 4] {
+        self.0.get_projection(fov, aspect_ratio, zfar, znear)
+    }
     fn get_fragment_shader(&self) -> String {
         self.0.get_fragment_shader()
     }
@@ -124,5 +153,17 @@ impl ShaderPriv for Unshaded2D {
     fn create_assets(&mut self, display: &glium::Display) -> Result<(), AssetCreationError> {
         self.0.create_assets(display)
     }
+    fn invalidate_assets(&mut self) {
+        self.0.invalidate_assets()
+    }
+    fn get_blend_mode(&self) -> BlendMode {
+        self.0.get_blend_mode()
+    }
+    fn describe(&self, _assets: &crate::Assets) -> Option<MaterialDescription> {
+        Some(MaterialDescription::Unshaded2D {
+            colour: self.0.colour,
+            blend_mode: Some(self.0.blend_mode).filter(|b| *b != BlendMode::Opaque),
+        })
+    }
 }
 impl Shader for Unshaded2D {}
\ No newline at end of file
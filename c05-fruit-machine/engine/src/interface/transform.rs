@@ -1,83 +1,303 @@
-use ndarray::Array2;
-use ndarray::arr2;
+use serde::{Deserialize, Serialize};
+
+use crate::{Mat4, Quat, Vec3};
 
 ///A struct representing a position, rotation, and scale in 3d space
-#[derive(Debug, Clone)]
+///
+///Applies scale, then rotation, then translation.
+///Transforms combine with `a * b`, which applies a then b, the same as multiplying their matrices.
+///Combining a non uniform scale with a rotation that comes before it would need a shear,
+///which can't be stored, so the result keeps the scale axes aligned in that case.
+///In scene files, any of position, rotation and scale can be left out to keep their values at the origin
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Transform {
-    pub matrix: Array2<f64>,
+    position: Vec3,
+    rotation: Quat,
+    scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::origin()
+    }
 }
 
 impl Transform {
 
     pub fn origin() -> Self {
-        Transform::from_pos(0.0, 0.0, 0.0)
+        Transform { position: Vec3::ZERO, rotation: Quat::IDENTITY, scale: Vec3::ONE }
+    }
+
+    pub fn new(position: Vec3, rotation: Quat, scale: Vec3) -> Self {
+        Transform { position, rotation, scale }
     }
 
     pub fn from_pos(x: f64, y: f64, z: f64) -> Self {
-        Transform { matrix:  arr2(
-            &[
-                [1.0, 0.0, 0.0, 0.0],
-                [0.0, 1.0, 0.0, 0.0],
-                [0.0, 0.0, 1.0, 0.0],
-                [x, y, z, 1.0]
-            ])}
+        Transform { position: Vec3::new(x, y, z), ..Transform::origin() }
     }
 
     pub fn from_scale(x: f64, y: f64, z: f64) -> Self {
-        Transform { matrix: arr2(
-            &[
-                [x, 0.0, 0.0, 0.0],
-                [0.0, y, 0.0, 0.0],
-                [0.0, 0.0, z, 0.0],
-                [0.0, 0.0, 0.0, 1.0]
-            ]
-        
-        )}
+        Transform { scale: Vec3::new(x, y, z), ..Transform::origin() }
     }
 
     pub fn from_euler(x: f64, y: f64, z: f64) -> Self {
-        Transform { matrix: arr2(
-            &[
-                [1.0, 0.0, 0.0, 0.0],
-                [0.0, x.cos(), -x.sin(), 0.0],
-                [0.0, x.sin(), x.cos(), 0.0],
-                [0.0, 0.0, 0.0, 1.0]
-            ]).dot(&arr2(
-                &[
-                    [y.cos(), 0.0, y.sin(), 0.0],
-                    [0.0, 1.0, 0.0, 0.0],
-                    [-y.sin(), 0.0, y.cos(), 0.0],
-                    [0.0, 0.0, 0.0, 1.0]
-                ]
-            )).dot(&arr2(
-                &[
-                    [z.cos(), z.sin(), 0.0, 0.0],
-                    [-z.sin(), z.cos(), 0.0, 0.0],
-                    [0.0, 0.0, 1.0, 0.0],
-                    [0.0, 0.0, 0.0, 1.0]
-                ]
-            ))
+        Transform { rotation: Quat::from_euler(x, y, z), ..Transform::origin() }
+    }
+
+    pub fn from_rotation(rotation: Quat) -> Self {
+        Transform { rotation, ..Transform::origin() }
+    }
+
+    ///Splits a matrix into position, rotation and scale
+    ///Any shear in the matrix is lost
+    pub fn from_matrix(matrix: &Mat4) -> Self {
+        let rows = &matrix.rows;
+        let axis = |i: usize| Vec3::new(rows[i][0], rows[i][1], rows[i][2]);
+
+        let mut scale = Vec3::new(axis(0).length(), axis(1).length(), axis(2).length());
+        //A negative determinant means the matrix mirrors, which a rotation can't do
+        if axis(0).cross(axis(1)).dot(axis(2)) < 0.0 {
+            scale.x = -scale.x;
         }
+
+        let unscaled = |i: usize, s: f64| if s == 0.0 { Vec3::ZERO } else { axis(i) * (1.0 / s) };
+        let (x, y, z) = (unscaled(0, scale.x), unscaled(1, scale.y), unscaled(2, scale.z));
+        let rotation = Quat::from_mat4(&Mat4::from_rows([
+            [x.x, x.y, x.z, 0.0],
+            [y.x, y.y, y.z, 0.0],
+            [z.x, z.y, z.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]));
+
+        Transform { position: matrix.get_translation(), rotation, scale }
+    }
+
+    ///Creates a transform at position, rotated so that +z points at target
+    pub fn look_at(position: Vec3, target: Vec3, up: Vec3) -> Self {
+        Transform {
+            position,
+            rotation: Quat::look_rotation(target - position, up),
+            scale: Vec3::ONE,
+        }
+    }
+
+    pub fn to_matrix(&self) -> Mat4 {
+        Mat4::from_scale(self.scale) * Mat4::from_rotation(self.rotation) * Mat4::from_translation(self.position)
+    }
+
+    ///Gets the transform as a matrix, which it used to be stored as
+    #[deprecated(note = "Transform is stored as position, rotation and scale, use to_matrix")]
+    pub fn matrix(&self) -> Mat4 {
+        self.to_matrix()
+    }
+
+    ///Sets the transform from a matrix, losing any shear
+    #[deprecated(note = "Transform is stored as position, rotation and scale, use from_matrix")]
+    pub fn set_matrix(&mut self, matrix: &Mat4) {
+        *self = Transform::from_matrix(matrix);
     }
 
     pub fn to_array(&self) -> [[f32; 4]; 4] {
-        let slice = self.matrix.as_slice().unwrap();
-        [
-            [slice[0] as f32, slice[1] as f32, slice[2] as f32, slice[3] as f32],
-            [slice[4] as f32, slice[5] as f32, slice[6] as f32, slice[7] as f32],
-            [slice[8] as f32, slice[9] as f32, slice[10] as f32, slice[11] as f32],
-            [slice[12] as f32, slice[13] as f32, slice[14] as f32, slice[15] as f32],
-        ]
+        self.to_matrix().to_f32_array()
     }
 
     pub fn get_pos(&self) -> (f64, f64, f64) {
-        (self.matrix.column(0)[3], self.matrix.column(1)[3], self.matrix.column(2)[3])
+        self.position.to_tuple()
+    }
+
+    pub fn get_position(&self) -> Vec3 {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: Vec3) {
+        self.position = position;
+    }
+
+    pub fn get_rotation(&self) -> Quat {
+        self.rotation
+    }
+
+    pub fn set_rotation(&mut self, rotation: Quat) {
+        self.rotation = rotation.normalised();
+    }
+
+    pub fn get_scale(&self) -> Vec3 {
+        self.scale
+    }
+
+    pub fn set_scale(&mut self, scale: Vec3) {
+        self.scale = scale;
+    }
+
+    ///Moves the transform without changing its rotation or scale
+    pub fn translate(&mut self, delta: Vec3) {
+        self.position += delta;
+    }
+
+    ///Rotates the transform around its own position
+    pub fn rotate(&mut self, rotation: Quat) {
+        self.rotation = (rotation * self.rotation).normalised();
+    }
+
+    ///Points +z at target, keeping the position and scale
+    pub fn look_at_point(&mut self, target: Vec3, up: Vec3) {
+        self.rotation = Quat::look_rotation(target - self.position, up);
+    }
+
+    ///Gets the transform that undoes this one
+    ///Returns None if any axis has a scale of 0
+    ///If the scale is not uniform and there is a rotation, the inverse needs a shear, which is lost
+    pub fn inverse(&self) -> Option<Self> {
+        self.to_matrix().inverse().map(|m| Transform::from_matrix(&m))
+    }
+
+    ///Moves a point from the transform's local space into the space it is in
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        self.rotation.rotate(point.scale(self.scale)) + self.position
+    }
+
+    ///Moves a direction from the transform's local space into the space it is in, ignoring position
+    pub fn transform_vector(&self, vector: Vec3) -> Vec3 {
+        self.rotation.rotate(vector.scale(self.scale))
+    }
+
+    ///Interpolates between self (t = 0) and rhs (t = 1)
+    ///Position and scale are interpolated linearly and rotation spherically
+    pub fn lerp(&self, rhs: &Transform, t: f64) -> Self {
+        Transform {
+            position: self.position.lerp(rhs.position, t),
+            rotation: self.rotation.slerp(rhs.rotation, t),
+            scale: self.scale.lerp(rhs.scale, t),
+        }
     }
 }
 
+///Applies self, then rhs
+///When rhs has a non uniform scale and self has a rotation, the result isn't the same as multiplying their matrices:
+///rhs's scale stretches along self's rotated axes rather than the axes of rhs's space, since the shear that would need can't be stored.
+///Multiply the matrices from to_matrix instead when the exact result is needed
 impl std::ops::Mul for Transform {
     type Output = Self;
     fn mul(self, rhs: Self) -> Self {
-        Transform { matrix: self.matrix.dot(&rhs.matrix) }
+        Transform {
+            position: rhs.transform_point(self.position),
+            rotation: (rhs.rotation * self.rotation).normalised(),
+            scale: self.scale.scale(rhs.scale),
+        }
+    }
+}
+
+impl From<Mat4> for Transform {
+    fn from(matrix: Mat4) -> Self {
+        Transform::from_matrix(&matrix)
+    }
+}
+
+impl From<Transform> for Mat4 {
+    fn from(transform: Transform) -> Self {
+        transform.to_matrix()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EPSILON: f64 = 1e-9;
+
+    fn assert_vec3_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < EPSILON, "{:?} != {:?}", a, b);
+    }
+
+    ///Checks two transforms are the same, where rotations q and -q are the same
+    fn assert_transform_near(a: &Transform, b: &Transform) {
+        assert_vec3_near(a.get_position(), b.get_position());
+        assert_vec3_near(a.get_scale(), b.get_scale());
+        assert!(a.get_rotation().dot(b.get_rotation()).abs() > 1.0 - EPSILON, "{:?} != {:?}", a, b);
+    }
+
+    fn example() -> Transform {
+        Transform::new(Vec3::new(1.0, -2.0, 3.0), Quat::from_euler(0.5, 1.1, -0.3), Vec3::new(2.0, 0.5, 1.5))
+    }
+
+    #[test]
+    fn matrix_round_trip() {
+        let transform = example();
+        assert_transform_near(&Transform::from_matrix(&transform.to_matrix()), &transform);
+
+        //A mirrored transform keeps its mirroring on the x axis
+        let mirrored = Transform::new(Vec3::ZERO, Quat::IDENTITY, Vec3::new(-1.0, 2.0, 3.0));
+        assert_transform_near(&Transform::from_matrix(&mirrored.to_matrix()), &mirrored);
+    }
+
+    #[test]
+    fn points_match_the_matrix() {
+        let transform = example();
+        let point = Vec3::new(0.3, 4.0, -1.0);
+        assert_vec3_near(transform.transform_point(point), transform.to_matrix().transform_point(point));
+        assert_vec3_near(transform.transform_vector(point), transform.to_matrix().transform_vector(point));
+    }
+
+    #[test]
+    fn inverse_undoes_uniform_scale() {
+        let transform = Transform::new(Vec3::new(1.0, -2.0, 3.0), Quat::from_euler(0.5, 1.1, -0.3), Vec3::new(2.0, 2.0, 2.0));
+        let inverse = transform.inverse().unwrap();
+        assert_transform_near(&(transform * inverse), &Transform::origin());
+        assert_transform_near(&(inverse * transform), &Transform::origin());
+
+        let point = Vec3::new(5.0, 0.0, -1.0);
+        assert_vec3_near(inverse.transform_point(transform.transform_point(point)), point);
+        assert_eq!(Transform::from_scale(1.0, 0.0, 1.0).inverse(), None);
+    }
+
+    #[test]
+    fn combining_matches_matrices_for_uniform_scale() {
+        //Only the second transform's scale needs to be uniform, as it is applied after the first's rotation
+        let a = example();
+        let b = Transform::new(Vec3::new(1.0, 0.0, 0.0), Quat::from_euler(0.2, 0.0, 0.7), Vec3::new(3.0, 3.0, 3.0));
+        assert_transform_near(&(a * b), &Transform::from_matrix(&(a.to_matrix() * b.to_matrix())));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn combining_with_non_uniform_scale_keeps_axes_aligned() {
+        let a = Transform::from_rotation(Quat::from_axis_angle(Vec3::Z, std::f64::consts::FRAC_PI_4));
+        let b = Transform::from_scale(2.0, 1.0, 1.0);
+        let combined = a * b;
+        assert_transform_near(&combined, &Transform::new(Vec3::ZERO, a.get_rotation(), b.get_scale()));
+
+        //The matrices stretch the rotated x axis along world x, but the transform stretches it along its own x
+        let half = std::f64::consts::FRAC_1_SQRT_2;
+        assert_vec3_near((a.to_matrix() * b.to_matrix()).transform_vector(Vec3::X), Vec3::new(2.0 * half, half, 0.0));
+        assert_vec3_near(combined.transform_vector(Vec3::X), Vec3::new(2.0 * half, 2.0 * half, 0.0));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_matrix_accessors_match_to_matrix() {
+        let mut transform = Transform::origin();
+        transform.set_matrix(&example().to_matrix());
+        assert_transform_near(&transform, &example());
+        assert_eq!(transform.matrix(), transform.to_matrix());
+    }
+
+    #[test]
+    fn look_at_points_z_at_the_target() {
+        let position = Vec3::new(1.0, 2.0, 3.0);
+        let target = Vec3::new(-4.0, 0.0, 5.0);
+        let transform = Transform::look_at(position, target, Vec3::Y);
+        assert_vec3_near(transform.transform_vector(Vec3::Z), (target - position).normalised());
+        //+y stays as close to up as it can, so +x is level
+        assert!(transform.transform_vector(Vec3::X).y.abs() < EPSILON);
+        assert!(transform.transform_vector(Vec3::Y).y > 0.0);
+    }
+
+    #[test]
+    fn lerp_endpoints_and_midpoint() {
+        let a = Transform::from_pos(0.0, 0.0, 0.0);
+        let b = Transform::new(Vec3::new(2.0, 4.0, 0.0), Quat::from_axis_angle(Vec3::Y, 1.0), Vec3::new(3.0, 3.0, 3.0));
+        assert_transform_near(&a.lerp(&b, 0.0), &a);
+        assert_transform_near(&a.lerp(&b, 1.0), &b);
+        assert_transform_near(&a.lerp(&b, 0.5), &Transform::new(Vec3::new(1.0, 2.0, 0.0), Quat::from_axis_angle(Vec3::Y, 0.5), Vec3::new(2.0, 2.0, 2.0)));
+    }
+}
@@ -0,0 +1,224 @@
+///A rectangle of a surface, from (0, 0) at the bottom left to (1, 1) at the top right
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+///A corner of a viewport, for placing picture in picture views
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+///Which view of a SplitView a point is in
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ViewportHit {
+    ///Index of the view in each nested SplitView, from the outermost
+    ///Empty if the point is over a single camera
+    pub path: Vec<usize>,
+    ///Position of the point within the view, from (0, 0) at the bottom left to (1, 1) at the top right
+    pub position: (f32, f32),
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Viewport::FULL
+    }
+}
+
+impl Viewport {
+    ///The whole surface
+    pub const FULL: Viewport = Viewport { x: 0.0, y: 0.0, width: 1.0, height: 1.0 };
+
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Viewport { x, y, width, height }
+    }
+
+    ///Gets the edges in -1 to 1 space as (x_start, x_end, y_start, y_end)
+    pub(crate) fn to_ndc(self) -> (f32, f32, f32, f32) {
+        (self.x * 2.0 - 1.0, (self.x + self.width) * 2.0 - 1.0, self.y * 2.0 - 1.0, (self.y + self.height) * 2.0 - 1.0)
+    }
+
+    ///Places this viewport inside another, treating the other viewport as the whole surface
+    pub fn within(self, parent: Viewport) -> Viewport {
+        Viewport::new(
+            parent.x + self.x * parent.width,
+            parent.y + self.y * parent.height,
+            self.width * parent.width,
+            self.height * parent.height,
+        )
+    }
+
+    pub fn contains(self, point: (f32, f32)) -> bool {
+        point.0 >= self.x && point.0 < self.x + self.width &&
+        point.1 >= self.y && point.1 < self.y + self.height
+    }
+
+    ///Gets where a point on the surface is within the viewport, from (0, 0) to (1, 1) if it is inside
+    pub fn to_local(self, point: (f32, f32)) -> (f32, f32) {
+        ((point.0 - self.x) / self.width, (point.1 - self.y) / self.height)
+    }
+
+    ///Shrinks the viewport to a width / height aspect ratio in pixels, keeping it centred
+    ///Leaves bars at the top and bottom or at the sides of the original viewport
+    pub fn letterboxed(self, aspect_ratio: f32, surface: (u32, u32)) -> Viewport {
+        let width = self.width * surface.0 as f32;
+        let height = self.height * surface.1 as f32;
+        if width <= 0.0 || height <= 0.0 || aspect_ratio <= 0.0 {
+            return self;
+        }
+
+        if width / height > aspect_ratio {
+            let new_width = self.width * (height * aspect_ratio / width);
+            Viewport::new(self.x + (self.width - new_width) / 2.0, self.y, new_width, self.height)
+        }
+        else {
+            let new_height = self.height * (width / aspect_ratio / height);
+            Viewport::new(self.x, self.y + (self.height - new_height) / 2.0, self.width, new_height)
+        }
+    }
+
+    ///Splits the surface into count viewports side by side, from left to right
+    pub fn horizontal_split(count: usize) -> Vec<Viewport> {
+        let width = 1.0 / count as f32;
+        (0..count).map(|i| Viewport::new(i as f32 * width, 0.0, width, 1.0)).collect()
+    }
+
+    ///Splits the surface into count viewports stacked on top of each other, from top to bottom
+    pub fn vertical_split(count: usize) -> Vec<Viewport> {
+        let height = 1.0 / count as f32;
+        (0..count).map(|i| Viewport::new(0.0, 1.0 - (i + 1) as f32 * height, 1.0, height)).collect()
+    }
+
+    ///Splits the surface into a grid, going left to right along each row from the top row
+    pub fn grid(columns: usize, rows: usize) -> Vec<Viewport> {
+        let width = 1.0 / columns as f32;
+        let height = 1.0 / rows as f32;
+        (0..rows).flat_map(|row| (0..columns).map(move |column| {
+            Viewport::new(column as f32 * width, 1.0 - (row + 1) as f32 * height, width, height)
+        })).collect()
+    }
+
+    ///Creates a small viewport in a corner, for picture in picture
+    ///size is the fraction of the surface it covers along each axis, and margin is the gap to the edges
+    pub fn inset(corner: Corner, size: f32, margin: f32) -> Viewport {
+        let far = 1.0 - size - margin;
+        let (x, y) = match corner {
+            Corner::TopLeft => (margin, far),
+            Corner::TopRight => (far, far),
+            Corner::BottomLeft => (margin, margin),
+            Corner::BottomRight => (far, margin),
+        };
+        Viewport::new(x, y, size, size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Camera, Renderable, SplitView, Transform, View};
+
+    fn assert_close(a: Viewport, b: Viewport) {
+        let close = |x: f32, y: f32| (x - y).abs() < 1e-5;
+        assert!(close(a.x, b.x) && close(a.y, b.y) && close(a.width, b.width) && close(a.height, b.height), "{:?} != {:?}", a, b);
+    }
+
+    fn camera() -> Renderable {
+        Renderable::Camera(Camera::new(Transform::origin(), 3.0))
+    }
+
+    #[test]
+    fn converts_to_ndc() {
+        let viewport = Viewport::new(0.25, 0.5, 0.5, 0.25);
+        assert_eq!(viewport.to_ndc(), (-0.5, 0.5, 0.0, 0.5));
+        assert_eq!(Viewport::FULL.to_ndc(), (-1.0, 1.0, -1.0, 1.0));
+    }
+
+    #[test]
+    fn within_nests_viewports() {
+        let parent = Viewport::new(0.5, 0.0, 0.5, 0.5);
+        assert_close(Viewport::FULL.within(parent), parent);
+        assert_close(Viewport::new(0.5, 0.5, 0.5, 0.5).within(parent), Viewport::new(0.75, 0.25, 0.25, 0.25));
+    }
+
+    #[test]
+    fn splits_cover_surface_in_order() {
+        let horizontal = Viewport::horizontal_split(2);
+        assert_close(horizontal[0], Viewport::new(0.0, 0.0, 0.5, 1.0));
+        assert_close(horizontal[1], Viewport::new(0.5, 0.0, 0.5, 1.0));
+
+        let vertical = Viewport::vertical_split(2);
+        assert_close(vertical[0], Viewport::new(0.0, 0.5, 1.0, 0.5));
+        assert_close(vertical[1], Viewport::new(0.0, 0.0, 1.0, 0.5));
+
+        let grid = Viewport::grid(2, 2);
+        assert_eq!(grid.len(), 4);
+        assert_close(grid[0], Viewport::new(0.0, 0.5, 0.5, 0.5));
+        assert_close(grid[1], Viewport::new(0.5, 0.5, 0.5, 0.5));
+        assert_close(grid[3], Viewport::new(0.5, 0.0, 0.5, 0.5));
+        let area: f32 = grid.iter().map(|v| v.width * v.height).sum();
+        assert!((area - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn letterbox_keeps_aspect_ratio() {
+        //A 16:9 view in a square surface gets bars at the top and bottom
+        let boxed = Viewport::FULL.letterboxed(16.0 / 9.0, (900, 900));
+        assert_close(boxed, Viewport::new(0.0, (1.0 - 9.0 / 16.0) / 2.0, 1.0, 9.0 / 16.0));
+
+        //A square view in a 16:9 surface gets bars at the sides
+        let boxed = Viewport::FULL.letterboxed(1.0, (1600, 900));
+        assert_close(boxed, Viewport::new((1.0 - 900.0 / 1600.0) / 2.0, 0.0, 900.0 / 1600.0, 1.0));
+
+        //Already the right shape
+        assert_close(Viewport::FULL.letterboxed(16.0 / 9.0, (1600, 900)), Viewport::FULL);
+    }
+
+    #[test]
+    fn inset_corners() {
+        assert_close(Viewport::inset(Corner::TopRight, 0.25, 0.05), Viewport::new(0.7, 0.7, 0.25, 0.25));
+        assert_close(Viewport::inset(Corner::BottomLeft, 0.25, 0.05), Viewport::new(0.05, 0.05, 0.25, 0.25));
+    }
+
+    #[test]
+    fn contains_and_local_position() {
+        let viewport = Viewport::new(0.5, 0.5, 0.5, 0.5);
+        assert!(viewport.contains((0.75, 0.5)));
+        assert!(!viewport.contains((0.25, 0.75)));
+        assert!(!viewport.contains((1.0, 0.75)));
+        assert_eq!(viewport.to_local((0.75, 0.625)), (0.5, 0.25));
+    }
+
+    #[test]
+    fn hit_test_prefers_higher_priority() {
+        let split = Renderable::SplitView(SplitView::picture_in_picture(camera(), camera(), Viewport::inset(Corner::TopRight, 0.25, 0.0)));
+
+        let hit = split.get_view_at((0.9, 0.9), (100, 100)).unwrap();
+        assert_eq!(hit.path, vec![1]);
+        assert_eq!(split.get_view_at((0.1, 0.1), (100, 100)).unwrap().path, vec![0]);
+        assert_eq!(split.get_view_at((1.5, 0.5), (100, 100)), None);
+    }
+
+    #[test]
+    fn hit_test_nested_and_letterboxed() {
+        let inner = Renderable::SplitView(SplitView::vertical(vec![camera(), camera()]));
+        let split = Renderable::SplitView(SplitView::new(vec![
+            View::new(camera(), Viewport::new(0.0, 0.0, 0.5, 1.0)),
+            View::new(inner, Viewport::new(0.5, 0.0, 0.5, 1.0)),
+        ]));
+        let hit = split.get_view_at((0.75, 0.25), (100, 100)).unwrap();
+        assert_eq!(hit.path, vec![1, 1]);
+        assert_eq!(hit.position, (0.5, 0.5));
+
+        //A square view in a wide surface leaves the sides uncovered
+        let boxed = Renderable::SplitView(SplitView::new(vec![View::new(camera(), Viewport::FULL).with_aspect_ratio(1.0)]));
+        assert_eq!(boxed.get_view_at((0.1, 0.5), (200, 100)), None);
+        assert_eq!(boxed.get_view_at((0.5, 0.5), (200, 100)).unwrap().position, (0.5, 0.5));
+    }
+}
@@ -1,17 +1,22 @@
+use std::cell::RefCell;
 use std::collections::VecDeque;
 use std::fmt::Debug;
 use std::mem::take;
+use std::rc::Weak;
 
 use glium::glutin::event::StartCause;
-use glium::glutin::event_loop::ControlFlow;
+use glium::glutin::event_loop::{ControlFlow, EventLoopWindowTarget};
 use glium::glutin::event::ElementState;
-use glium::{glutin, SwapBuffersError};
+use glium::{glutin, Surface, SwapBuffersError};
 use glium::glutin::dpi::{PhysicalSize, LogicalSize, PhysicalPosition, LogicalPosition};
 use glium::backend::glutin::DisplayCreationError;
+use glium::backend::Facade;
 use glutin::CreationError;
 use glutin::event::Event;
 
-use crate::{Scene, Frame};
+use crate::{Scene, SceneStack, SceneHost, SceneChange, Transition, Frame, Assets, AssetLoadError, Audio, AudioDevice, PostEffect, PostProcessing, DebugOverlay, LineRenderer, Viewport, ViewportHit, Monitor, FullscreenMode, VSync};
+use crate::shaders::{Texture, RenderError};
+use crate::shaders::shader_priv::{TexturePriv, TextureLoadError};
 
 ///Represents the resolution of a window
 #[derive(Debug)]
@@ -21,6 +26,52 @@ pub enum Resolution {
     Logical (f64, f64)
 }
 
+///How the scene's main camera fills a window that isn't the size the game was designed for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScalingPolicy {
+    ///Draws to the whole window at the window's aspect ratio, so wider windows show more of the scene
+    #[default]
+    Expand,
+    ///Draws to the whole window as if it was the design resolution, stretching the image to fit
+    Stretch(u32, u32),
+    ///Draws at the design resolution's aspect ratio in the middle of the window, with black bars around it
+    Letterbox(u32, u32),
+}
+
+impl ScalingPolicy {
+    ///Gets the part of a surface that the main camera draws to
+    pub fn get_viewport(self, surface: (u32, u32)) -> Viewport {
+        match self {
+            ScalingPolicy::Letterbox(width, height) if height > 0 => Viewport::FULL.letterboxed(width as f32 / height as f32, surface),
+            _ => Viewport::FULL
+        }
+    }
+
+    ///Changes a height / width aspect ratio measured in the surface's pixels to the one the camera should use
+    pub(crate) fn adjust_aspect_ratio(self, aspect_ratio: f32, surface: (u32, u32)) -> f32 {
+        match self {
+            //Measure in design pixels instead, which are stretched to fill the surface
+            ScalingPolicy::Stretch(width, height) if width > 0 && surface.1 > 0 => {
+                aspect_ratio * (surface.0 as f32 * height as f32) / (surface.1 as f32 * width as f32)
+            },
+            _ => aspect_ratio
+        }
+    }
+}
+
+///Finds which view of a scene's main camera a cursor is over, when the scene fills a surface using a scaling policy
+///cursor is in pixels from the top left of the surface
+fn find_cursor_view(scene: Option<&Scene>, cursor: Option<(f64, f64)>, policy: ScalingPolicy, surface: (u32, u32)) -> Option<ViewportHit> {
+    match (scene, cursor) {
+        (Some(scene), Some((x, y))) if surface.0 > 0 && surface.1 > 0 => {
+            //Cursor positions start at the top left, but viewports start at the bottom left
+            let point = (x as f32 / surface.0 as f32, 1.0 - y as f32 / surface.1 as f32);
+            scene.main_camera.view_at(point, policy.get_viewport(surface), surface)
+        },
+        _ => None
+    }
+}
+
 #[derive(Debug)]
 pub enum Position {
     Physical (i32, i32),
@@ -29,7 +80,15 @@ pub enum Position {
 
 ///Trait with callbacks for a window
 pub trait WindowCallback : Debug{
-    fn on_error(&mut self, window: &mut Window, _error: WindowRuntimeError){println!("Closing window");window.close();}
+    fn on_error(&mut self, window: &mut Window, error: WindowRuntimeError){
+        match error {
+            //The window recovers from a lost context by itself
+            WindowRuntimeError::ContextLost => println!("Context lost, recreating"),
+            WindowRuntimeError::AssetReload(e) => println!("Failed to reload asset: {:?}", e),
+            WindowRuntimeError::Render(e) => println!("Failed to draw frame: {:?}", e),
+            _ => {println!("Closing window");window.close();}
+        }
+    }
     fn on_close(&mut self, window: &mut Window){window.close();}
 
     fn on_resize(&mut self, _window: &mut Window, _resolution: Resolution){}
@@ -53,6 +112,22 @@ enum WindowAction {
     UpdateResolution,
 }
 
+///How the window waits between frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FramePacing {
+    ///Sleeps until just before each frame is due then spins for the rest, for accurate timing at the target framerate
+    #[default]
+    SleepAndSpin,
+    ///Only sleeps, using less cpu but often starting frames a little late
+    Sleep,
+    ///Starts each frame as soon as the last one finishes, ignoring the target framerate
+    ///With vsync on, frames are paced by the monitor instead
+    Unlimited,
+}
+
+///How long before a frame is due that SleepAndSpin stops sleeping, as sleeps can overshoot by about this much
+const SPIN_TIME: std::time::Duration = std::time::Duration::from_millis(2);
+
 pub type ScanCode = glium::glutin::event::ScanCode;
 pub type KeyCode = glium::glutin::event::VirtualKeyCode;
 
@@ -64,7 +139,10 @@ pub struct Window
 {
     callbacks: Option<Box<dyn WindowCallback>>,
     pub target_framerate: u64,
-    scene: Option<Scene>,
+    pub frame_pacing: FramePacing,
+    ///When the next frame should start, so that frames don't drift later each time
+    next_frame_time: std::time::Instant,
+    scenes: SceneStack,
 
     actions: VecDeque<WindowAction>,
     pub(crate) frame: Frame,
@@ -72,6 +150,20 @@ pub struct Window
     event_loop: Option<glutin::event_loop::EventLoop<()>>,
     event_loop_started: bool,
     pub(crate) display: glium::Display,
+
+    ///Stored so that the window can be recreated if the context is lost
+    title: String,
+    icon: Option<glutin::window::Icon>,
+    recovery: ContextRecovery,
+    vsync: VSync,
+    ///Every texture created for this window, so that they can be reuploaded if the context is lost
+    pub(crate) textures: RefCell<Vec<Weak<TexturePriv>>>,
+    assets: Assets,
+    audio_device: Box<dyn AudioDevice>,
+    post_processing: PostProcessing,
+    debug_overlay: DebugOverlay,
+    debug_renderer: LineRenderer,
+    pub(crate) scaling_policy: ScalingPolicy,
 }
 
 ///Represents an error that can occur with a window
@@ -122,48 +214,195 @@ pub enum WindowIconChangeError {
 }
 pub enum WindowRuntimeError {
     ContextLost,
+    ContextRecreation(WindowCreationError),
+    TextureReload(TextureLoadError),
+    ///A watched asset changed on disk but could not be reloaded
+    ///The asset keeps its previous data
+    AssetReload(AssetLoadError),
+    ///Part of a frame couldn't be drawn, so the rest of the scene was skipped
+    ///Drawing is tried again next frame
+    Render(RenderError),
+}
+
+///Creates a display with the context settings used for every display the engine creates
+fn create_display(window_builder: glutin::window::WindowBuilder, vsync: VSync, event_loop: &EventLoopWindowTarget<()>) -> Result<glium::Display, WindowCreationError> {
+    let context_builder = glutin::ContextBuilder::new().with_depth_buffer(24).with_vsync(vsync.is_enabled());
+    let display = glium::Display::new(window_builder, context_builder, event_loop).map_err(WindowCreationError::from)?;
+    //Modes the context builder can't set fall back to plain vsync if they can't be applied
+    vsync.apply(&display);
+    Ok(display)
+}
+
+///Creates the display a window draws to
+///Context recovery goes through this so that it can be run on a headless context in tests
+pub(crate) trait DisplayFactory {
+    type Display: Facade;
+    fn create_display(&mut self) -> Result<Self::Display, WindowCreationError>;
+}
+
+///Creates a display with a new os window matching an existing one
+struct WindowDisplayFactory<'a> {
+    display: &'a glium::Display,
+    title: &'a str,
+    icon: &'a Option<glutin::window::Icon>,
+    vsync: VSync,
+    event_loop: &'a EventLoopWindowTarget<()>,
+}
+
+impl DisplayFactory for WindowDisplayFactory<'_> {
+    type Display = glium::Display;
+
+    fn create_display(&mut self) -> Result<glium::Display, WindowCreationError> {
+        let window_builder = {
+            let gl_window = self.display.gl_window();
+            let window = gl_window.window();
+            let mut window_builder = glutin::window::WindowBuilder::new()
+                .with_title(self.title)
+                .with_inner_size(window.inner_size())
+                .with_fullscreen(window.fullscreen())
+                .with_window_icon(self.icon.clone());
+            if let Ok(pp) = window.outer_position() {
+                window_builder = window_builder.with_position(pp);
+            }
+            window_builder
+        };
+
+        create_display(window_builder, self.vsync, self.event_loop)
+    }
+}
+
+///The gpu state owned by a window, which has to be rebuilt when its display is replaced
+pub(crate) struct GpuState<'a> {
+    pub textures: &'a RefCell<Vec<Weak<TexturePriv>>>,
+    pub scenes: &'a mut SceneStack,
+    pub post_processing: &'a mut PostProcessing,
+    pub debug_overlay: &'a mut DebugOverlay,
+    pub debug_renderer: &'a mut LineRenderer,
+}
+
+///Tracks whether a window's display needs replacing, and moves its gpu state onto the new one
+#[derive(Debug, Default)]
+pub(crate) struct ContextRecovery {
+    ///Set when the gl context has been lost and needs to be recreated
+    lost: bool,
+    ///Set when the display needs recreating with new context settings, such as vsync
+    outdated: bool,
+}
+
+impl ContextRecovery {
+    pub(crate) fn is_needed(&self) -> bool {
+        self.lost || self.outdated
+    }
+
+    ///Creates a new display if one is needed, reuploads every live texture onto it
+    ///and drops all other gpu assets so that they are recreated on the next render
+    ///Returns the new display, or None if none was needed or it couldn't be created, in which case the next call tries again
+    ///Textures that can't be reloaded are left blank at their old size, with an error returned for each,
+    ///so nothing is left using the old context
+    pub(crate) fn recover<F: DisplayFactory>(&mut self, factory: &mut F, gpu: GpuState) -> (Option<F::Display>, Vec<WindowRuntimeError>) {
+        if !self.is_needed() {
+            return (None, Vec::new());
+        }
+
+        let display = match factory.create_display() {
+            Ok(d) => d,
+            Err(e) => return (None, vec![WindowRuntimeError::ContextRecreation(e)])
+        };
+        self.lost = false;
+        self.outdated = false;
+
+        //Reupload textures that are still in use and forget about ones that have been dropped
+        //Every texture is tried, so one bad texture doesn't leave the rest on the old context
+        gpu.textures.borrow_mut().retain(|t| t.strong_count() > 0);
+        let mut errors = Vec::new();
+        for texture in gpu.textures.borrow().iter() {
+            if let Some(texture) = texture.upgrade() {
+                if let Err(e) = Texture::reload(&texture, &display) {
+                    errors.push(WindowRuntimeError::TextureReload(e));
+                }
+            }
+        }
+
+        gpu.scenes.invalidate_assets();
+        gpu.post_processing.invalidate_assets();
+        gpu.debug_overlay.invalidate_assets();
+        gpu.debug_renderer.invalidate_assets();
+
+        (Some(display), errors)
+    }
+}
+
+impl SceneHost for Window {
+    fn get_scenes(&mut self) -> &mut SceneStack {
+        &mut self.scenes
+    }
+
+    fn insert_scene(&mut self, scene: &mut Scene) {
+        scene.insert(self);
+    }
+
+    fn remove_scene(&mut self, scene: Scene) {
+        scene.remove(self);
+    }
 }
 
 impl Window {
     ///Constructs a new window
     pub fn new (callbacks: Option<Box<dyn WindowCallback>>, resolution: Resolution, title: String) -> Result<Self, WindowCreationError> {
         let event_loop = glutin::event_loop::EventLoop::new();
-        let mut window_builder = glutin::window::WindowBuilder::new().with_title(title);
+        let mut window_builder = glutin::window::WindowBuilder::new().with_title(title.clone());
         window_builder = match resolution {
-            Resolution::Fullscreen => window_builder.with_fullscreen(Some(glutin::window::Fullscreen::Borderless(event_loop.available_monitors().next()))),
-            Resolution::Physical (width, height) => window_builder.with_inner_size(glutin::dpi::Size::Physical(PhysicalSize{width:width, height:height})),
-            Resolution::Logical (width, height) => window_builder.with_inner_size(glutin::dpi::Size::Logical(LogicalSize{width:width, height:height}))
-        };
-        let context_builder = glutin::ContextBuilder::new().with_depth_buffer(24);
-        let display = match glium::Display::new(window_builder, context_builder, &event_loop) {
-            Ok (d) => d,
-            Err (e) => return Err(WindowCreationError::from(e))
+            Resolution::Fullscreen => window_builder.with_fullscreen(Some(glutin::window::Fullscreen::Borderless(event_loop.primary_monitor()))),
+            Resolution::Physical (width, height) => window_builder.with_inner_size(glutin::dpi::Size::Physical(PhysicalSize{width, height})),
+            Resolution::Logical (width, height) => window_builder.with_inner_size(glutin::dpi::Size::Logical(LogicalSize{width, height}))
         };
+        let display = create_display(window_builder, VSync::default(), &event_loop)?;
         
         let frame = Frame {
             display: crate::Display {
                 resolution: display.get_framebuffer_dimensions(),
                 position: match display.gl_window().window().inner_position() {
-                    Ok(pp) => (pp.x.clone(), pp.y.clone()),
+                    Ok(pp) => (pp.x, pp.y),
                     Err(_) => (0, 0)
                 },
+                scale_factor: display.gl_window().window().scale_factor(),
+                monitor: display.gl_window().window().current_monitor().map(Monitor::from),
+                fullscreen: display.gl_window().window().fullscreen().map(FullscreenMode::from_glutin),
+                vsync: VSync::default(),
             },
             time: Default::default(),
             input: Default::default(),
+            audio: Audio::new(),
+            debug: Default::default(),
         };
+        let audio_device = crate::open_default_audio_device(&frame.audio);
 
         Ok (
             Window {
                 callbacks: Some(callbacks.unwrap_or(Box::new(WindowCallbackDefault{}))),
                 target_framerate: 60,
-                scene: None,
+                frame_pacing: Default::default(),
+                next_frame_time: std::time::Instant::now(),
+                scenes: Default::default(),
 
                 actions: VecDeque::new(),
-                frame: frame,
+                frame,
 
                 event_loop: Some(event_loop), 
                 event_loop_started: false,
-                display: display,
+                display,
+
+                title,
+                icon: None,
+                recovery: Default::default(),
+                vsync: VSync::default(),
+                textures: RefCell::new(Vec::new()),
+                assets: Default::default(),
+                audio_device,
+                post_processing: Default::default(),
+                debug_overlay: Default::default(),
+                debug_renderer: Default::default(),
+                scaling_policy: Default::default(),
             }
         )
     }
@@ -178,14 +417,80 @@ impl Window {
         self.actions.push_back(WindowAction::SetTitle(title));
     }
 
-    ///Gets the scene the window is running
-    pub fn get_scene (&self) -> &Option<Scene> {
-        &self.scene
+    ///Gets the scene at the top of the window's scene stack, ignoring any scene that is leaving in a transition
+    pub fn get_scene (&self) -> Option<&Scene> {
+        self.scenes.get_top()
+    }
+
+    ///Gets the top scene mutably, for example to pause it before pushing a menu over it
+    pub fn get_scene_mut (&mut self) -> Option<&mut Scene> {
+        self.scenes.get_top_mut()
+    }
+
+    ///Whether a scene transition is playing or scene changes are waiting to be made
+    pub fn is_changing_scene (&self) -> bool {
+        self.scenes.is_changing()
+    }
+
+    ///Gets the window's asset registry
+    pub fn assets (&self) -> &Assets {
+        &self.assets
+    }
+
+    ///Gets the window's asset registry mutably, for loading new assets
+    pub fn assets_mut (&mut self) -> &mut Assets {
+        &mut self.assets
+    }
+
+    ///Gets the window's audio controller
+    pub fn audio (&self) -> &Audio {
+        &self.frame.audio
+    }
+
+    ///Replaces the device that sound is played through
+    ///A NullAudioDevice can be used to run without sound hardware
+    pub fn set_audio_device (&mut self, device: Box<dyn AudioDevice>) {
+        self.audio_device = device;
+    }
+
+    ///Adds an effect to be applied to the whole frame after the scene is drawn
+    ///Effects are applied in the order they are added
+    pub fn add_post_effect (&mut self, effect: PostEffect) {
+        self.post_processing.effects.push(effect);
+    }
+
+    ///Replaces every post processing effect
+    pub fn set_post_effects (&mut self, effects: Vec<PostEffect>) {
+        self.post_processing.effects = effects;
+    }
+
+    ///Gets the post processing effects, which can be changed to animate them
+    pub fn get_post_effects_mut (&mut self) -> &mut Vec<PostEffect> {
+        &mut self.post_processing.effects
+    }
+
+    ///Gets the debug overlay, to show it or change its toggle key
+    pub fn get_debug_overlay_mut (&mut self) -> &mut DebugOverlay {
+        &mut self.debug_overlay
+    }
+
+    ///Blocks until all assets that are currently loading have finished
+    pub fn wait_for_assets (&mut self) {
+        self.assets.wait(&self.display, &self.textures);
     }
 
     pub fn set_icon (&mut self, path: String) -> Result<(), WindowIconChangeError> {
-        let icon_image = match image::open(path) {
-            Ok(i) => i,
+        self.set_icon_image(image::open(path))
+    }
+
+    ///Sets the window's icon from the contents of an image file, such as one embedded with include_bytes!
+    pub fn set_icon_from_bytes (&mut self, bytes: &[u8]) -> Result<(), WindowIconChangeError> {
+        self.set_icon_image(image::load_from_memory(bytes))
+    }
+
+    fn set_icon_image (&mut self, icon_image: image::ImageResult<image::DynamicImage>) -> Result<(), WindowIconChangeError> {
+        let icon_image = match icon_image {
+            Ok(i) => i.to_rgba8(),
             Err(e) => match e {
                 image::ImageError::IoError(e) => return Err(WindowIconChangeError::Io(e)),
                 _ => return Err(WindowIconChangeError::ImageError)
@@ -193,7 +498,7 @@ impl Window {
         };
 
         let icon_r = glutin::window::Icon::from_rgba(
-            icon_image.as_rgba8().unwrap().clone().into_vec(),
+            icon_image.as_raw().clone(),
             icon_image.width(),
             icon_image.height()
         );
@@ -207,21 +512,43 @@ impl Window {
             }
         }
 
-        self.display.gl_window().window().set_window_icon(Some(icon));
+        self.display.gl_window().window().set_window_icon(Some(icon.clone()));
+        self.icon = Some(icon);
 
         Ok(())
     }
 
-    ///Sets the scene the window is running
+    ///Replaces every scene in the window with one scene straight away, cancelling any transition or waiting changes
+    ///The old scenes are removed from the top down before the new scene is inserted
     pub fn set_scene (&mut self, mut new_scene: Scene) {
-        if let Some(old_scene) = take(&mut self.scene) {
-            self.scene = None;
-            old_scene.remove(self);
-        }
+        self.remove_scenes();
 
         new_scene.insert(self);
+        self.scenes.push(new_scene, Transition::Cut);
+        self.scenes.finish_transition();
+    }
 
-        self.scene = Some(new_scene);
+    ///Adds a scene on top of the others, such as a pause menu
+    ///Scene changes are made at the start of the next frame, after any transition already playing has finished
+    pub fn push_scene (&mut self, scene: Scene, transition: Transition) {
+        self.scenes.queue(SceneChange::Push(scene, transition));
+    }
+
+    ///Removes the top scene, showing the one below it
+    pub fn pop_scene (&mut self, transition: Transition) {
+        self.scenes.queue(SceneChange::Pop(transition));
+    }
+
+    ///Replaces the top scene with another, such as going from a title screen to the game
+    pub fn replace_scene (&mut self, scene: Scene, transition: Transition) {
+        self.scenes.queue(SceneChange::Replace(scene, transition));
+    }
+
+    ///Removes every scene from the top down
+    fn remove_scenes(&mut self) {
+        for scene in self.scenes.clear() {
+            scene.remove(self);
+        }
     }
 
     pub fn set_resolution(&mut self, resolution: &Resolution) {
@@ -242,6 +569,57 @@ impl Window {
         self.actions.push_back(WindowAction::UpdateResolution);
     }
 
+    ///Sets how the scene is fitted to the window when it isn't the design resolution
+    pub fn set_scaling_policy(&mut self, policy: ScalingPolicy) {
+        self.scaling_policy = policy;
+    }
+
+    pub fn get_scaling_policy(&self) -> ScalingPolicy {
+        self.scaling_policy
+    }
+
+    ///Gets every monitor connected to the computer
+    pub fn get_monitors(&self) -> Vec<Monitor> {
+        self.display.gl_window().window().available_monitors().map(Monitor::from).collect()
+    }
+
+    ///Gets the monitor the window is on, if known
+    pub fn get_current_monitor(&self) -> Option<Monitor> {
+        self.display.gl_window().window().current_monitor().map(Monitor::from)
+    }
+
+    ///Gets the monitor the operating system treats as the main one, if known
+    pub fn get_primary_monitor(&self) -> Option<Monitor> {
+        self.display.gl_window().window().primary_monitor().map(Monitor::from)
+    }
+
+    ///Makes the window fullscreen, or windowed again if None
+    pub fn set_fullscreen(&mut self, mode: Option<FullscreenMode>) {
+        self.display.gl_window().window().set_fullscreen(mode.map(|m| m.to_glutin()));
+        self.actions.push_back(WindowAction::UpdateResolution);
+    }
+
+    ///Sets whether frames wait for the monitor to refresh
+    ///Changing vsync creates a new os window with a new gl context before the next frame, as the context can't change it in place
+    ///The new window keeps the title, icon, size, position and fullscreen mode, but other window state is lost,
+    ///such as being maximised or minimised, focus, and cursor grab and visibility
+    ///Gpu assets are recreated in the same way as after a context loss
+    pub fn set_vsync(&mut self, vsync: VSync) {
+        if vsync != self.vsync {
+            self.vsync = vsync;
+            self.recovery.outdated = true;
+        }
+    }
+
+    pub fn get_vsync(&self) -> VSync {
+        self.vsync
+    }
+
+    ///Gets the number of physical pixels per logical pixel
+    pub fn get_scale_factor(&self) -> f64 {
+        self.display.gl_window().window().scale_factor()
+    }
+
     pub fn set_position(&mut self, position: &Position) {
         //println!("{:?}", position);
         match position {
@@ -258,27 +636,115 @@ impl Window {
     fn run_actions(&mut self) -> Result<(), ()> {
         for action in self.actions.iter() {
             match action {
-                //TODO: call scene's quit method first
                 WindowAction::Close => {return Err(())},
-                WindowAction::SetTitle(title) => self.display.gl_window().window().set_title(&title[..]),
+                WindowAction::SetTitle(title) => {
+                    self.display.gl_window().window().set_title(&title[..]);
+                    self.title = title.clone();
+                },
 
-                WindowAction::UpdatePosition => self.frame.display.position = match self.display.gl_window().window().inner_position() {
-                    Ok(pp) => (pp.x, pp.y),
-                    Err(_) => (0, 0)
+                WindowAction::UpdatePosition => {
+                    let gl_window = self.display.gl_window();
+                    let window = gl_window.window();
+                    self.frame.display.position = match window.inner_position() {
+                        Ok(pp) => (pp.x, pp.y),
+                        Err(_) => (0, 0)
+                    };
+                    self.frame.display.monitor = window.current_monitor().map(Monitor::from);
                 },
                 WindowAction::UpdateResolution => {
-                    let ps = self.display.gl_window().window().inner_size();
+                    let gl_window = self.display.gl_window();
+                    let window = gl_window.window();
+                    let ps = window.inner_size();
                     self.frame.display.resolution = (ps.width, ps.height);
+                    self.frame.display.scale_factor = window.scale_factor();
+                    self.frame.display.monitor = window.current_monitor().map(Monitor::from);
+                    self.frame.display.fullscreen = window.fullscreen().map(FullscreenMode::from_glutin);
+                    self.frame.display.vsync = self.vsync;
                 }
             }
         }
         self.actions.clear();
-        return Ok(());
+        Ok(())
+    }
+
+    ///Updates the scene with reloaded assets and reports any reload errors to callbacks
+    fn apply_reloads(&mut self) {
+        for handle in self.assets.take_reloaded_meshes() {
+            if let Some(data) = self.assets.get(handle) {
+                for scene in self.scenes.scenes_mut() {
+                    scene.reload_mesh(handle, data);
+                }
+            }
+        }
+
+        for error in self.assets.take_reload_errors() {
+            if let Some(mut callbacks) = take(&mut self.callbacks) {
+                callbacks.on_error(self, WindowRuntimeError::AssetReload(error));
+                self.callbacks = Some(callbacks);
+            }
+        }
+    }
+
+    ///Simulates the gl context being lost
+    ///The window will recover in the same way as it would from a real context loss
+    pub fn simulate_context_loss(&mut self) {
+        self.lose_context();
+    }
+
+    ///Notifies callbacks that the context has been lost and marks it to be recreated
+    fn lose_context(&mut self) {
+        if let Some(mut callbacks) = take(&mut self.callbacks) {
+            callbacks.on_error(self, WindowRuntimeError::ContextLost);
+            self.callbacks = Some(callbacks);
+        }
+        self.recovery.lost = true;
+    }
+
+    ///Recreates the display and all gpu assets if the context has been lost or its settings have changed
+    ///If the display can't be created it is tried again next frame
+    fn recover_context(&mut self, event_loop: &EventLoopWindowTarget<()>) {
+        if !self.recovery.is_needed() {
+            return;
+        }
+
+        let mut factory = WindowDisplayFactory {
+            display: &self.display,
+            title: &self.title,
+            icon: &self.icon,
+            vsync: self.vsync,
+            event_loop,
+        };
+        let (display, errors) = self.recovery.recover(&mut factory, GpuState {
+            textures: &self.textures,
+            scenes: &mut self.scenes,
+            post_processing: &mut self.post_processing,
+            debug_overlay: &mut self.debug_overlay,
+            debug_renderer: &mut self.debug_renderer,
+        });
+
+        if let Some(display) = display {
+            self.display = display;
+            self.actions.push_back(WindowAction::UpdateResolution);
+            self.actions.push_back(WindowAction::UpdatePosition);
+        }
+
+        for error in errors {
+            if let Some(mut callbacks) = take(&mut self.callbacks) {
+                callbacks.on_error(self, error);
+                self.callbacks = Some(callbacks);
+            }
+        }
     }
 
     ///Called every frame, is responsible for calling logic and rendering code
     fn tick(&mut self, control_flow: &mut ControlFlow) {
 
+        if self.frame_pacing == FramePacing::SleepAndSpin {
+            while std::time::Instant::now() < self.next_frame_time {
+                std::hint::spin_loop();
+            }
+        }
+
         let frame_time = std::time::Instant::now();
 
         if self.event_loop_started {
@@ -292,13 +758,24 @@ impl Window {
 
         self.frame.time.frame_time = frame_time;
 
+        self.audio_device.update(&self.frame.audio, self.frame.time.delta_time);
+
+        //Upload any assets that finished loading since the last frame
+        self.assets.process(&self.display, &self.textures);
+        self.apply_reloads();
+
         //Set window to wait for next frame
-        let next_frame_time = frame_time +
-            std::time::Duration::from_nanos(1_000_000_000 / self.target_framerate);
-       *control_flow = glutin::event_loop::ControlFlow::WaitUntil(next_frame_time);
+        self.schedule_next_frame(frame_time, control_flow);
+
+        self.update_cursor_view();
 
         if let Some(mut callbacks) = take(&mut self.callbacks) {
-            callbacks.on_tick(self, &self.frame.clone());
+            //Move the stats into the copy passed to callbacks rather than copying their history every frame
+            let stats = take(&mut self.frame.time.stats);
+            let mut frame = self.frame.clone();
+            frame.time.stats = stats;
+            callbacks.on_tick(self, &frame);
+            self.frame.time.stats = frame.time.stats;
 
             //Handle actions queued by on_tick() call
             //Prevents another run of the event loop from happening if close() was called during the callback
@@ -307,46 +784,138 @@ impl Window {
             match self.run_actions() {
                 Ok(()) => (),
                 Err(()) => {
-                    *control_flow = glutin::event_loop::ControlFlow::Exit;
+                    self.exit(control_flow);
                     return;
                 }
             }
 
             self.callbacks = Some(callbacks);
         }
+
+        SceneStack::update(self, self.frame.time.delta_time.as_secs_f64());
         
-        if let Some(mut scene) = take(&mut self.scene) {
+        let mut update_time = frame_time.elapsed();
+        let mut render_time = std::time::Duration::ZERO;
 
-            scene.tick(&self);
+        if !self.scenes.is_empty() {
+            let mut scenes = take(&mut self.scenes);
+
+            scenes.tick(self);
+            let mut debug_overlay = take(&mut self.debug_overlay);
+            if let Some(scene) = scenes.get_top_mut() {
+                debug_overlay.update(&self.frame, scene);
+            }
+            update_time = frame_time.elapsed();
 
             let mut target = self.display.draw();
+            //Cameras only clear their own part of the window, so clear the bars around a letterboxed scene
+            if let ScalingPolicy::Letterbox(..) = self.scaling_policy {
+                target.clear_color_and_depth((0.0, 0.0, 0.0, 1.0), 1.0);
+            }
             
-            scene.render(&mut target, &self);
+            let result = if self.post_processing.is_empty() {
+                scenes.render(&mut target, self)
+            }
+            else {
+                let mut post_processing = take(&mut self.post_processing);
+                let result = post_processing.render(&mut target, &self.display, |surface| scenes.render(surface, self));
+                self.post_processing = post_processing;
+                result
+            };
+
+            //Drawn after post processing so debug drawing and the overlay are always readable
+            let mut results = vec![result];
+            if let Some(scene) = scenes.get_top() {
+                let mut debug_renderer = take(&mut self.debug_renderer);
+                results.push(self.frame.debug.render(&mut target, scene, self, &mut debug_renderer));
+                self.debug_renderer = debug_renderer;
+                results.push(debug_overlay.render(&mut target, scene, self));
+            }
+            self.debug_overlay = debug_overlay;
+
+            render_time = frame_time.elapsed() - update_time;
 
             match target.finish() {
                 Ok(_) => (),
                 Err(e) =>  match e {
-                    SwapBuffersError::ContextLost => {
-                        if let Some(mut callbacks) = take(&mut self.callbacks) {
-                            callbacks.on_error(self, WindowRuntimeError::ContextLost);
-                            self.callbacks = Some(callbacks);
-                        }
-                        //TODO: recreate context instead of crashing
-                        todo!();
-                    }
+                    SwapBuffersError::ContextLost => self.lose_context(),
                     SwapBuffersError::AlreadySwapped => panic!("Buffers swapped multiple times - was target.finish called more than once?"),
                 }
             }
-            self.scene = Some(scene);
+            self.scenes = scenes;
+
+            for e in results.into_iter().filter_map(Result::err) {
+                if let Some(mut callbacks) = take(&mut self.callbacks) {
+                    callbacks.on_error(self, WindowRuntimeError::Render(e));
+                    self.callbacks = Some(callbacks);
+                }
+            }
         }
 
         self.frame.input.scancodes_this_frame.clear();
         self.frame.input.keycodes_this_frame.clear();
+        self.frame.debug.clear();
+
+        if self.frame.time.frames > 0 {
+            let delta_time = self.frame.time.delta_time;
+            self.frame.time.stats.record(delta_time, update_time, render_time);
+        }
+
+    }
+
+    ///Updates the frame to a new window size, then tells the callbacks and scenes
+    fn resize(&mut self, size: PhysicalSize<u32>) {
+        self.frame.display.resolution = (size.width, size.height);
+        //The letterbox bars move with the window, so the cursor can be over a different view without moving
+        self.update_cursor_view();
+
+        if let Some(mut callbacks) = take(&mut self.callbacks) {
+            callbacks.on_resize(self, Resolution::Physical(size.width, size.height));
+            self.callbacks = Some(callbacks);
+        }
+        let mut scenes = take(&mut self.scenes);
+        for scene in scenes.scenes_mut() {
+            scene.resize(&self.frame);
+        }
+        self.scenes = scenes;
+    }
+
+    ///Works out when the next frame should start and sets the event loop to wake up for it
+    fn schedule_next_frame(&mut self, frame_time: std::time::Instant, control_flow: &mut ControlFlow) {
+        if self.frame_pacing == FramePacing::Unlimited || self.target_framerate == 0 {
+            *control_flow = ControlFlow::Poll;
+            return;
+        }
+
+        //Step from when this frame was due rather than when it started, so that late frames don't push back every frame after them
+        let period = std::time::Duration::from_nanos(1_000_000_000 / self.target_framerate);
+        self.next_frame_time += period;
+        //Start again from now after a long stall, such as the window being dragged, instead of rushing to catch up
+        if self.next_frame_time + period < frame_time {
+            self.next_frame_time = frame_time + period;
+        }
+
+        *control_flow = match self.frame_pacing {
+            FramePacing::SleepAndSpin => ControlFlow::WaitUntil(self.next_frame_time.checked_sub(SPIN_TIME).unwrap_or(self.next_frame_time)),
+            _ => ControlFlow::WaitUntil(self.next_frame_time)
+        };
+    }
 
+    ///Finds which view of the top scene's main camera the cursor is over
+    fn update_cursor_view(&mut self) {
+        let cursor_view = find_cursor_view(self.scenes.get_top(), self.frame.input.cursor_position, self.scaling_policy, self.frame.display.resolution);
+        self.frame.input.cursor_view = cursor_view;
+    }
+
+    ///Removes every scene so that their on_remove callbacks run, then stops the event loop
+    fn exit(&mut self, control_flow: &mut ControlFlow) {
+        self.remove_scenes();
+        *control_flow = ControlFlow::Exit;
     }
 
     ///Runs the window's event loop
     ///Can only be called once on a given window
+    #[allow(clippy::result_unit_err)]
     pub fn main_loop (mut self) -> Result<(), ()> {
 
         if self.event_loop_started {return Err(())}
@@ -358,7 +927,7 @@ impl Window {
         
         self.event_loop = None;
 
-        event_loop.run(move |ev, _, control_flow| {
+        event_loop.run(move |ev, event_loop, control_flow| {
 
             if !self.event_loop_started {
                 self.tick(control_flow);
@@ -369,11 +938,13 @@ impl Window {
             match self.run_actions() {
                 Ok(()) => (),
                 Err(()) => {
-                    *control_flow = glutin::event_loop::ControlFlow::Exit;
+                    self.exit(control_flow);
                     return;
                 }
             }
 
+            self.recover_context(event_loop);
+
             //Handle events
             match ev {
 
@@ -387,12 +958,21 @@ impl Window {
                         }
                     },
                     glutin::event::WindowEvent::Resized(ps) => {
-                        if let Some(mut callbacks) = take(&mut self.callbacks) {
-                            callbacks.on_resize(&mut self, Resolution::Physical(ps.width, ps.height));
-                            self.callbacks = Some(callbacks);
-                        }
+                        self.resize(ps);
+                    },
+                    glutin::event::WindowEvent::ScaleFactorChanged { scale_factor, new_inner_size } => {
+                        self.frame.display.scale_factor = scale_factor;
+                        self.resize(*new_inner_size);
+                    },
+                    glutin::event::WindowEvent::CursorMoved { position, .. } => {
+                        self.frame.input.cursor_position = Some((position.x, position.y));
+                    },
+                    glutin::event::WindowEvent::CursorLeft { .. } => {
+                        self.frame.input.cursor_position = None;
                     },
                     glutin::event::WindowEvent::Moved(pp) => {
+                        //The window may have moved onto another monitor
+                        self.actions.push_back(WindowAction::UpdatePosition);
                         if let Some(mut callbacks) = take(&mut self.callbacks) {
                             callbacks.on_move(&mut self, Position::Physical(pp.x, pp.y));
                             self.callbacks = Some(callbacks);
@@ -422,14 +1002,8 @@ impl Window {
                         _ => ()
                     }
                 },
-                Event::NewEvents(c) => match c {
-                    StartCause::ResumeTimeReached { start: _, requested_resume: _ } => {
-                        self.tick(control_flow);
-                        if *control_flow == glutin::event_loop::ControlFlow::Exit {
-                            return;
-                        }
-                    },
-                    _ => ()
+                Event::NewEvents(StartCause::ResumeTimeReached { start: _, requested_resume: _ } | StartCause::Poll) => {
+                    self.tick(control_flow);
                 },
                 Event::MainEventsCleared => {},
                 /* 
@@ -444,4 +1018,212 @@ impl Window {
             }
         });
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use glium::backend::Context;
+    use glium::{Program, VertexBuffer};
+
+    use super::*;
+    use crate::{Camera, GameObject, Mesh, Renderable, SceneCallback, SceneCallbackDefault, Transform, headless};
+    use crate::shaders::{self, BlendMode, Shader};
+    use crate::shaders::shader_priv::{AssetCreationError, ShaderPriv, ShaderUniforms, TextureSource};
+
+    ///Creates headless contexts, failing a set number of times first
+    struct HeadlessFactory {
+        failures: usize,
+    }
+
+    impl DisplayFactory for HeadlessFactory {
+        type Display = Rc<Context>;
+
+        fn create_display(&mut self) -> Result<Rc<Context>, WindowCreationError> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err(WindowCreationError::NoSupportedBackend);
+            }
+            headless::create_context().ok_or(WindowCreationError::NoSupportedBackend)
+        }
+    }
+
+    ///A shader holding a program and a buffer created on a given context, like real shaders cache theirs
+    #[derive(Debug)]
+    struct CachingShader {
+        program: Option<Program>,
+        vertices: Option<Rc<VertexBuffer<shaders::ClearVertex>>>,
+    }
+
+    impl ShaderPriv for CachingShader {
+        fn get_vertex_shader(&self) -> String {
+            shaders::CLEAR_VERTEX_SHADER.to_string()
+        }
+        fn get_fragment_shader(&self) -> String {
+            shaders::GRADIENT_FRAGMENT_SHADER.to_string()
+        }
+        fn get_program(&self) -> &Option<Program> {
+            &self.program
+        }
+        fn create_assets(&mut self, _display: &glium::Display) -> Result<(), AssetCreationError> {
+            Ok(())
+        }
+        fn invalidate_assets(&mut self) {
+            self.program = None;
+            self.vertices = None;
+        }
+        fn get_uniforms(&self, _camera_mat: &Transform, _mesh_mat: &Transform, _fov: f32, _aspect_ratio: f32, _zfar: f32, _znear: f32, _obj_mat: &Transform, _out: &mut ShaderUniforms) {}
+        fn get_projection(&self, _fov: f32, _aspect_ratio: f32, _zfar: f32, _znear: f32) -> [[f32; 4]; 4] {
+            [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]]
+        }
+        fn get_blend_mode(&self) -> BlendMode {
+            BlendMode::Opaque
+        }
+    }
+    impl Shader for CachingShader {}
+
+    #[test]
+    fn recovery_moves_gpu_state_to_new_context() {
+        let Some(old) = headless::create_context() else {
+            println!("Skipping, no headless gl context available");
+            return;
+        };
+
+        let textures = RefCell::new(Vec::new());
+        let kept = Texture::from_image(TextureSource::Empty(4, 2), image::RgbaImage::new(4, 2), &old, &textures).unwrap();
+        //Its source can't be loaded again, so it should be left blank
+        let broken = Texture::from_image(TextureSource::Path("missing.png".to_string()), image::RgbaImage::new(3, 5), &old, &textures).unwrap();
+        drop(Texture::from_image(TextureSource::Empty(1, 1), image::RgbaImage::new(1, 1), &old, &textures).unwrap());
+
+        let vertices = Rc::new(VertexBuffer::new(&old, &shaders::CLEAR_VERTICES).unwrap());
+        let cached_vertices = Rc::downgrade(&vertices);
+        let shader = CachingShader {
+            program: Some(Program::from_source(&old, shaders::CLEAR_VERTEX_SHADER, shaders::GRADIENT_FRAGMENT_SHADER, None).unwrap()),
+            vertices: Some(vertices),
+        };
+        let mut object = GameObject::new(None, "cached".to_string(), Transform::origin());
+        object.meshes.push((Transform::origin(), Mesh::cube(Box::new(shader))));
+        let mut scene = Scene::new(Box::new(SceneCallbackDefault {}), Renderable::Camera(Camera::new(Transform::origin(), 3.0)));
+        scene.add_object(object);
+        let mut scenes = SceneStack::default();
+        scenes.push(scene, Transition::Cut);
+
+        let (mut post_processing, mut debug_overlay, mut debug_renderer) = Default::default();
+        let mut recovery = ContextRecovery { lost: true, outdated: false };
+        let mut factory = HeadlessFactory { failures: 1 };
+        let mut recover = |recovery: &mut ContextRecovery, factory: &mut HeadlessFactory| recovery.recover(factory, GpuState {
+            textures: &textures,
+            scenes: &mut scenes,
+            post_processing: &mut post_processing,
+            debug_overlay: &mut debug_overlay,
+            debug_renderer: &mut debug_renderer,
+        });
+
+        //A failed recreation is reported and leaves everything as it was, to be tried again
+        let (display, errors) = recover(&mut recovery, &mut factory);
+        assert!(display.is_none());
+        assert!(matches!(errors[..], [WindowRuntimeError::ContextRecreation(WindowCreationError::NoSupportedBackend)]));
+        assert!(recovery.is_needed());
+        assert!(Rc::strong_count(&old) > 1);
+
+        let (display, errors) = recover(&mut recovery, &mut factory);
+        let new = display.expect("the retry should create a new context");
+        assert!(!recovery.is_needed());
+        assert!(matches!(errors[..], [WindowRuntimeError::TextureReload(TextureLoadError::Io(_))]));
+        assert_eq!(textures.borrow().len(), 2);
+
+        //Nothing more happens until the context is lost again
+        let (display, errors) = recover(&mut recovery, &mut factory);
+        assert!(display.is_none() && errors.is_empty());
+
+        let object = &scenes.get_top().unwrap().objects()[0];
+        assert!(object.meshes[0].1.shader.get_program().is_none());
+        assert!(cached_vertices.upgrade().is_none());
+
+        //Every texture moved to the new context, including the one that couldn't be reloaded
+        assert_eq!(kept.get_dimensions(), (4, 2));
+        assert_eq!(broken.get_dimensions(), (3, 5));
+        assert_eq!(Rc::strong_count(&old), 1);
+        assert!(Rc::strong_count(&new) > 1);
+    }
+
+    const EPSILON: f32 = 1e-6;
+
+    fn assert_viewport_near(a: Viewport, b: Viewport) {
+        assert!([a.x - b.x, a.y - b.y, a.width - b.width, a.height - b.height].iter().all(|d| d.abs() < EPSILON), "{:?} != {:?}", a, b);
+    }
+
+    const WIDE: (u32, u32) = (1600, 900);
+    const TALL: (u32, u32) = (900, 1600);
+    const ZERO: (u32, u32) = (0, 0);
+
+    #[test]
+    fn scaling_policy_viewports() {
+        for policy in [ScalingPolicy::Expand, ScalingPolicy::Stretch(800, 600), ScalingPolicy::Letterbox(800, 0)] {
+            for surface in [WIDE, TALL, ZERO] {
+                assert_viewport_near(policy.get_viewport(surface), Viewport::FULL);
+            }
+        }
+
+        //A 4:3 picture is 1200 pixels wide in a 1600 by 900 window, and 675 pixels tall in a 900 by 1600 one
+        let letterbox = ScalingPolicy::Letterbox(800, 600);
+        assert_viewport_near(letterbox.get_viewport(WIDE), Viewport::new(0.125, 0.0, 0.75, 1.0));
+        assert_viewport_near(letterbox.get_viewport(TALL), Viewport::new(0.0, 0.2890625, 1.0, 0.421875));
+        assert_viewport_near(letterbox.get_viewport(ZERO), Viewport::FULL);
+    }
+
+    #[test]
+    fn scaling_policy_aspect_ratios() {
+        let aspect_ratio = |surface: (u32, u32)| surface.1 as f32 / surface.0 as f32;
+        for policy in [ScalingPolicy::Expand, ScalingPolicy::Letterbox(800, 600), ScalingPolicy::Stretch(0, 600)] {
+            for surface in [WIDE, TALL] {
+                assert_eq!(policy.adjust_aspect_ratio(aspect_ratio(surface), surface), aspect_ratio(surface), "{:?}", policy);
+            }
+        }
+
+        //Stretching always shows the design resolution's aspect ratio
+        let stretch = ScalingPolicy::Stretch(800, 600);
+        for surface in [WIDE, TALL] {
+            assert!((stretch.adjust_aspect_ratio(aspect_ratio(surface), surface) - 0.75).abs() < EPSILON);
+        }
+        //A minimised window has no size to stretch from
+        assert_eq!(stretch.adjust_aspect_ratio(1.0, ZERO), 1.0);
+    }
+
+    #[test]
+    fn cursor_view_follows_resize() {
+        let scene = Scene::new(Box::new(SceneCallbackDefault {}), Renderable::Camera(Camera::new(Transform::origin(), 3.0)));
+        let policy = ScalingPolicy::Letterbox(1, 1);
+        let cursor = Some((10.0, 50.0));
+
+        //In a wide window the cursor is over the bar on the left
+        assert_eq!(find_cursor_view(Some(&scene), cursor, policy, (200, 100)), None);
+        //Once the window is square there are no bars, so the same position is over the camera
+        let hit = find_cursor_view(Some(&scene), cursor, policy, (100, 100)).unwrap();
+        assert!(hit.path.is_empty());
+        assert!((hit.position.0 - 0.1).abs() < EPSILON && (hit.position.1 - 0.5).abs() < EPSILON, "{:?}", hit);
+
+        assert_eq!(find_cursor_view(Some(&scene), cursor, policy, ZERO), None);
+        assert_eq!(find_cursor_view(Some(&scene), None, policy, (100, 100)), None);
+        assert_eq!(find_cursor_view(None, cursor, policy, (100, 100)), None);
+    }
+
+    ///Records the resolution each resize was given
+    #[derive(Debug)]
+    struct ResizeRecorder(Rc<Cell<Option<(u32, u32)>>>);
+
+    impl SceneCallback for ResizeRecorder {
+        fn on_resize(&mut self, _scene: &mut Scene, frame: &Frame) {
+            self.0.set(Some(frame.display.resolution));
+        }
+    }
+
+    #[test]
+    fn scene_resize_sees_new_resolution() {
+        let resolution = Rc::new(Cell::new(None));
+        let mut scene = Scene::new(Box::new(ResizeRecorder(resolution.clone())), Renderable::Camera(Camera::new(Transform::origin(), 3.0)));
+        scene.resize(&Frame::headless((640, 480)));
+        assert_eq!(resolution.get(), Some((640, 480)));
+    }
+}
@@ -0,0 +1,44 @@
+use engine::{GameObjectCallback, GameObject, Scene, Quat, Vec3};
+
+///Component that moves an object up and down around where it started
+#[derive(Debug)]
+pub struct Bob {
+    ///How far the object moves above and below its start
+    pub height: f64,
+    ///How fast the object bobs, in radians per second
+    pub speed: f64,
+    ///Seconds since the object was loaded
+    time: f64,
+    ///Where the object was when it was loaded
+    start: Vec3,
+}
+
+impl Bob {
+    pub fn new(height: f64, speed: f64) -> Self {
+        Bob { height, speed, time: 0.0, start: Vec3::ZERO }
+    }
+}
+
+impl GameObjectCallback for Bob {
+    fn on_load(&mut self, object: &mut GameObject, _scene: &mut Scene) {
+        self.start = object.transform.get_position();
+    }
+
+    fn on_tick(&mut self, object: &mut GameObject, frame: &engine::Frame) {
+        self.time += frame.time.delta_time.as_secs_f64();
+        object.transform.set_position(self.start + Vec3::new(0.0, (self.time * self.speed).sin() * self.height, 0.0));
+    }
+}
+
+///Component that turns an object around the y axis
+#[derive(Debug)]
+pub struct Spin {
+    ///How fast the object turns, in radians per second
+    pub speed: f64,
+}
+
+impl GameObjectCallback for Spin {
+    fn on_tick(&mut self, object: &mut GameObject, frame: &engine::Frame) {
+        object.transform.rotate(Quat::from_euler(0.0, self.speed * frame.time.delta_time.as_secs_f64(), 0.0));
+    }
+}
@@ -0,0 +1,91 @@
+use std::{rc::Rc, cell::RefCell};
+
+use engine::{Window, WindowCallback, SceneCallback, Scene, Renderable, Camera, Transform, GameObject, Mesh, KeyCode, Transition, shaders::{TextureOnly2D, Unshaded2D, BlendMode}};
+
+use crate::wheels::{GameState, Screen, STARTING_BALANCE};
+use crate::behaviours::{Bob, Spin};
+
+///Sets how long it takes to fade to the game over screen
+const GAME_OVER_FADE_TIME: f64 = 1.5;
+///Sets how long it takes to blend back to the machine after restarting
+const RESTART_FADE_TIME: f64 = 0.75;
+///Key that restarts the game from the game over screen
+const RESTART_KEY: KeyCode = KeyCode::Space;
+///Key that closes the window from the game over screen
+const QUIT_KEY: KeyCode = KeyCode::Escape;
+///Sets how fast the skull bobs up and down, in radians per second
+const SKULL_BOB_SPEED: f64 = 2.0;
+///Sets how far the skull bobs up and down
+const SKULL_BOB_HEIGHT: f64 = 0.25;
+///Sets how fast the skull turns, in radians per second
+const SKULL_SPIN_SPEED: f64 = 1.5;
+
+///Scene drawn over the paused machine when the balance runs out
+#[derive(Debug)]
+pub struct GameOverScene {}
+
+impl SceneCallback for GameOverScene {
+    fn on_insert(&mut self, scene: &mut Scene, window: &mut Window) {
+        //the skull was loaded with the other fruits
+        let skull_handle = window.assets_mut().load_texture("fruits/skull.png");
+        window.wait_for_assets();
+        let skull_texture = window.assets().get(skull_handle).unwrap().clone();
+
+        //darken the machine behind the skull
+        let mut backdrop = GameObject::new(None, "Backdrop".to_string(), Transform::from_pos(0.0, 0.0, -5.0));
+        backdrop.meshes.push((Transform::from_scale(20.0, 20.0, 1.0), Mesh::plane(true, Box::new(Unshaded2D::new([0.0, 0.0, 0.0, 0.7]).with_blend_mode(BlendMode::Alpha)))));
+        scene.add_object(backdrop);
+
+        let mut skull = GameObject::new(None, "Skull".to_string(), Transform::from_pos(0.0, 0.0, -4.0))
+            .with_component(Box::new(Bob::new(SKULL_BOB_HEIGHT, SKULL_BOB_SPEED)))
+            .with_component(Box::new(Spin { speed: SKULL_SPIN_SPEED }));
+        skull.meshes.push((Transform::from_scale(2.0, 2.0, 1.0), Mesh::plane(true, Box::new(TextureOnly2D::new(skull_texture).with_blend_mode(BlendMode::Alpha)))));
+        scene.add_object(skull);
+    }
+
+    fn on_remove(&mut self, _scene: &mut Scene, window: &mut Window) {
+        //the machine is the top scene again once this scene has left
+        if let Some(machine) = window.get_scene_mut() {
+            machine.set_paused(false);
+        }
+    }
+}
+
+///Window callback that shows the game over screen on Loss, then restarts or quits from it
+#[derive(Debug)]
+pub struct GameOverWatcher {
+    pub state: Rc<RefCell<GameState>>,
+    ///Whether the game over screen has been pushed
+    pub showing: bool,
+}
+
+impl WindowCallback for GameOverWatcher {
+    fn on_tick(&mut self, window: &mut Window, frame: &engine::Frame) {
+        //wait for the fades to finish before reacting again
+        if window.is_changing_scene() {
+            return;
+        }
+
+        if !self.showing {
+            if let Screen::Loss = self.state.borrow().screen {
+                //freeze the machine under the game over screen
+                if let Some(machine) = window.get_scene_mut() {
+                    machine.set_paused(true);
+                }
+                let camera = Camera::new(Transform::from_scale(3.5, 3.5, 3.5), 3.0);
+                window.push_scene(Scene::new(Box::new(GameOverScene {}), Renderable::Camera(camera)), Transition::fade(GAME_OVER_FADE_TIME));
+                self.showing = true;
+            }
+        }
+        else if frame.input.is_key_pressed_this_frame(RESTART_KEY) {
+            let state = &mut *self.state.borrow_mut();
+            state.balance = STARTING_BALANCE;
+            state.screen = Screen::Scroll;
+            window.pop_scene(Transition::Crossfade { duration: RESTART_FADE_TIME });
+            self.showing = false;
+        }
+        else if frame.input.is_key_pressed_this_frame(QUIT_KEY) {
+            window.close();
+        }
+    }
+}
@@ -3,10 +3,13 @@ extern crate engine;
 
 mod wheels;
 mod numbers;
-use std::{cell::RefCell, rc::Rc};
+mod gameover;
+mod behaviours;
+use std::{cell::RefCell, rc::Rc, path::PathBuf};
 
-use engine::{Window, Resolution, Camera, Transform, shaders::Texture, Scene, Renderable};
+use engine::{Window, Resolution, Camera, Transform, shaders::Texture, Scene, Renderable, AssetPack};
 use wheels::*;
+use gameover::GameOverWatcher;
 
 const FRUIT_PATHS: [&str; 6] = [
     "bell.webp",
@@ -28,36 +31,88 @@ const NUMBER_PATHS: [&str; 10] = [
     "8.png",
     "9.png",
 ];
+const SOUND_PATHS: [&str; 3] = [
+    "lever.wav",
+    "tick.wav",
+    "win.wav",
+];
+
+///Adds resource files to a pack, embedding them in the executable
+macro_rules! embed {
+    ($pack:expr, $($path:literal),* $(,)?) => {
+        $( $pack.insert($path, include_bytes!(concat!("resources/", $path))); )*
+    };
+}
+
+///Creates a pack of every resource the game uses
+///Used when the resources directory isn't there, so that the executable runs on its own
+fn embedded_assets() -> AssetPack {
+    let mut pack = AssetPack::new();
+    embed!(pack,
+        "icon.webp",
+        "fruits/bell.webp", "fruits/cherry.webp", "fruits/lemon.png",
+        "fruits/orange.png", "fruits/skull.png", "fruits/star.webp",
+        "numbers/0.png", "numbers/1.png", "numbers/2.png", "numbers/3.png", "numbers/4.png",
+        "numbers/5.png", "numbers/6.png", "numbers/7.png", "numbers/8.png", "numbers/9.png",
+        "objects/back.obj", "objects/cylinder.obj", "objects/sphere.obj",
+        "sounds/lever.wav", "sounds/tick.wav", "sounds/win.wav",
+        "scenes/main.json",
+    );
+    pack
+}
 
 fn main() {
 
     //Ititialise shared state
     let state = Rc::new( RefCell::new( GameState {
-        balance: 100,
+        balance: STARTING_BALANCE,
         screen: Screen::Scroll,
     }));
 
     //Create window and camera
-    let mut window = Window::new(Some(Box::new(QuitWatcher{state: state.clone()})), Resolution::Physical(1920, 1080), "Fruit Machine".to_string()).unwrap();
-    window.set_icon("src/resources/icon.webp".to_string()).unwrap();
+    let mut window = Window::new(Some(Box::new(GameOverWatcher{state: state.clone(), showing: false})), Resolution::Physical(1920, 1080), "Fruit Machine".to_string()).unwrap();
+    //reload art from disk while it is being edited, if the game is run on the machine it was built on
+    let source_resources = PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/src/resources"));
+    if cfg!(debug_assertions) && source_resources.is_dir() {
+        window.assets_mut().set_root(source_resources);
+        window.assets_mut().set_hot_reload(true);
+    }
+    //ship as a single executable
+    else {
+        window.assets_mut().mount(embedded_assets());
+    }
+
+    let icon = window.assets().read("icon.webp").unwrap();
+    window.set_icon_from_bytes(&icon).unwrap();
 
     let mut main_camera = Camera::new(Transform::from_scale(3.5, 3.5, 3.5), 3.0);
     main_camera.set_clear_colour(Some([1.0, 1.0, 1.0, 0.0]));
 
     //load textures from files
-    let fruit_textures: [Texture; 6] = FRUIT_PATHS.map(|p| {
-        Texture::new("src/resources/fruits/".to_string() + p, &window).unwrap()
+    let fruit_handles = FRUIT_PATHS.map(|p| {
+        window.assets_mut().load_texture(&("fruits/".to_string() + p))
+    });
+    let number_handles = NUMBER_PATHS.map(|p| {
+        window.assets_mut().load_texture(&("numbers/".to_string() + p))
     });
-    let number_textures: [Texture; 10] = NUMBER_PATHS.map(|p| {
-        Texture::new("src/resources/numbers/".to_string() + p, &window).unwrap()
+    //load sounds from files
+    let sound_handles = SOUND_PATHS.map(|p| {
+        window.assets_mut().load_sound(&("sounds/".to_string() + p))
     });
+    window.wait_for_assets();
+    let fruit_textures: [Texture; 6] = fruit_handles.map(|h| window.assets().get(h).unwrap().clone());
+    let number_textures: [Texture; 10] = number_handles.map(|h| window.assets().get(h).unwrap().clone());
+    let [lever_sound, tick_sound, win_sound] = sound_handles.map(|h| window.assets().get(h).unwrap().clone());
 
     //Create scene
     window.set_scene(Scene::new(
         Box::new( MainScene {
-            state: state,
+            state,
             fruit_textures,
             number_textures,
+            lever_sound,
+            tick_sound,
+            win_sound,
         }), 
         Renderable::Camera(main_camera)
     ));
@@ -22,19 +22,21 @@ impl GameObjectCallback for BalanceIndicatorDigit {
         let balance = self.state.borrow().balance;
         //only change if balance has changed
         if balance != self.balance_current {
-            let digit = balance % ((10 as u32).pow(self.digit_no)) / (10 as u32).pow(self.digit_no - 1);
-            let prev_digit = self.balance_current % ((10 as u32).pow(self.digit_no)) / (10 as u32).pow(self.digit_no - 1);
+            let digit = balance % (10u32.pow(self.digit_no)) / 10u32.pow(self.digit_no - 1);
+            let prev_digit = self.balance_current % (10u32.pow(self.digit_no)) / 10u32.pow(self.digit_no - 1);
             //only change if this digit has changed
             if digit != prev_digit {
-                object.meshes[digit as usize].0 = Transform::from_pos(0.0, 0.0, 0.0);
-                object.meshes[prev_digit as usize].0 = Transform::from_pos(0.0, 10.0, 0.0);
+                object.meshes[digit as usize].1.visible = true;
+                object.meshes[prev_digit as usize].1.visible = false;
             }
             self.balance_current = balance;
         }
     }
     fn on_load(&mut self, object: &mut GameObject, _scene: &mut Scene) {
         for texture in self.textures.iter() {
-            object.meshes.push((Transform::from_pos(0.0, 10.0, 0.0), Mesh::plane(true, Box::new(TextureOnly2D::new(texture.clone())))));
+            let mut mesh = Mesh::plane(true, Box::new(TextureOnly2D::new(texture.clone())));
+            mesh.visible = false;
+            object.meshes.push((Transform::origin(), mesh));
         }
     }
 }
\ No newline at end of file
@@ -1,18 +1,18 @@
 use std::{rc::Rc, cell::RefCell, f64::consts::PI};
 
-use engine::{Window, GameObjectCallback, SceneCallback, Scene, Transform, GameObject, Mesh, shaders::{Texture, TextureOnly2D, Unshaded2D, Unshaded3D}, KeyCode, WindowCallback};
+use engine::{Window, GameObjectCallback, SceneCallback, Scene, Transform, GameObject, Mesh, shaders::{Texture, TextureOnly2D}, KeyCode, Sound, PlayParams, Vec3, ParticleEmitter, ParticleSettings, DebugProjection, SceneDescription, CallbackRegistry};
 
 extern crate rand;
 use rand::Rng;
 
 use super::numbers::*;
 
+///Sets the balance at the start of a game
+pub const STARTING_BALANCE: u32 = 100;
 ///Sets how long the wheels spin at full speed before decelerating
 const SPIN_TIME: f64 = 4.0;
 ///Sets how fast the wheels spin during scroll
 const WHEEL_SPEEDS_SCROLL: [f64; 3] = [1.0, 1.2, 1.4];
-///Sets how far the wheels are apart horizontally
-const WHEEL_SPACING_X: f64 = 2.25;
 ///Sets how far the wheels are apart vertically
 const WHEEL_SPACING_Y: f64 = 2.1;
 ///Sets how fast the wheels spin at the start of a spin
@@ -21,6 +21,18 @@ const WHEEL_SPEEDS_SPIN: [f64; 3] = [35.0, 35.0, 35.0];
 const WHEEL_DECEL_SPIN: [f64; 3] = [3.0, 2.5, 2.0];
 ///The range of possible speeds wheels can stop spinning at
 const LOWER_SPEED_RANGE: [f64; 2] = [2.5, 4.0];
+///Sets the pitch of each wheel's tick sound
+const WHEEL_TICK_PITCHES: [f32; 3] = [1.0, 1.12, 1.25];
+///Sets the volume of the wheels' tick sounds
+const WHEEL_TICK_VOLUME: f32 = 0.4;
+///Scene file with the wheels, balance digits and lever
+const MAIN_SCENE_PATH: &str = "scenes/main.json";
+///Sets how many coins burst out on a jackpot
+const JACKPOT_COINS: usize = 80;
+///Key that shows the wheels' velocities and targets
+const WHEEL_DEBUG_KEY: KeyCode = KeyCode::F4;
+///Sets how long the wheels' velocity rays are, in seconds of movement
+const WHEEL_DEBUG_RAY_TIME: f64 = 0.05;
 
 ///Matches textures to fruits
 const FRUITS: [Fruit; 6] = [
@@ -59,7 +71,7 @@ pub enum Screen {
     ///A state active when wheels are stopped before returning to scroll
     //stores how long to wait for before scrolling again
     Wait(f64),
-    ///A state to signal to the QuitWatcher that the window should be closed
+    ///A state to signal to the GameOverWatcher that the game over screen should be shown
     Loss
 }
 
@@ -96,6 +108,46 @@ pub struct WheelObject {
     pub game_state: Rc<RefCell<GameState>>,
     ///Textures (only used during initialisation)
     pub fruit_textures: [Texture; 6],
+    ///Sound played when a fruit passes the centre
+    pub tick_sound: Sound,
+    ///Whether the wheel's velocity and target are drawn
+    pub show_debug: bool,
+}
+
+impl WheelObject {
+    ///Plays a tick if a fruit has passed the centre while the wheels are spinning
+    fn tick_if_passed(&self, frame: &engine::Frame, y_before: f64, y_after: f64) {
+        if let Screen::Scroll | Screen::Wait(_) = self.game_state.borrow().screen {
+            return;
+        }
+        if y_before >= 0.0 && y_after < 0.0 {
+            frame.audio.play(&self.tick_sound, PlayParams {
+                pitch: WHEEL_TICK_PITCHES[self.wheel_number],
+                volume: WHEEL_TICK_VOLUME,
+                ..Default::default()
+            });
+        }
+    }
+
+    ///Draws each fruit's velocity, the line fruits stop on, and the fruit the wheel is stopping at
+    fn draw_debug(&self, object: &GameObject, frame: &engine::Frame) {
+        //The wheels use 2D shaders
+        let debug = frame.debug.with_projection(DebugProjection::Orthographic);
+        let centre = object.transform.get_position();
+
+        debug.line(centre + Vec3::new(-1.0, 0.0, 0.0), centre + Vec3::new(1.0, 0.0, 0.0), [1.0, 0.0, 0.0, 1.0]);
+        for mesh in &object.meshes {
+            let position = object.transform.transform_point(mesh.0.get_position());
+            debug.ray(position, Vec3::new(0.0, -self.velocity * WHEEL_DEBUG_RAY_TIME, 0.0), [0.0, 0.8, 0.0, 1.0]);
+        }
+        if let WheelState::Stopping(target) = self.wheel_state {
+            if let Some(bounds) = object.get_mesh_bounds(target) {
+                debug.wire_box(&bounds, [1.0, 0.8, 0.0, 1.0]);
+                debug.text(bounds.max, &format!("Y {:.2}", object.meshes[target].0.get_pos().1), [1.0, 0.8, 0.0, 1.0]);
+            }
+        }
+        debug.text(centre + Vec3::new(-1.0, WHEEL_SPACING_Y * 1.5, 0.0), &format!("V {:.2}", self.velocity), [0.0, 0.8, 0.0, 1.0]);
+    }
 }
 
 impl GameObjectCallback for WheelObject {
@@ -130,24 +182,28 @@ impl GameObjectCallback for WheelObject {
                 }
                 //move all meshes down by self.velocity and reset to top if far enough down
                 for mesh in &mut object.meshes {
-                    mesh.0 = mesh.0.clone() * Transform::from_pos(0.0,-self.velocity * frame.time.delta_time.as_secs_f64(), 0.0);
+                    let y_before = mesh.0.get_pos().1;
+                    mesh.0.translate(Vec3::new(0.0, -self.velocity * frame.time.delta_time.as_secs_f64(), 0.0));
+                    self.tick_if_passed(frame, y_before, mesh.0.get_pos().1);
                     if mesh.0.get_pos().1 < -WHEEL_SPACING_Y * 3.0 {
-                        mesh.0 = mesh.0.clone() * Transform::from_pos(0.0, WHEEL_SPACING_Y * 6.0, 0.0)
+                        mesh.0.translate(Vec3::new(0.0, WHEEL_SPACING_Y * 6.0, 0.0))
                     }
                 }
             },
             WheelState::Stopping(m) => {
                 //move all meshes down and wrap
                 for (i, mesh) in &mut object.meshes.iter_mut().enumerate() {
-                    mesh.0 = mesh.0.clone() * Transform::from_pos(0.0,-self.velocity * frame.time.delta_time.as_secs_f64(), 0.0);
+                    let y_before = mesh.0.get_pos().1;
+                    mesh.0.translate(Vec3::new(0.0, -self.velocity * frame.time.delta_time.as_secs_f64(), 0.0));
                     let y = mesh.0.get_pos().1;
+                    self.tick_if_passed(frame, y_before, y);
                     if y < -WHEEL_SPACING_Y * 3.0 {
-                        mesh.0 = mesh.0.clone() * Transform::from_pos(0.0, WHEEL_SPACING_Y * 6.0, 0.0)
+                        mesh.0.translate(Vec3::new(0.0, WHEEL_SPACING_Y * 6.0, 0.0))
                     }
                     //check if target fruit has reached centre, and if so save it in shared state
                     if i == m && y < 0.0 {
                         self.wheel_state = WheelState::Stopped;
-                        mesh.0 = mesh.0.clone() * Transform::from_pos(0.0, -y, 0.0);
+                        mesh.0.translate(Vec3::new(0.0, -y, 0.0));
                         let gs = &mut *self.game_state.borrow_mut();
                         if let Screen::Decel(mut t) = gs.screen {
                             t[self.wheel_number] = Some(FRUITS[i]);
@@ -164,6 +220,13 @@ impl GameObjectCallback for WheelObject {
                 }
             }
         }
+
+        if frame.input.is_key_pressed_this_frame(WHEEL_DEBUG_KEY) {
+            self.show_debug = !self.show_debug;
+        }
+        if self.show_debug {
+            self.draw_debug(object, frame);
+        }
     }
     fn on_load(&mut self, object: &mut GameObject, _scene: &mut Scene) {
         //start with a random fruit
@@ -180,6 +243,7 @@ impl GameObjectCallback for WheelObject {
 #[derive(Debug)]
 pub struct LeverObject {
     ///Shared game state
+    ///The lever's back, handle and sphere meshes come from the scene file
     pub state: Rc<RefCell<GameState>>,
 }
 
@@ -190,41 +254,6 @@ const LEVER_UP_TIME: f64 = 0.5;
 
 
 impl GameObjectCallback for LeverObject {
-    fn on_load(&mut self, object: &mut GameObject, _scene: &mut Scene) {
-        //add mesh for the grey part (back of the lever)
-        object.meshes.push((
-            Transform::origin(),
-            Mesh::from_obj(
-                "src/resources/objects/back.obj", 
-                Box::new(
-                    Unshaded3D::new([0.3, 0.3, 0.3, 0.0])
-                )
-            ).unwrap()
-        ));
-
-        //add mesh for the tan part (handle of the lever)
-        object.meshes.push((
-            Transform::origin(),
-            Mesh::from_obj(
-                "src/resources/objects/cylinder.obj", 
-                Box::new(
-                    Unshaded3D::new([0.8, 0.4, 0.2, 0.0])
-                )
-            ).unwrap()
-        ));
-
-        //add mesh for the brown part (sphere on the end of lever)
-        object.meshes.push((
-            Transform::origin(),
-            Mesh::from_obj(
-                "src/resources/objects/sphere.obj", 
-                Box::new(
-                    Unshaded3D::new([0.5, 0.2, 0.0, 0.0])
-                )
-            ).unwrap()
-        ));
-    }
-
     fn on_tick(&mut self, object: &mut GameObject, _frame: &engine::Frame) {
         if let Screen::Spin(d) = self.state.borrow().screen {
             //the final rotation of the lever
@@ -247,8 +276,8 @@ impl GameObjectCallback for LeverObject {
 
             //set meshes' rotation based on calculated rotation
             let t = Transform::from_euler(r, 0.0, 0.0);
-            object.meshes[1].0 = t.clone();
-            object.meshes[2].0 = t.clone();
+            object.meshes[1].0 = t;
+            object.meshes[2].0 = t;
         }
     }
 }
@@ -263,6 +292,12 @@ pub struct MainScene {
     pub fruit_textures: [Texture; 6],
     ///Number textures for passing to digit objects
     pub number_textures: [Texture; 10],
+    ///Sound played when the lever is pulled
+    pub lever_sound: Sound,
+    ///Sound played when a fruit passes the centre of a wheel
+    pub tick_sound: Sound,
+    ///Sound played when a spin wins money
+    pub win_sound: Sound,
 }
 
 ///Helper function to try to subtract y from x
@@ -297,16 +332,53 @@ fn score_spin(fs: [Fruit; 3], balance: u32) -> Option<u32> {
     i.sort();
     i.dedup();
     match i.len() {
-        1 => return Some(balance + 100),
-        2 => return Some(balance + 50),
-        _ => return Some(balance)
+        1 => Some(balance + 100),
+        2 => Some(balance + 50),
+        _ => Some(balance)
+    }
+}
+
+impl MainScene {
+    ///Creates the factories for the callbacks used in the scene file
+    fn callback_registry(&self) -> CallbackRegistry {
+        let mut registry = CallbackRegistry::new();
+
+        let (state, fruit_textures, tick_sound) = (self.state.clone(), self.fruit_textures.clone(), self.tick_sound.clone());
+        registry.register("Wheel", move |parameters| {
+            let wheel_number = parameters["number"].as_u64().filter(|n| *n < 3).ok_or("number should be 0, 1 or 2")? as usize;
+            Ok(Box::new(WheelObject {
+                velocity: WHEEL_SPEEDS_SCROLL[wheel_number],
+                wheel_number,
+                game_state: state.clone(),
+                fruit_textures: fruit_textures.clone(),
+                tick_sound: tick_sound.clone(),
+                wheel_state: WheelState::Stopped,
+                show_debug: false,
+            }))
+        });
+
+        let (state, number_textures) = (self.state.clone(), self.number_textures.clone());
+        registry.register("BalanceDigit", move |parameters| {
+            let digit_no = parameters["digit"].as_u64().filter(|d| *d >= 1).ok_or("digit should be 1 or more")? as u32;
+            Ok(Box::new(BalanceIndicatorDigit {
+                state: state.clone(),
+                balance_current: 999999999,
+                digit_no,
+                textures: number_textures.clone(),
+            }))
+        });
+
+        let state = self.state.clone();
+        registry.register("Lever", move |_| Ok(Box::new(LeverObject { state: state.clone() })));
+
+        registry
     }
 }
 
 impl SceneCallback for MainScene {
-    fn on_tick(&mut self, _scene: &mut Scene, frame: &engine::Frame) {
+    fn on_tick(&mut self, scene: &mut Scene, frame: &engine::Frame) {
         //stores what the shared screen state will be set to after the function
-        let mut end_state = self.state.borrow().screen.clone();
+        let mut end_state = self.state.borrow().screen;
         //stores what the shared balance state will be set to after the function
         let mut end_balance = self.state.borrow().balance;
         match end_state {
@@ -315,6 +387,7 @@ impl SceneCallback for MainScene {
                 if frame.input.is_key_pressed_this_frame(KeyCode::Space) {
                     end_state = Screen::SpinStart;
                     end_balance -= 20;
+                    frame.audio.play_one_shot(&self.lever_sound);
                 }
             },
             Screen::SpinStart => end_state = Screen::Spin(SPIN_TIME),
@@ -324,17 +397,24 @@ impl SceneCallback for MainScene {
                     end_state = Screen::Decel([None, None, None]);
                 }
             },
-            Screen::Decel(fs) => match fs {
-                //if all three wheels are stopped, calculate new balance
-                [Some(f1), Some(f2), Some(f3)] => {
-                    end_state = Screen::Wait(1.5);
-                    match score_spin([f1, f2, f3], end_balance) {
-                        Some(b) if b >= 20 => end_balance = b,
-                        _ => end_state = Screen::Loss
-                    }
-                },
-                _ => {}
+            //if all three wheels are stopped, calculate new balance
+            Screen::Decel([Some(f1), Some(f2), Some(f3)]) => {
+                end_state = Screen::Wait(1.5);
+                match score_spin([f1, f2, f3], end_balance) {
+                    Some(b) if b >= 20 => {
+                        if b > end_balance {
+                            frame.audio.play_one_shot(&self.win_sound);
+                        }
+                        //burst coins on a jackpot
+                        if [f1, f2, f3] == [Fruit::Bell, Fruit::Bell, Fruit::Bell] {
+                            scene.alter_object_by_name("Coins".to_string(), Box::new(|o| o.emitters[0].burst(JACKPOT_COINS)));
+                        }
+                        end_balance = b;
+                    },
+                    _ => end_state = Screen::Loss
+                }
             },
+            Screen::Decel(_) => {},
             Screen::Wait(d) => {
                 end_state = Screen::Wait(d - frame.time.delta_time.as_secs_f64());
                 if d < 0.0 {
@@ -349,119 +429,25 @@ impl SceneCallback for MainScene {
         state.balance = end_balance;
     }
 
-    fn on_insert(&mut self, scene: &mut Scene, _window: &mut Window) {
-        
-        //add left wheel
-        scene.add_object( 
-            GameObject::new( Some( Box::new( WheelObject {
-                velocity: WHEEL_SPEEDS_SCROLL[0], 
-                wheel_number: 0, 
-                game_state: self.state.clone(), 
-                fruit_textures: self.fruit_textures.clone(), 
-                wheel_state: WheelState::Stopped
-            })), 
-            "Wheel1".to_string(), 
-            Transform::from_pos(-WHEEL_SPACING_X, 0.0, 0.0))
-        );
-        //add middle wheel
-        scene.add_object( 
-            GameObject::new( Some( Box::new( WheelObject {
-                velocity: WHEEL_SPEEDS_SCROLL[1], 
-                wheel_number: 1, 
-                game_state: self.state.clone(), 
-                fruit_textures: self.fruit_textures.clone(), 
-                wheel_state: WheelState::Stopped
-            })), 
-            "Wheel2".to_string(), 
-            Transform::from_pos(0.0, 0.0, 0.0))
-        );
-        //add right wheel
-        scene.add_object( 
-            GameObject::new( Some( Box::new( WheelObject {
-                velocity: WHEEL_SPEEDS_SCROLL[2], 
-                wheel_number: 2, 
-                game_state: self.state.clone(), 
-                fruit_textures: self.fruit_textures.clone(), 
-                wheel_state: WheelState::Stopped
-            })), 
-            "Wheel3".to_string(), 
-            Transform::from_pos(WHEEL_SPACING_X, 0.0, 0.0))
-        );
-        
-        //add black box at top of screen
-        let mut top_box = GameObject::new(
-            None, 
-            "TopBox".to_string(),
-            Transform::from_scale(10.0, 4.0, 1.0) * Transform::from_pos(0.0, 6.0, -1.0)
-        );
-        top_box.meshes.push((Transform::origin(), Mesh::plane(true, Box::new(Unshaded2D::new([0.0, 0.0, 0.0, 1.0])))));
-        scene.add_object(top_box);
-
-        //add £10s digit
-        scene.add_object(GameObject::new( Some( Box::new( 
-            BalanceIndicatorDigit {
-                state: self.state.clone(),
-                balance_current: 999999999,
-                digit_no: 4,
-                textures: self.number_textures.clone()
-            })),
-        "Digit4".to_string(),
-        Transform::from_scale(0.5, 0.5, 1.0) * Transform::from_pos(-2.0, 2.5, -2.0)
-        ));
-        //add £1s digit
-        scene.add_object(GameObject::new( Some( Box::new( 
-            BalanceIndicatorDigit {
-                state: self.state.clone(),
-                balance_current: 999999999,
-                digit_no: 3,
-                textures: self.number_textures.clone()
-            })),
-        "Digit3".to_string(),
-        Transform::from_scale(0.5, 0.5, 1.0) * Transform::from_pos(-1.0, 2.5, -2.0)
-        ));
-        //add 10ps digit
-        scene.add_object(GameObject::new( Some( Box::new( 
-            BalanceIndicatorDigit {
-                state: self.state.clone(),
-                balance_current: 999999999,
-                digit_no: 2,
-                textures: self.number_textures.clone()
-            })),
-        "Digit2".to_string(),
-        Transform::from_scale(0.5, 0.5, 1.0) * Transform::from_pos(1.0, 2.5, -2.0)
-        ));
-        //add 1ps digit
-        scene.add_object(GameObject::new( Some( Box::new( 
-            BalanceIndicatorDigit {
-                state: self.state.clone(),
-                balance_current: 999999999,
-                digit_no: 1,
-                textures: self.number_textures.clone()
-            })),
-            "Digit1".to_string(),
-            Transform::from_scale(0.5, 0.5, 1.0) * Transform::from_pos(2.0, 2.5, -2.0)
-        ));
-
-        //add lever
-        scene.add_object(GameObject::new( Some( Box::new(
-            LeverObject {
-                state: self.state.clone()
-            })),
-            "Lever".to_string(), 
-            Transform::from_scale(1.0, 1.0, 1.0) * Transform::from_euler(0.0, -0.5, 0.0) * Transform::from_pos(15.0, -3.0, 20.0)
-        ));
+    fn on_insert(&mut self, scene: &mut Scene, window: &mut Window) {
+        //add the wheels, balance digits and lever from the scene file
+        SceneDescription::open(MAIN_SCENE_PATH, window).unwrap()
+            .add_to(scene, &self.callback_registry(), window).unwrap();
+
+        //add coins that burst out on a jackpot
+        let mut coins = GameObject::new(None, "Coins".to_string(), Transform::from_pos(0.0, -1.0, -3.0));
+        let mut coin_emitter = ParticleEmitter::new(ParticleSettings {
+            max_particles: JACKPOT_COINS,
+            lifetime: (1.5, 2.5),
+            velocity: Vec3::new(0.0, 6.0, 0.0),
+            velocity_spread: Vec3::new(4.0, 2.0, 0.0),
+            gravity: Vec3::new(0.0, -9.8, 0.0),
+            size: (0.3, 0.2),
+            colour: ([1.0, 0.9, 0.3, 1.0], [1.0, 0.9, 0.3, 0.0]),
+            ..Default::default()
+        });
+        coin_emitter.emitting = false;
+        coins.add_emitter(coin_emitter, Mesh::plane(true, Box::new(TextureOnly2D::new(self.fruit_textures[5].clone()))));
+        scene.add_object(coins);
     }
 }
-
-///Struct to close window on Loss
-#[derive(Debug)]
-pub struct QuitWatcher {
-    pub state: Rc<RefCell<GameState>>,
-}
-impl WindowCallback for QuitWatcher {
-    fn on_tick(&mut self, window: &mut Window, _frame: &engine::Frame) {
-        if let Screen::Loss = self.state.borrow().screen{
-            window.close();
-        }
-    }
-}
\ No newline at end of file